@@ -0,0 +1,459 @@
+use ast::expression::Expression;
+use context::Context;
+use error::{Result, SassError, ErrorKind};
+use operator::Operator;
+use operator_offset::OperatorOffset;
+use operator_or_token::OperatorOrToken;
+use token::{Token, format_like};
+use token_offset::TokenOffset;
+use tokenizer::Tokenizer;
+
+use std::fmt;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+// `calc()`, `min()`, `max()` or `clamp()`, left for the browser because
+// it can't be worked out yet, like `calc(100% - 10px)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calculation {
+    pub name: String,
+    pub arguments: Vec<CalculationValue>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalculationValue {
+    Number(Token),
+    Operation(Box<CalculationValue>, Operator, Box<CalculationValue>),
+    Calculation(Calculation),
+    // Passed through as written, like `var(--a)`.
+    Raw(String),
+}
+
+impl fmt::Display for Calculation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arguments = self.arguments.iter().map(|a| format_like(f, a)).collect::<Vec<_>>();
+        write!(f, "{}({})", self.name, arguments.join(", "))
+    }
+}
+
+impl fmt::Display for CalculationValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CalculationValue::Number(ref number) => write!(f, "{}", format_like(f, number)),
+            CalculationValue::Operation(ref left, operator, ref right) => {
+                // Parenthesize the sides that wouldn't otherwise go first.
+                let left_parens = precedence(left) < precedence_of(operator);
+                let right_parens = precedence(right) < precedence_of(operator) || (
+                    precedence(right) == precedence_of(operator) &&
+                    (operator == Operator::Minus || operator == Operator::Slash)
+                );
+                write_side(f, left, left_parens)?;
+                write!(f, " {} ", operator)?;
+                write_side(f, right, right_parens)
+            },
+            // A `calc()` in a calculation is just its parentheses.
+            CalculationValue::Calculation(ref calculation) if calculation.name == "calc" => {
+                write!(f, "({})", format_like(f, &calculation.arguments[0]))
+            },
+            CalculationValue::Calculation(ref calculation) => write!(f, "{}", format_like(f, calculation)),
+            CalculationValue::Raw(ref text) => write!(f, "{}", text),
+        }
+    }
+}
+
+fn write_side(f: &mut fmt::Formatter, side: &CalculationValue, parens: bool) -> fmt::Result {
+    let side = format_like(f, side);
+    if parens {
+        write!(f, "({})", side)
+    } else {
+        write!(f, "{}", side)
+    }
+}
+
+fn precedence_of(operator: Operator) -> usize {
+    match operator {
+        Operator::Plus | Operator::Minus => 1,
+        _ => 2,
+    }
+}
+
+fn precedence(value: &CalculationValue) -> usize {
+    match *value {
+        CalculationValue::Operation(_, operator, _) => precedence_of(operator),
+        _ => 3,
+    }
+}
+
+// Whether `name` is a calculation rather than a function call.
+pub fn is_calculation(name: &str) -> bool {
+    matches!(&name.to_lowercase()[..], "calc" | "min" | "max" | "clamp")
+}
+
+impl Calculation {
+    // Works out `name(arguments...)` as far as it can with the values of
+    // the variables in it: a number if it comes to one, otherwise the
+    // calculation.
+    pub fn evaluate(name: &str, arguments: Vec<OperatorOrToken>, offset: Option<usize>, context: &Context)
+                    -> Result<Expression> {
+        let calculation = Calculation::parse(name, arguments, offset, context)?;
+        Ok(match calculation.simplify() {
+            CalculationValue::Number(number) => Expression::Value(OperatorOrToken::Token(TokenOffset {
+                token: number,
+                offset,
+            })),
+            CalculationValue::Calculation(calculation) => Expression::Calculation(calculation),
+            other => Expression::Calculation(Calculation {
+                name: String::from("calc"),
+                arguments: vec![other],
+            }),
+        })
+    }
+
+    // `calc(...)` as the tokenizer keeps it, in one piece.
+    pub fn evaluate_text(text: &str, offset: Option<usize>, context: &Context) -> Result<Expression> {
+        let open = text.find('(').unwrap_or(text.len());
+        let close = if text.ends_with(')') { text.len() - 1 } else { text.len() };
+        let inside = &text[(open + 1).min(close)..close];
+        let start = offset.map(|o| o + open + 1).unwrap_or(0);
+        let tokens = Tokenizer::starting_at(inside, start).collect::<Result<Vec<_>>>()?;
+        Calculation::evaluate(&text[..open], tokens, offset, context)
+    }
+
+    fn parse(name: &str, arguments: Vec<OperatorOrToken>, offset: Option<usize>, context: &Context)
+             -> Result<Calculation> {
+        let name = name.to_lowercase();
+        let mut parsed = vec![];
+        for argument in split_arguments(arguments).into_iter() {
+            let mut tokens = argument.into_iter().peekable();
+            let value = sum(&mut tokens, offset, context)?;
+            if let Some(extra) = tokens.next() {
+                return Err(invalid(&format!("Expected an operator, instead saw `{}`.", extra), extra.offset().or(offset)))
+            }
+            parsed.push(value);
+        }
+
+        let expected = match &name[..] {
+            "calc" => "exactly 1 argument",
+            "clamp" => "exactly 3 arguments",
+            _ => "at least 1 argument",
+        };
+        let right_count = match &name[..] {
+            "calc" => parsed.len() == 1,
+            "clamp" => parsed.len() == 3,
+            _ => !parsed.is_empty(),
+        };
+        if !right_count {
+            return Err(SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ArgumentError,
+                message: format!("{}() takes {}, but {} were passed.", name, expected, parsed.len()),
+                location: None,
+                calls: vec![],
+            })
+        }
+        Ok(Calculation {
+            name,
+            arguments: parsed,
+        })
+    }
+
+    // The number this comes to, if it comes to one, or the calculation
+    // with its arguments simplified.
+    fn simplify(self) -> CalculationValue {
+        let arguments = self.arguments.into_iter().map(|a| a.simplify()).collect::<Vec<_>>();
+        let numbers = arguments.iter().filter_map(|a| match *a {
+            CalculationValue::Number(Token::Number { value, ref units, .. }) => Some((value, units.clone())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let comparable = numbers.len() == arguments.len() &&
+                         numbers.iter().all(|(_, units)| *units == numbers[0].1);
+
+        match &self.name[..] {
+            "calc" => arguments.into_iter().next().unwrap(),
+            "min" | "max" if comparable => {
+                let pick_min = self.name == "min";
+                let mut picked = 0;
+                for (i, &(value, _)) in numbers.iter().enumerate() {
+                    if (pick_min && value < numbers[picked].0) || (!pick_min && value > numbers[picked].0) {
+                        picked = i;
+                    }
+                }
+                arguments.into_iter().nth(picked).unwrap()
+            },
+            "clamp" if comparable => {
+                let (lowest, value, highest) = (numbers[0].0, numbers[1].0, numbers[2].0);
+                let picked = if value < lowest { 0 } else if value > highest { 2 } else { 1 };
+                arguments.into_iter().nth(picked).unwrap()
+            },
+            _ => CalculationValue::Calculation(Calculation {
+                name: self.name,
+                arguments,
+            }),
+        }
+    }
+}
+
+impl CalculationValue {
+    fn simplify(self) -> CalculationValue {
+        match self {
+            CalculationValue::Operation(left, operator, right) => {
+                let (left, right) = (left.simplify(), right.simplify());
+                match (left, right) {
+                    (CalculationValue::Number(l), CalculationValue::Number(r)) => {
+                        match combine(l.clone(), operator, r.clone()) {
+                            Some(number) => CalculationValue::Number(number),
+                            None => CalculationValue::Operation(
+                                Box::new(CalculationValue::Number(l)), operator, Box::new(CalculationValue::Number(r))
+                            ),
+                        }
+                    },
+                    (left, right) => CalculationValue::Operation(Box::new(left), operator, Box::new(right)),
+                }
+            },
+            CalculationValue::Calculation(calculation) => calculation.simplify(),
+            other => other,
+        }
+    }
+}
+
+// Numbers can only be added or subtracted here if they have the same
+// units, since `calc(1 + 1px)` isn't valid CSS.
+fn combine(left: Token, operator: Operator, right: Token) -> Option<Token> {
+    let same_units = match (&left, &right) {
+        (Token::Number { units: l, .. }, Token::Number { units: r, .. }) => l == r,
+        _ => false,
+    };
+    let result = match operator {
+        Operator::Plus if same_units => left + right,
+        Operator::Minus if same_units => left - right,
+        Operator::Star => left * right,
+        Operator::Slash => left / right,
+        _ => return None,
+    };
+    result.ok()
+}
+
+fn split_arguments(tokens: Vec<OperatorOrToken>) -> Vec<Vec<OperatorOrToken>> {
+    let mut arguments = vec![vec![]];
+    let mut paren_level = 0;
+    for token in tokens.into_iter() {
+        match token.extract_operator() {
+            Some(Operator::Comma) if paren_level == 0 => {
+                arguments.push(vec![]);
+                continue;
+            },
+            Some(Operator::LeftParen) => paren_level += 1,
+            Some(Operator::RightParen) => paren_level -= 1,
+            _ => {},
+        }
+        arguments.last_mut().unwrap().push(token);
+    }
+    if arguments.len() == 1 && arguments[0].is_empty() {
+        arguments.pop();
+    }
+    arguments
+}
+
+type Tokens = Peekable<IntoIter<OperatorOrToken>>;
+
+// `a + b - ...`
+fn sum(tokens: &mut Tokens, offset: Option<usize>, context: &Context) -> Result<CalculationValue> {
+    let mut value = product(tokens, offset, context)?;
+    while let Some(operator) = next_operator(tokens, &[Operator::Plus, Operator::Minus]) {
+        let right = product(tokens, offset, context)?;
+        value = CalculationValue::Operation(Box::new(value), operator, Box::new(right));
+    }
+    Ok(value)
+}
+
+// `a * b / ...`
+fn product(tokens: &mut Tokens, offset: Option<usize>, context: &Context) -> Result<CalculationValue> {
+    let mut value = calculation_value(tokens, offset, context)?;
+    while let Some(operator) = next_operator(tokens, &[Operator::Star, Operator::Slash]) {
+        let right = calculation_value(tokens, offset, context)?;
+        value = CalculationValue::Operation(Box::new(value), operator, Box::new(right));
+    }
+    Ok(value)
+}
+
+fn next_operator(tokens: &mut Tokens, operators: &[Operator]) -> Option<Operator> {
+    match tokens.peek().and_then(|t| t.extract_operator()) {
+        Some(operator) if operators.contains(&operator) => {
+            tokens.next();
+            Some(operator)
+        },
+        _ => None,
+    }
+}
+
+// A number, a variable, `(...)`, or a function.
+fn calculation_value(tokens: &mut Tokens, offset: Option<usize>, context: &Context) -> Result<CalculationValue> {
+    match tokens.next() {
+        Some(OperatorOrToken::Token(TokenOffset { token: number @ Token::Number { .. }, .. })) => {
+            Ok(CalculationValue::Number(number))
+        },
+        Some(OperatorOrToken::Operator(OperatorOffset { operator: Operator::LeftParen, .. })) => {
+            let inside = until_closing_paren(tokens);
+            let mut inside = inside.into_iter().peekable();
+            let value = sum(&mut inside, offset, context)?;
+            if let Some(extra) = inside.next() {
+                return Err(invalid(&format!("Expected an operator, instead saw `{}`.", extra), extra.offset().or(offset)))
+            }
+            Ok(CalculationValue::Calculation(Calculation {
+                name: String::from("calc"),
+                arguments: vec![value],
+            }))
+        },
+        Some(OperatorOrToken::Token(t @ TokenOffset { token: Token::String(_), .. })) => {
+            let name = t.token.to_string();
+            let is_call = tokens.peek().and_then(|p| p.extract_operator()) == Some(Operator::LeftParen);
+            if is_call {
+                tokens.next();
+                let arguments = until_closing_paren(tokens);
+                if is_calculation(&name) {
+                    return Ok(CalculationValue::Calculation(
+                        Calculation::parse(&name, arguments, t.offset, context)?
+                    ))
+                }
+                return Ok(CalculationValue::Raw(format!("{}({})", name, join_arguments(&arguments))))
+            }
+            if name.to_lowercase().starts_with("calc(") {
+                return Ok(match Calculation::evaluate_text(&name, t.offset, context)? {
+                    Expression::Calculation(calculation) => CalculationValue::Calculation(calculation),
+                    Expression::Value(OperatorOrToken::Token(TokenOffset { token, .. })) => {
+                        CalculationValue::Number(token)
+                    },
+                    other => CalculationValue::Raw(other.to_string()),
+                })
+            }
+            if name.starts_with("-$") {
+                let variable = TokenOffset { token: Token::String(name[1..].to_string()), offset: t.offset };
+                return Ok(negate(variable_value(&variable, context)?))
+            }
+            if !name.starts_with('$') {
+                return Ok(CalculationValue::Raw(name))
+            }
+            variable_value(&t, context)
+        },
+        other => Err(invalid(
+            "Expected a number, variable, function or calculation.",
+            other.and_then(|o| o.offset()).or(offset),
+        )),
+    }
+}
+
+fn variable_value(variable: &TokenOffset, context: &Context) -> Result<CalculationValue> {
+    match context.get_variable(variable) {
+        Some(Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: number @ Token::Number { .. }, ..
+        }))) => Ok(CalculationValue::Number(number)),
+        Some(Expression::Calculation(calculation)) => Ok(CalculationValue::Calculation(calculation)),
+        Some(other) => Ok(CalculationValue::Raw(other.to_string())),
+        None => Err(SassError {
+            offset: variable.offset.unwrap_or(0),
+            kind: ErrorKind::UndefinedVariable,
+            message: format!("Undefined variable `{}`.", variable.token),
+            location: None,
+            calls: vec![],
+        }),
+    }
+}
+
+// `-$x`, which the tokenizer keeps as one string.
+fn negate(value: CalculationValue) -> CalculationValue {
+    match value {
+        CalculationValue::Number(Token::Number { value, units, computed }) => {
+            CalculationValue::Number(Token::Number { value: -value, units, computed })
+        },
+        CalculationValue::Raw(raw) => CalculationValue::Raw(format!("-{}", raw)),
+        other => CalculationValue::Operation(
+            Box::new(CalculationValue::Number(Token::Number { value: -1.0, units: None, computed: true })),
+            Operator::Star,
+            Box::new(other),
+        ),
+    }
+}
+
+// Puts the arguments of a plain function back the way they were
+// separated: `foo(1, bar(2))`.
+fn join_arguments(tokens: &[OperatorOrToken]) -> String {
+    let mut joined = String::new();
+    let mut previous: Option<Option<Operator>> = None;
+    for token in tokens.iter() {
+        let operator = token.extract_operator();
+        let tight = matches!((previous, operator),
+            (None, _) |
+            (Some(Some(Operator::LeftParen)), _) |
+            (_, Some(Operator::RightParen)) |
+            (_, Some(Operator::Comma)) |
+            (Some(None), Some(Operator::LeftParen)));
+        if !tight {
+            joined.push(' ');
+        }
+        joined.push_str(&token.to_string());
+        previous = Some(operator);
+    }
+    joined
+}
+
+fn until_closing_paren(tokens: &mut Tokens) -> Vec<OperatorOrToken> {
+    let mut inside = vec![];
+    let mut paren_level = 0;
+    for token in tokens {
+        match token.extract_operator() {
+            Some(Operator::RightParen) if paren_level == 0 => break,
+            Some(Operator::LeftParen) => paren_level += 1,
+            Some(Operator::RightParen) => paren_level -= 1,
+            _ => {},
+        }
+        inside.push(token);
+    }
+    inside
+}
+
+fn invalid(message: &str, offset: Option<usize>) -> SassError {
+    SassError {
+        offset: offset.unwrap_or(0),
+        kind: ErrorKind::ParserError,
+        message: String::from(message),
+        location: None,
+        calls: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compile_string;
+
+    fn compile(input: &str) -> String {
+        let mut output = Vec::new();
+        compile_string(input, &mut output, "compressed").unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn it_simplifies_calculations_as_far_as_it_can() {
+        assert_eq!(
+            compile("$x: 10px;\na { b: calc(1px + 2px); c: calc(100% - $x * 2); \
+                     d: calc(var(--a) + (2px * 3)); e: calc(1 + (2 - 3) * 4); f: calc(1vh - env(a)); }"),
+            "a{b:3px;c:calc(100% - 20px);d:calc(var(--a) + 6px);e:-3;f:calc(1vh - env(a))}"
+        );
+    }
+
+    #[test]
+    fn it_compares_min_max_and_clamp_arguments_with_the_same_units() {
+        assert_eq!(
+            compile("$w: 5px;\na { b: min(1px, $w); c: max(1px, $w, 3px); d: min(100%, $w); \
+                     e: clamp(1px, 10px, $w); f: clamp(1rem, 50%, 2rem); }"),
+            "a{b:1px;c:5px;d:min(100%, 5px);e:5px;f:clamp(1rem, 50%, 2rem)}"
+        );
+    }
+
+    #[test]
+    fn it_negates_variables_and_keeps_function_arguments_as_written() {
+        assert_eq!(
+            compile("$x: 2px;\na { b: calc(-$x + 1px); c: calc(100% - $x); d: calc(100% - foo(1, bar(2))); }"),
+            "a{b:-1px;c:calc(100% - 2px);d:calc(100% - foo(1, bar(2)))}"
+        );
+    }
+}
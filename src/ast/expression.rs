@@ -1,4 +1,4 @@
-use token::Token;
+use token::{Token, format_like};
 use token_offset::TokenOffset;
 use operator::Operator;
 use operator_offset::OperatorOffset;
@@ -6,27 +6,41 @@ use operator_or_token::OperatorOrToken;
 use context::Context;
 use error::{Result, SassError, ErrorKind};
 use expression_evaluator::ExpressionEvaluator;
+use ast::calculation::Calculation;
 
 use std::fmt;
+use std::mem;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     List(Vec<Expression>),
     Value(OperatorOrToken),
+    Calculation(Calculation),
 }
 
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Expression::List(ref elements) => {
-                elements.iter().map(|e|
-                    e.to_string()
-                ).collect::<Vec<_>>()
-                 .join(" ")
-                 .replace(" / ", "/")
-                 .fmt(f)
+                // Slashes separate their neighbours without spaces, like
+                // `12px/1.5` or `1/2/3`.
+                let mut list = String::new();
+                for (i, e) in elements.iter().enumerate() {
+                    if i > 0 && !e.is_operator_kind(Operator::Slash) &&
+                       !elements[i - 1].is_operator_kind(Operator::Slash) {
+                        list.push(' ');
+                    }
+                    list.push_str(&format_like(f, e));
+                }
+                let list = list
+                 .replace(" , ", ", ")
+                 .replace(" : ", ": ")
+                 .replace("( ", "(")
+                 .replace(" )", ")");
+                f.write_str(&list)
             },
             Expression::Value(ref v) => v.fmt(f),
+            Expression::Calculation(ref c) => c.fmt(f),
         }
     }
 }
@@ -35,7 +49,8 @@ impl Expression {
     pub fn parse<T>(tokenizer: &mut T) -> Result<Expression>
         where T: Iterator<Item = Result<OperatorOrToken>> {
         let mut list = vec![];
-        while let Some(Ok(t)) = tokenizer.next() {
+        for t in tokenizer.by_ref() {
+            let t = t?;
             match t {
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::Semicolon, ..
@@ -56,6 +71,7 @@ impl Expression {
         let error_offset = match list.pop() {
             Some(Expression::Value(v)) => v.offset().unwrap_or(0),
             Some(Expression::List(_)) => unreachable!(), // for now until nested lists
+            Some(Expression::Calculation(_)) => unreachable!(), // only made by evaluating
             None => 0,
         };
         Err(SassError {
@@ -64,50 +80,133 @@ impl Expression {
             message: String::from(
                 "Expected semicolon while parsing a value expression; reached EOF instead."
             ),
+            location: None,
+            calls: vec![],
         })
     }
 
-    fn apply_slash(first: OperatorOrToken, second: OperatorOrToken, paren_level: i32, offset: Option<usize>) -> Expression {
-        if paren_level == 0 {
-            debug!("Paren level 0. First computed: {}, second computed: {}", first.computed_number(), second.computed_number());
-            if first.computed_number() || second.computed_number() {
-                Expression::Value(first / second)
-            } else {
-                Expression::List(vec![
-                    Expression::Value(first),
-                    Expression::Value(OperatorOrToken::Operator(OperatorOffset {
-                        operator: Operator::Slash,
-                        offset: offset,
-                    })),
-                    Expression::Value(second),
-                ])
-            }
+    pub fn from_tokens(tokens: Vec<OperatorOrToken>) -> Expression {
+        let mut list = tokens.into_iter()
+                             .map(Expression::Value)
+                             .collect::<Vec<_>>();
+        if list.len() == 1 {
+            list.pop().unwrap()
         } else {
-            debug!("Paren level {}", paren_level);
-            Expression::Value(first / second)
+            Expression::List(list)
         }
     }
 
-    fn force_list_collapse(list: Vec<Expression>, context: &Context) -> Expression {
-        if list.iter().any(|item| {
-            match *item {
-                Expression::Value(OperatorOrToken::Operator(OperatorOffset {
-                    operator: Operator::Slash, ..
-                })) => true,
-                _ => false,
+    // A `/` between two plain values, like `12px/1.5` in `font`, separates
+    // them. It divides inside parentheses, or when either side is a
+    // variable, a function's return value or the result of other math.
+    fn apply_slash(operator: OperatorOffset, first: Expression, second: Expression, paren_level: i32) -> Result<Expression> {
+        debug!("Paren level {}. First computed: {}, second computed: {}", paren_level, first.is_computed(), second.is_computed());
+        if paren_level > 0 || first.is_computed() || second.is_computed() {
+            Expression::divide(operator, first, second)
+        } else {
+            Ok(Expression::slash_list(operator, first, second))
+        }
+    }
+
+    pub fn divide(operator: OperatorOffset, first: Expression, second: Expression) -> Result<Expression> {
+        match (first.without_slash()?, second.without_slash()?) {
+            (Expression::Value(f), Expression::Value(s)) => Ok(Expression::Value((f / s)?)),
+            (f, s) => Err(Expression::undefined_operation(operator, &f, &s)),
+        }
+    }
+
+    fn slash_list(operator: OperatorOffset, first: Expression, second: Expression) -> Expression {
+        let mut list = vec![];
+        for (i, part) in vec![first, second].into_iter().enumerate() {
+            if i > 0 {
+                list.push(Expression::Value(OperatorOrToken::Operator(operator)));
+            }
+            match part {
+                Expression::List(parts) if Expression::is_slash_separated(&parts) => list.extend(parts),
+                other => list.push(other),
             }
+        }
+        Expression::List(list)
+    }
+
+    fn is_slash_separated(parts: &[Expression]) -> bool {
+        parts.len() > 1 && parts.len() % 2 == 1 &&
+        parts.iter().enumerate().all(|(i, e)|
+            (i % 2 == 1) == e.is_operator_kind(Operator::Slash)
+        )
+    }
+
+    pub fn is_slash_list(&self) -> bool {
+        match *self {
+            Expression::List(ref parts) => Expression::is_slash_separated(parts),
+            _ => false,
+        }
+    }
+
+    // Whether a `/` next to this value divides rather than separates.
+    fn is_computed(&self) -> bool {
+        match *self {
+            Expression::Value(ref v) => v.computed_number(),
+            _ => false,
+        }
+    }
+
+    // The value as a variable or function returns it: slash-separated
+    // numbers are divided, and numbers divide when they're next to a `/`.
+    pub fn without_slash(self) -> Result<Expression> {
+        match self {
+            Expression::List(parts) => {
+                if !Expression::is_slash_separated(&parts) {
+                    return Ok(Expression::List(parts))
+                }
+                let mut parts = parts.into_iter();
+                let mut quotient = parts.next().unwrap();
+                while let (Some(slash), Some(divisor)) = (parts.next(), parts.next()) {
+                    let slash = slash.extract_operator_offset()?;
+                    quotient = match (quotient, divisor) {
+                        (Expression::Value(q), Expression::Value(d)) => Expression::Value((q / d)?),
+                        (q, d) => return Err(Expression::undefined_operation(slash, &q, &d)),
+                    };
+                }
+                Ok(quotient)
+            },
+            Expression::Value(OperatorOrToken::Token(TokenOffset {
+                token: Token::Number { value, units, .. }, offset
+            })) => Ok(Expression::Value(OperatorOrToken::Token(TokenOffset {
+                token: Token::Number { value, units, computed: true },
+                offset,
+            }))),
+            other => Ok(other),
+        }
+    }
+
+    fn force_list_collapse(list: Vec<Expression>, context: &Context) -> Result<Expression> {
+        if list.iter().any(|item| {
+            matches!(*item, Expression::Value(OperatorOrToken::Operator(OperatorOffset {
+                operator: Operator::Slash, ..
+            })))
         }) {
             let mut evaluator = ExpressionEvaluator::new(context);
             evaluator.paren_level = 1;
             evaluator.evaluate_list(list)
         } else {
-            Expression::List(list)
+            Ok(Expression::List(list))
         }
     }
 
-    pub fn apply_math(operator: OperatorOffset, first: Expression, second: Expression, context: &Context, paren_level: i32) -> Expression {
+    pub fn apply_math(operator: OperatorOffset, first: Expression, second: Expression, context: &Context, paren_level: i32) -> Result<Expression> {
         debug!("Applying math to:\nfirst: {:#?}\nop: {:#?}\nsecond: {:#?}", first, operator, second);
 
+        let (first, second) = match operator.operator {
+            Operator::Slash if first.is_slash_list() || second.is_slash_list() => {
+                return Expression::apply_slash(operator, first, second, paren_level)
+            },
+            Operator::Plus | Operator::Minus | Operator::Star | Operator::Percent => {
+                (first.without_slash()?, second.without_slash()?)
+            },
+            _ => (first, second),
+        };
+
         match (first, second) {
             (Expression::Value(f), Expression::Value(s)) => {
                 let result = match operator.operator {
@@ -116,25 +215,28 @@ impl Expression {
                     Operator::Star => f * s,
                     Operator::Percent => f % s,
                     Operator::Slash => return Expression::apply_slash(
-                        f, s, paren_level, operator.offset
+                        operator, Expression::Value(f), Expression::Value(s), paren_level
                     ),
-                    _ => unimplemented!(),
+                    _ => return Err(Expression::undefined_operation(
+                        operator, &Expression::Value(f), &Expression::Value(s)
+                    )),
                 };
-                Expression::Value(result)
+                Ok(Expression::Value(result?))
             },
             (Expression::List(f), Expression::List(s)) => {
-                let eval_first = Expression::force_list_collapse(f, context);
-                let eval_second = Expression::force_list_collapse(s, context);
+                let eval_first = Expression::force_list_collapse(f, context)?;
+                let eval_second = Expression::force_list_collapse(s, context)?;
 
                 match (eval_first, eval_second) {
                     (Expression::List(mut fi), Expression::List(se)) => {
                         match operator.operator {
                             Operator::Plus | Operator::Comma => {
                                 fi.extend(se);
-                                Expression::List(fi)
+                                Ok(Expression::List(fi))
                             },
-                            _ => panic!("Can't use an operator other than \
-                                         plus or comma on two lists"),
+                            _ => Err(Expression::undefined_operation(
+                                operator, &Expression::List(fi), &Expression::List(se)
+                            )),
                         }
                     },
                     (eval_first, eval_second) => {
@@ -148,21 +250,21 @@ impl Expression {
             (Expression::List(f), Expression::Value(s)) => {
                 let mut first_evaluator = ExpressionEvaluator::new(context);
                 first_evaluator.paren_level = paren_level;
-                let eval_first = first_evaluator.evaluate_list(f);
+                let eval_first = first_evaluator.evaluate_list(f)?;
 
                 match eval_first {
                     Expression::List(mut fi) => {
                         match operator.operator {
                             Operator::Plus => {
                                 fi.push(Expression::Value(s));
-                                Expression::List(fi)
+                                Ok(Expression::List(fi))
                             },
                             Operator::Slash => {
                                 if s.computed_number() {
                                     let forced = Expression::force_list_collapse(
                                         fi,
                                         context
-                                    );
+                                    )?;
 
                                     match forced {
                                         Expression::List(mut fi) => {
@@ -174,11 +276,14 @@ impl Expression {
                                                 )
                                             );
                                             fi.push(Expression::Value(s));
-                                            Expression::List(fi)
+                                            Ok(Expression::List(fi))
                                         },
                                         Expression::Value(fo) => {
-                                            Expression::Value(fo / s)
-                                        }
+                                            Ok(Expression::Value((fo / s)?))
+                                        },
+                                        other => Err(Expression::undefined_operation(
+                                            operator, &other, &Expression::Value(s)
+                                        )),
                                     }
                                 } else {
                                     fi.push(
@@ -187,11 +292,12 @@ impl Expression {
                                         )
                                     );
                                     fi.push(Expression::Value(s));
-                                    Expression::List(fi)
+                                    Ok(Expression::List(fi))
                                 }
                             },
-                            _ => panic!("Can't use an operator other than \
-                                         plus on a list and a value"),
+                            _ => Err(Expression::undefined_operation(
+                                operator, &Expression::List(fi), &Expression::Value(s)
+                            )),
                         }
                     },
                     _ => Expression::apply_math(
@@ -202,14 +308,15 @@ impl Expression {
             },
             (Expression::Value(f), Expression::List(s)) => {
                 debug!("Value Op List: {:#?}\n{:#?}\n{:#?}\n", f, operator, s);
-                let eval_second = Expression::force_list_collapse(s, context);
+                let eval_second = Expression::force_list_collapse(s, context)?;
                 match eval_second {
+                    Expression::List(ref se) if se.is_empty() => {
+                        Ok(Expression::Value(f))
+                    },
                     Expression::List(se) => {
                         match operator.operator {
                             Operator::Plus => {
-                                let (first_in_list, rest) = se.split_first()
-                                    .expect("Trying to get the first and rest \
-                                          of a list that isn't a value failed");
+                                let (first_in_list, rest) = se.split_first().unwrap();
                                 let new_first = format!("{}{}", f, first_in_list);
                                 let mut new_list = vec![
                                     Expression::Value(OperatorOrToken::Token(
@@ -220,10 +327,11 @@ impl Expression {
                                     ))
                                 ];
                                 new_list.extend_from_slice(rest);
-                                Expression::List(new_list)
+                                Ok(Expression::List(new_list))
                             },
-                            _ => panic!("Can't use an operator other than \
-                                         plus on a value and a list"),
+                            _ => Err(Expression::undefined_operation(
+                                operator, &Expression::Value(f), &Expression::List(se)
+                            )),
                         }
                     },
                     _ => Expression::apply_math(
@@ -232,11 +340,28 @@ impl Expression {
                     ),
                 }
             },
+            // Calculations can't be used in Sass math, only passed along.
+            (first, second) => Err(Expression::undefined_operation(operator, &first, &second)),
+        }
+    }
+
+    fn undefined_operation(operator: OperatorOffset, first: &Expression, second: &Expression) -> SassError {
+        SassError {
+            offset: operator.offset.unwrap_or(0),
+            kind: ErrorKind::TypeError,
+            message: format!(
+                "Undefined operation `{} {} {}`.", first, operator, second
+            ),
+            location: None,
+            calls: vec![],
         }
     }
 
     pub fn create_list(head: Option<Expression>, tail: Expression) -> Expression {
         let mut list = match head {
+            Some(Expression::List(v)) if Expression::is_slash_separated(&v) => {
+                vec![Expression::List(v)]
+            },
             Some(Expression::List(v)) => v,
             Some(e) => vec![e],
             None => vec![],
@@ -245,66 +370,216 @@ impl Expression {
         Expression::List(list)
     }
 
-    pub fn is_number(&self) -> bool {
+    pub fn offset(&self) -> Option<usize> {
         match *self {
-            Expression::Value(OperatorOrToken::Token(TokenOffset {
-                token: Token::Number { .. }, ..
-            })) => true,
-            _ => false,
+            Expression::Value(ref v) => v.offset(),
+            Expression::List(ref list) => list.iter().filter_map(|e| e.offset()).next(),
+            Expression::Calculation(_) => None,
         }
     }
 
+    pub fn is_number(&self) -> bool {
+        matches!(*self, Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::Number { .. }, ..
+        })))
+    }
+
     pub fn is_string(&self) -> bool {
-        match *self {
-            Expression::Value(OperatorOrToken::Token(TokenOffset {
-                token: Token::String(_), ..
-            })) => true,
-            _ => false,
-        }
+        matches!(*self, Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(_), ..
+        })))
     }
 
     pub fn is_right_paren(&self) -> bool {
-        match *self {
-            Expression::Value(OperatorOrToken::Operator(OperatorOffset {
-                operator: Operator::RightParen, ..
-            })) => true,
-            _ => false,
-        }
+        matches!(*self, Expression::Value(OperatorOrToken::Operator(OperatorOffset {
+            operator: Operator::RightParen, ..
+        })))
     }
 
     pub fn is_left_paren(&self) -> bool {
+        matches!(*self, Expression::Value(OperatorOrToken::Operator(OperatorOffset {
+            operator: Operator::LeftParen, ..
+        })))
+    }
+
+    pub fn is_operator_kind(&self, kind: Operator) -> bool {
         match *self {
             Expression::Value(OperatorOrToken::Operator(OperatorOffset {
-                operator: Operator::LeftParen, ..
-            })) => true,
+                operator, ..
+            })) => operator == kind,
             _ => false,
         }
     }
 
+    pub fn is_comma(&self) -> bool {
+        matches!(*self, Expression::Value(OperatorOrToken::Operator(OperatorOffset {
+            operator: Operator::Comma, ..
+        })))
+    }
+
     pub fn is_operator(&self) -> bool {
-        match *self {
-            Expression::Value(OperatorOrToken::Operator(_)) => true,
-            _ => false,
-        }
+        matches!(*self, Expression::Value(OperatorOrToken::Operator(_)))
     }
 
-    pub fn extract_operator_offset(self) -> OperatorOffset {
+    pub fn extract_operator_offset(self) -> Result<OperatorOffset> {
         match self {
             Expression::Value(OperatorOrToken::Operator(operator_offset)) => {
-                operator_offset
+                Ok(operator_offset)
             },
-            _ => panic!("Can't extract operator offset from {:?}", self),
+            other => Err(Expression::expected_error("an operator", other)),
         }
     }
 
-    pub fn extract_token_offset(self) -> TokenOffset {
+    pub fn extract_token_offset(self) -> Result<TokenOffset> {
         match self {
             Expression::Value(OperatorOrToken::Token(token_offset)) => {
-                token_offset
+                Ok(token_offset)
             },
-            _ => panic!("Can't extract token offset from {:?}", self),
+            other => Err(Expression::expected_error("a value", other)),
+        }
+    }
+
+    // Comma-separated lists keep their commas between the elements, which
+    // may be space-separated lists themselves.
+    pub fn comma_list(elements: Vec<Expression>, offset: Option<usize>) -> Expression {
+        let mut list = vec![];
+        for element in elements.into_iter() {
+            if !list.is_empty() {
+                list.push(Expression::Value(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Comma,
+                    offset,
+                })));
+            }
+            list.push(element);
+        }
+        if list.len() == 1 {
+            list.pop().unwrap()
+        } else {
+            Expression::List(list)
+        }
+    }
+
+    pub fn comma_elements(self) -> Vec<Expression> {
+        match self {
+            Expression::List(list) => {
+                if list.is_empty() {
+                    return vec![]
+                }
+                if !list.iter().any(|e| e.is_comma()) {
+                    return vec![Expression::List(list)]
+                }
+                let mut elements = vec![];
+                let mut current = vec![];
+                for e in list.into_iter() {
+                    if e.is_comma() {
+                        elements.push(Expression::from_parts(mem::take(&mut current)));
+                    } else {
+                        current.push(e);
+                    }
+                }
+                elements.push(Expression::from_parts(current));
+                elements
+            },
+            value => vec![value],
+        }
+    }
+
+    // The elements of a list with any separator, or the one value that
+    // isn't a list.
+    pub fn list_elements(self) -> Vec<Expression> {
+        match self {
+            Expression::List(list) if !list.iter().any(|e| e.is_comma()) => list,
+            other => other.comma_elements(),
+        }
+    }
+
+    pub fn from_parts(mut parts: Vec<Expression>) -> Expression {
+        if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            Expression::List(parts)
+        }
+    }
+
+    // Maps are kept as they're written, `(key: value, ...)`, with each
+    // value evaluated.
+    pub fn map(entries: Vec<(String, Expression)>, offset: Option<usize>) -> Expression {
+        let operator = |operator| Expression::Value(OperatorOrToken::Operator(OperatorOffset {
+            operator,
+            offset,
+        }));
+        let mut list = vec![operator(Operator::LeftParen)];
+        for (i, (key, value)) in entries.into_iter().enumerate() {
+            if i > 0 {
+                list.push(operator(Operator::Comma));
+            }
+            list.push(Expression::Value(OperatorOrToken::Token(TokenOffset {
+                token: Token::String(key),
+                offset,
+            })));
+            list.push(operator(Operator::Colon));
+            list.push(value);
+        }
+        list.push(operator(Operator::RightParen));
+        Expression::List(list)
+    }
+
+    pub fn map_entries(&self) -> Option<Vec<(String, Expression)>> {
+        let list = match *self {
+            Expression::List(ref list) => list,
+            _ => return None,
+        };
+        if list.len() < 2 || !list[0].is_left_paren() || !list[list.len() - 1].is_right_paren() {
+            return None
+        }
+
+        let mut entries = vec![];
+        for entry in split_at_depth(&list[1..list.len() - 1], Operator::Comma).into_iter() {
+            let mut halves = split_at_depth(&entry, Operator::Colon).into_iter();
+            match (halves.next(), halves.next(), halves.next()) {
+                (Some(key), Some(value), None) => {
+                    let key = Expression::from_parts(key).to_string();
+                    entries.push((key, Expression::from_parts(value)));
+                },
+                _ => return None,
+            }
+        }
+        Some(entries)
+    }
+
+    fn expected_error(expected: &str, actual: Expression) -> SassError {
+        let offset = match actual {
+            Expression::Value(ref v) => v.offset(),
+            Expression::List(_) | Expression::Calculation(_) => None,
+        };
+        SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::TypeError,
+            message: format!("Expected {}, instead saw `{}`.", expected, actual),
+            location: None,
+            calls: vec![],
+        }
+    }
+}
+
+// Splits `parts` wherever `operator` appears outside parentheses.
+pub fn split_at_depth(parts: &[Expression], operator: Operator) -> Vec<Vec<Expression>> {
+    let mut split = vec![];
+    let mut current = vec![];
+    let mut depth = 0;
+    for part in parts.iter() {
+        if part.is_left_paren() {
+            depth += 1;
+        } else if part.is_right_paren() {
+            depth -= 1;
+        } else if depth == 0 && part.is_operator_kind(operator) {
+            split.push(mem::take(&mut current));
+            continue
         }
+        current.push(part.clone());
     }
+    split.push(current);
+    split
 }
 
 #[cfg(test)]
@@ -1,3 +1,4 @@
+pub mod calculation;
 pub mod expression;
 pub mod node;
 pub mod root;
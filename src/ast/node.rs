@@ -2,9 +2,16 @@ use sass::output_style::{SassOutputStyle, Streamable};
 use sass::rule::SassRule;
 use sass::variable::SassVariable;
 use sass::comment::SassComment;
+use sass::import_rule::SassImport;
+use sass::message_rule::SassMessage;
+use sass::mixin::{SassInclude, SassContent};
+use sass::at_root::SassAtRoot;
+use sass::supports::SassSupports;
+use sass::media::SassMedia;
 use ast::expression::Expression;
 use token_offset::TokenOffset;
 use error::{Result};
+use source_map::MappedOutput;
 
 use std::io::Write;
 
@@ -14,23 +21,42 @@ pub enum Node {
     Property(TokenOffset, Expression),
     Variable(SassVariable),
     Comment(SassComment),
+    Import(Vec<SassImport>),
+    Message(SassMessage),
+    Include(SassInclude),
+    Content(SassContent),
+    AtRoot(SassAtRoot),
+    Supports(SassSupports),
+    Media(SassMedia),
+    Return(Expression),
 }
 
 impl Streamable for Node {
-    fn stream(&self, output: &mut Write, style: &SassOutputStyle)
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle)
               -> Result<()> {
         match *self {
-            Node::Rule(ref sr) => try!(sr.stream(output, style)),
+            Node::Rule(ref sr) => sr.stream(output, style)?,
             Node::Variable(..) => {}, // variable declarations never get output
             Node::Property(ref name, ref expression) => {
-                let ref n = name.token.to_string();
-                let ref v = expression.to_string();
-                try!(write!(output, "{}", style.property(n, v)));
+                let n = &name.token.to_string();
+                let v = &style.value(expression, output.precision);
+                let property = style.property(n, v);
+                let indent = property.len() - property.trim_start().len();
+                write!(output, "{}", &property[..indent])?;
+                output.mark(name.offset);
+                write!(output, "{}", &property[indent..])?;
             },
             Node::Comment(ref sc) => {
-                try!(write!(output, "{}", style.before_comment()));
-                try!(sc.stream(output, style));
+                write!(output, "{}", style.before_comment())?;
+                sc.stream(output, style)?;
             },
+            Node::Import(..) => {}, // nested imports get loaded before evaluation
+            Node::Message(..) => {}, // logged or raised during evaluation
+            Node::Include(..) => {}, // replaced by the mixin's contents
+            Node::Content(..) => {}, // replaced by the content block
+            Node::AtRoot(..) => {}, // moved out of its rule by the optimizer
+            Node::Supports(..) | Node::Media(..) => {}, // bubbled out of its rule by the optimizer
+            Node::Return(..) => {}, // only in functions
         }
         Ok(())
     }
@@ -1,30 +1,63 @@
 use sass::output_style::{SassOutputStyle, Streamable};
-use sass::rule::SassRule;
+use sass::rule::{SassRule, evaluate_children};
 use sass::variable::SassVariable;
+use sass::mixin::{SassMixin, SassInclude};
+use sass::function_rule::SassFunctionRule;
+use sass::at_root::SassAtRoot;
+use sass::supports::SassSupports;
+use sass::media::SassMedia;
+use ast::node::Node;
 use sass::comment::SassComment;
+use sass::use_rule::SassUse;
+use sass::forward_rule::SassForward;
+use sass::import_rule::SassImport;
+use sass::message_rule::SassMessage;
 use context::Context;
-use expression_evaluator::ExpressionEvaluator;
-use error::{Result};
+use error::{Result, SassError, ErrorKind};
+use source_map::MappedOutput;
 
 use std::io::Write;
 
+// `AtRoot` is named for `@at-root`, not for being a `Root`.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Root {
     Rule(SassRule),
     Variable(SassVariable),
     Comment(SassComment),
+    Use(SassUse),
+    Forward(SassForward),
+    Import(Vec<SassImport>),
+    Message(SassMessage),
+    Mixin(SassMixin),
+    Function(SassFunctionRule),
+    Include(SassInclude),
+    AtRoot(SassAtRoot),
+    Supports(SassSupports),
+    Media(SassMedia),
 }
 
 impl Streamable for Root {
-    fn stream(&self, output: &mut Write, style: &SassOutputStyle)
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle)
               -> Result<()> {
         match *self {
-            Root::Rule(ref sr) => try!(sr.stream(output, style)),
+            Root::Rule(ref sr) => sr.stream(output, style)?,
             Root::Comment(ref sc) => {
-                try!(sc.stream(output, style));
-                try!(write!(output, "{}", style.after_comment()));
+                sc.stream(output, style)?;
+                write!(output, "{}", style.after_comment())?;
             },
             Root::Variable(..) => {}, // variable declarations never get output
+            Root::Use(..) | Root::Forward(..) => {},
+            Root::Message(..) => {},
+            Root::Mixin(..) | Root::Function(..) | Root::Include(..) => {},
+            Root::AtRoot(..) => {},
+            Root::Supports(ref supports) => supports.stream(output, style)?,
+            Root::Media(ref media) => media.stream(output, style)?,
+            Root::Import(ref imports) => {
+                for import in imports.iter() {
+                    import.stream(output, style)?;
+                }
+            },
         }
 
         Ok(())
@@ -32,21 +65,67 @@ impl Streamable for Root {
 }
 
 impl Root {
-    pub fn evaluate(self, context: &mut Context) -> Option<Root> {
+    // Evaluates to the roots that end up in the CSS, if any.
+    pub fn evaluate(self, context: &mut Context) -> Result<Vec<Root>> {
         match self {
-            Root::Rule(sr) => Some(Root::Rule(sr.evaluate(&context))),
+            Root::Rule(sr) => Ok(vec![Root::Rule(sr.evaluate(context)?)]),
             Root::Variable(sv) => {
-                let evaluated_var = ExpressionEvaluator::evaluate(
-                    sv.value,
-                    &context
-                );
-                context.add_variable(SassVariable {
-                    name: sv.name,
-                    value: evaluated_var,
-                });
-                None
-            },
-            Root::Comment(c) => Some(Root::Comment(c)),
+                sv.assign(context)?;
+                Ok(vec![])
+            },
+            Root::Comment(c) => Ok(vec![Root::Comment(c)]),
+            Root::Message(sm) => {
+                sm.evaluate(context)?;
+                Ok(vec![])
+            },
+            Root::Mixin(mixin) => {
+                context.mixins.insert(mixin.name_string(), mixin);
+                Ok(vec![])
+            },
+            Root::Function(function) => {
+                context.function_rules.insert(function.name_string(), function);
+                Ok(vec![])
+            },
+            Root::Include(include) => {
+                let offset = include.name.offset;
+                roots_from_nodes(include.evaluate(context)?, offset)
+            },
+            Root::Supports(supports) => Ok(vec![Root::Supports(supports.evaluate(context)?)]),
+            Root::Media(media) => Ok(vec![Root::Media(media.evaluate(context)?)]),
+            // There's nothing to leave at the top level.
+            Root::AtRoot(at_root) => {
+                let mut local_context = context.clone();
+                let children = evaluate_children(at_root.children, &mut local_context)?;
+                roots_from_nodes(children, None)
+            },
+            // Module and import rules are loaded by the ModuleLoader before evaluation.
+            Root::Use(..) | Root::Forward(..) | Root::Import(..) => Ok(vec![]),
+        }
+    }
+}
+
+// The rules and comments in `nodes`, which can't include properties
+// outside of a rule.
+fn roots_from_nodes(nodes: Vec<Node>, offset: Option<usize>) -> Result<Vec<Root>> {
+    let mut roots = vec![];
+    for node in nodes.into_iter() {
+        match node {
+            Node::Rule(rule) => roots.push(Root::Rule(rule)),
+            Node::Comment(comment) => roots.push(Root::Comment(comment)),
+            Node::AtRoot(at_root) => roots.extend(roots_from_nodes(at_root.children, offset)?),
+            Node::Supports(supports) => roots.push(Root::Supports(supports)),
+            Node::Media(media) => roots.push(Root::Media(media)),
+            Node::Property(..) => return Err(SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ParserError,
+                message: String::from(
+                    "Declarations may only be used within style rules."
+                ),
+                location: None,
+                calls: vec![],
+            }),
+            _ => {},
         }
     }
+    Ok(roots)
 }
@@ -0,0 +1,386 @@
+use error::{Result, SassError};
+use importer::{Importer, FilesystemImporter};
+use module::{ModuleLoader, Stylesheet};
+use options::{CompileOptions, IndentType, OutputStyle};
+use source_map::SourceMap;
+
+use std::env;
+use std::io::Write;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+// Compiles any number of stylesheets with the same options.
+pub struct Compiler {
+    options: CompileOptions,
+    importers: Vec<Box<dyn Importer>>,
+}
+
+impl Compiler {
+    pub fn new(mut options: CompileOptions) -> Compiler {
+        let mut importers = mem::take(&mut options.importers);
+        importers.push(Box::new(FilesystemImporter::new(options.load_paths.clone())));
+
+        Compiler {
+            options,
+            importers,
+        }
+    }
+
+    pub fn compile_file(&self, input_url: &str, output: &mut dyn Write) -> Result<()> {
+        self.compile_file_with_source_map(input_url, output).map(|_| ())
+    }
+
+    // Relative imports in `input` are resolved against `base_url`, as if
+    // `input` were the contents of that file.
+    pub fn compile_string(&self, input: &str, base_url: Option<&str>, output: &mut dyn Write) -> Result<()> {
+        self.compile_string_with_source_map(input, base_url, output).map(|_| ())
+    }
+
+    // Also returns the source map's JSON when the `source_map` option is on.
+    pub fn compile_file_with_source_map(&self, input_url: &str, output: &mut dyn Write)
+                                        -> Result<Option<String>> {
+        let mut loader = ModuleLoader::new(
+            &self.importers, self.options.functions.clone(), &*self.options.logger
+        );
+        let (stylesheet, sass) = loader.load_entry(input_url)?;
+        self.compile_text(&mut loader, &stylesheet, &sass, output)
+    }
+
+    pub fn compile_string_with_source_map(&self, input: &str, base_url: Option<&str>,
+                                          output: &mut dyn Write) -> Result<Option<String>> {
+        let mut loader = ModuleLoader::new(
+            &self.importers, self.options.functions.clone(), &*self.options.logger
+        );
+        let stylesheet = Stylesheet {
+            syntax: self.options.syntax,
+            ..Stylesheet::from_string(base_url)
+        };
+        self.compile_text(&mut loader, &stylesheet, input, output)
+    }
+
+    // Every error in the file and what it loads, rather than just the
+    // first one.
+    pub fn check_file(&self, input_url: &str) -> Vec<SassError> {
+        let mut loader = ModuleLoader::new(
+            &self.importers, self.options.functions.clone(), &*self.options.logger
+        );
+        match loader.load_entry(input_url) {
+            Ok((stylesheet, sass)) => loader.check_text(&stylesheet, &sass),
+            Err(e) => vec![e],
+        }
+    }
+
+    pub fn check_string(&self, input: &str, base_url: Option<&str>) -> Vec<SassError> {
+        let mut loader = ModuleLoader::new(
+            &self.importers, self.options.functions.clone(), &*self.options.logger
+        );
+        let stylesheet = Stylesheet {
+            syntax: self.options.syntax,
+            ..Stylesheet::from_string(base_url)
+        };
+        loader.check_text(&stylesheet, input)
+    }
+
+    fn compile_text(&self, loader: &mut ModuleLoader, stylesheet: &Stylesheet,
+                    sass: &str, output: &mut dyn Write) -> Result<Option<String>> {
+        let style = self.options.style.output_style();
+        loader.precision = self.options.precision;
+        let mut css = Vec::new();
+        loader.stream_text(stylesheet, sass, vec![], &mut css, &*style)
+              .map_err(|e| loader.locate(e))?;
+
+        let css = String::from_utf8_lossy(&css);
+        let mut formatted = self.format(&css);
+        if !self.options.source_map {
+            write!(output, "{}", formatted)?;
+            return Ok(None)
+        }
+
+        let mut map = loader.source_map(self.options.source_map_contents);
+        self.adjust_mappings(&css, &mut map);
+        map.relativize_sources(self.map_directory().as_deref());
+        let url = if self.options.source_map_embed {
+            Some(map.to_data_url())
+        } else {
+            self.options.source_map_url.clone()
+        };
+        if let Some(url) = url {
+            let linefeed = self.options.linefeed.as_str();
+            if !formatted.is_empty() && !formatted.ends_with(linefeed) {
+                formatted.push_str(linefeed);
+            }
+            formatted.push_str(&format!("/*# sourceMappingURL={} */", url));
+        }
+        write!(output, "{}", formatted)?;
+        Ok(Some(map.to_json()))
+    }
+
+    // Where the source map is written, if it's written to a file of its own.
+    fn map_directory(&self) -> Option<PathBuf> {
+        if self.options.source_map_embed {
+            return None
+        }
+        let url = self.options.source_map_url.as_ref()?;
+        let path = env::current_dir().ok()?.join(url);
+        path.parent().map(Path::to_path_buf)
+    }
+
+    // Moves mappings to where `format` puts the text they point at.
+    fn adjust_mappings(&self, css: &str, map: &mut SourceMap) {
+        let lines = css.split('\n').collect::<Vec<_>>();
+        let in_comment = comment_lines(css);
+        let charset_line = self.options.charset && !css.is_ascii() &&
+                           self.options.style != OutputStyle::Compressed;
+        for m in map.mappings.iter_mut() {
+            let line = lines.get(m.generated_line).map_or("", |l| *l);
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            let reindented = !in_comment.get(m.generated_line).cloned().unwrap_or(false);
+            if reindented && m.generated_column >= spaces {
+                let indent = (spaces / 2) * self.options.indent_width + spaces % 2;
+                m.generated_column = m.generated_column - spaces + indent;
+            }
+            if charset_line {
+                m.generated_line += 1;
+            }
+        }
+    }
+
+    // The output styles always indent with two spaces and end lines with
+    // `\n`, so other indentation and line feeds are swapped in afterwards.
+    // Lines that continue a comment are kept as they were written.
+    fn format(&self, css: &str) -> String {
+        let indent = match self.options.indent_type {
+            IndentType::Space => " ".repeat(self.options.indent_width),
+            IndentType::Tab => "\t".repeat(self.options.indent_width),
+        };
+        let linefeed = self.options.linefeed.as_str();
+
+        let in_comment = comment_lines(css);
+        let mut formatted = css.split('\n').zip(in_comment).map(|(line, in_comment)| {
+            if indent == "  " || in_comment {
+                return String::from(line)
+            }
+            let content = line.trim_start_matches(' ');
+            let spaces = line.len() - content.len();
+            format!("{}{}{}", indent.repeat(spaces / 2), " ".repeat(spaces % 2), content)
+        }).collect::<Vec<_>>().join(linefeed);
+
+        if self.options.charset && !css.is_ascii() {
+            formatted = match self.options.style {
+                OutputStyle::Compressed => format!("\u{FEFF}{}", formatted),
+                _ => format!("@charset \"UTF-8\";{}{}", linefeed, formatted),
+            };
+        }
+        formatted
+    }
+}
+
+// Whether each line of `css` starts inside a comment.
+fn comment_lines(css: &str) -> Vec<bool> {
+    let mut lines = vec![false];
+    let mut in_comment = false;
+    let mut quote = None;
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => lines.push(in_comment),
+            '*' if in_comment && chars.peek() == Some(&'/') => {
+                chars.next();
+                in_comment = false;
+            },
+            _ if in_comment => {},
+            '\\' if quote.is_some() => { chars.next(); },
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '/' if quote.is_none() && chars.peek() == Some(&'*') => {
+                chars.next();
+                in_comment = true;
+            },
+            _ => {},
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::ErrorKind;
+    use importer::MemoryImporter;
+    use options::{CompileOptions, IndentType, LineFeed, OutputStyle};
+
+    fn compile(compiler: &Compiler, input: &str) -> Result<String> {
+        let mut output = Vec::new();
+        compiler.compile_string(input, None, &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn it_uses_the_configured_output_style() {
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compressed));
+        assert_eq!(compile(&compiler, "a { b: c; }"), Ok(String::from("a{b:c}")));
+    }
+
+    #[test]
+    fn it_can_be_reused() {
+        let mut importer = MemoryImporter::new();
+        importer.add("_colors.scss", "$c: red;\n");
+        let compiler = Compiler::new(CompileOptions::new().importer(importer));
+
+        for _ in 0..2 {
+            let mut output = Vec::new();
+            assert!(compiler.compile_string("@import \"colors\";\na { b: $c; }", Some("main.scss"), &mut output).is_ok());
+            assert_eq!(String::from_utf8(output).unwrap(), "a {\n  b: red; }\n\n");
+        }
+    }
+
+    #[test]
+    fn it_uses_the_configured_indentation_and_line_feeds() {
+        let compiler = Compiler::new(
+            CompileOptions::new().indent_type(IndentType::Tab)
+                                 .indent_width(1)
+                                 .linefeed(LineFeed::Crlf)
+        );
+        assert_eq!(
+            compile(&compiler, "a { b: c; }"),
+            Ok(String::from("a {\r\n\tb: c; }\r\n\r\n"))
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_indentation_inside_comments() {
+        let compiler = Compiler::new(CompileOptions::new().indent_width(4));
+        assert_eq!(
+            compile(&compiler, "a {\n  /* keep\n  two spaces */\n  b: c;\n}"),
+            Ok(String::from("a {\n    /* keep\n  two spaces */\n    b: c; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_adds_a_charset_for_non_ascii_output() {
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compact));
+        assert_eq!(
+            compile(&compiler, "a { content: \"✓\"; }"),
+            Ok(String::from("@charset \"UTF-8\";\na { content: \"✓\"; }\n\n"))
+        );
+
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compact).charset(false));
+        assert_eq!(
+            compile(&compiler, "a { content: \"✓\"; }"),
+            Ok(String::from("a { content: \"✓\"; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_writes_numbers_with_the_configured_precision() {
+        let input = "a { b: (1/3); c: (-0.25 * 1); d: -0 * 1; e: 0.0000001 * 2; }";
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compact));
+        assert_eq!(
+            compile(&compiler, input),
+            Ok(String::from("a { b: 0.3333333333; c: -0.25; d: 0; e: 0.0000002; }\n\n"))
+        );
+
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compressed).precision(3));
+        assert_eq!(
+            compile(&compiler, input),
+            Ok(String::from("a{b:.333;c:-.25;d:0;e:0}"))
+        );
+    }
+
+    #[test]
+    fn it_keeps_pseudo_selectors_together() {
+        let input = ":root { a: b; }\na:hover, b::before { c:d; }\n.x { &:hover { e: f; } .y:not(.z) { g: h; } }";
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compact));
+        assert_eq!(
+            compile(&compiler, input),
+            Ok(String::from(
+                ":root { a: b; }\n\na:hover, b::before { c: d; }\n\n.x:hover { e: f; }\n\n.x .y:not(.z) { g: h; }\n\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_quotes_strings_without_escaping_when_it_can() {
+        let input = "a { b: 'say \"hi\"'; c: 'plain'; d: \"it's\"; }";
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compact));
+        assert_eq!(
+            compile(&compiler, input),
+            Ok(String::from("a { b: 'say \"hi\"'; c: \"plain\"; d: \"it's\"; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_tells_slash_separators_from_division() {
+        let input = "@use \"sass:math\";\n\
+                     @function half($x) { @return $x / 2; }\n\
+                     $font: sans-serif; $a: 10px; $b: 2;\n\
+                     a { font: 12px/1.5 $font; grid-area: 1 / 2 / 3; b: $a / $b; \
+                     c: (10px / 2); d: 1/2 + 1; e: half(8px); f: math.div(10px, 4); }";
+        let compiler = Compiler::new(CompileOptions::new().style(OutputStyle::Compact));
+        assert_eq!(
+            compile(&compiler, input),
+            Ok(String::from(
+                "a { font: 12px/1.5 sans-serif; grid-area: 1/2/3; b: 5px; c: 5px; d: 1.5; e: 4px; f: 2.5px; }\n\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_writes_the_same_css_when_given_the_default_precision() {
+        let input = "a { content: \"\u{e9}\"; b: (1/3); }";
+        assert_eq!(
+            compile(&Compiler::new(CompileOptions::new().precision(10)), input),
+            compile(&Compiler::new(CompileOptions::new()), input)
+        );
+        assert_eq!(
+            compile(&Compiler::new(CompileOptions::new()), input),
+            Ok(String::from("@charset \"UTF-8\";\na {\n  content: \"\u{e9}\";\n  b: 0.3333333333; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_calls_custom_functions() {
+        let compiler = Compiler::new(CompileOptions::new().function("double", |args| {
+            match args.first().and_then(|a| a.trim_end_matches("px").parse::<f64>().ok()) {
+                Some(n) => Ok(format!("{}px", n * 2.0)),
+                None => Err(SassError {
+                    offset: 0,
+                    kind: ErrorKind::ParserError,
+                    message: String::from("double() needs a number"),
+                    location: None,
+                    calls: vec![],
+                }),
+            }
+        }));
+        assert_eq!(
+            compile(&compiler, "$w: 3px;\na { width: double($w) + 1px; }"),
+            Ok(String::from("a {\n  width: 7px; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_generates_source_maps() {
+        let compiler = Compiler::new(
+            CompileOptions::new().source_map(true)
+                                 .source_map_url("main.css.map")
+                                 .indent_type(IndentType::Tab)
+                                 .indent_width(1)
+        );
+        let mut output = Vec::new();
+        let map = compiler.compile_string_with_source_map(
+            "$c: red;\na {\n  color: $c;\n  b { d: e; }\n}\n", Some("main.scss"), &mut output
+        );
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "a {\n\tcolor: red; }\n\ta b {\n\t\td: e; }\n\n/*# sourceMappingURL=main.css.map */"
+        );
+        assert_eq!(
+            map,
+            Ok(Some(String::from(
+                "{\"version\":3,\"sourceRoot\":\"\",\"sources\":[\"main.scss\"],\"names\":[],\
+                 \"mappings\":\"AACA;CACE;CACA;EAAI\"}"
+            )))
+        );
+    }
+}
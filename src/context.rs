@@ -3,18 +3,113 @@ use sass::variable::SassVariable;
 use token_offset::TokenOffset;
 use token::Token;
 use operator_or_token::OperatorOrToken;
+use module::Module;
+use sass::function::SassFunction;
+use sass::message_rule::MessageKind;
+use sass::mixin::{SassMixin, Content};
+use sass::function_rule::SassFunctionRule;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Context {
     pub variables: HashMap<String, SassVariable>,
+    pub modules: HashMap<String, Module>,
+    pub global_modules: Vec<Module>,
+    pub functions: HashMap<String, SassFunction>,
+    pub mixins: HashMap<String, SassMixin>,
+    pub function_rules: HashMap<String, SassFunctionRule>,
+    // The keyword arguments passed in each argument list, by the name of
+    // the variable holding the rest of the list.
+    keywords: HashMap<String, Vec<(String, Expression)>>,
+    // The content block passed to the mixin being included.
+    pub content: Option<Rc<Content>>,
+    // `@debug` and `@warn` messages waiting to be logged, shared with the
+    // copies of this context made for nested rules.
+    logged: Rc<RefCell<Vec<(MessageKind, String, usize)>>>,
 }
 
 impl Context {
     pub fn new() -> Context {
         Context {
             variables: HashMap::new(),
+            modules: HashMap::new(),
+            global_modules: Vec::new(),
+            functions: HashMap::new(),
+            mixins: HashMap::new(),
+            function_rules: HashMap::new(),
+            keywords: HashMap::new(),
+            content: None,
+            logged: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    pub fn add_module(&mut self, namespace: String, module: Module) {
+        if namespace == "*" {
+            self.global_modules.push(module);
+        } else {
+            self.modules.insert(namespace, module);
+        }
+    }
+
+    pub fn log(&self, kind: MessageKind, message: String, offset: usize) {
+        self.logged.borrow_mut().push((kind, message, offset));
+    }
+
+    pub fn take_logged(&self) -> Vec<(MessageKind, String, usize)> {
+        self.logged.borrow_mut().drain(..).collect()
+    }
+
+    pub fn set_keywords(&mut self, name: &str, keywords: Vec<(String, Expression)>) {
+        self.keywords.insert(String::from(name), keywords);
+    }
+
+    pub fn keywords(&self, name: &str) -> Option<Vec<(String, Expression)>> {
+        self.keywords.get(name).cloned()
+    }
+
+    pub fn get_mixin(&self, name: &str) -> Option<SassMixin> {
+        match self.mixins.get(name) {
+            Some(mixin) => Some(mixin.clone()),
+            None => self.module_member(name, |m, n| m.mixins.get(n)),
+        }
+    }
+
+    pub fn get_function_rule(&self, name: &str) -> Option<SassFunctionRule> {
+        match self.function_rules.get(name) {
+            Some(function) => Some(function.clone()),
+            None => self.module_member(name, |m, n| m.function_rules.get(n)),
+        }
+    }
+
+    // Whether `name(...)` calls a function, rather than being plain CSS.
+    pub fn is_function(&self, name: &str) -> bool {
+        name == "keywords" || name == "content-exists" ||
+        self.is_builtin(name, "sass:math", "div") ||
+        self.functions.contains_key(name) ||
+        self.get_function_rule(name).is_some()
+    }
+
+    // Whether `name` is `member` of the built-in module `url`, as in
+    // `math.div` after `@use "sass:math"`.
+    pub fn is_builtin(&self, name: &str, url: &str, member: &str) -> bool {
+        match name.find('.') {
+            Some(dot) => &name[dot + 1..] == member && self.modules.get(&name[..dot]).is_some_and(|m| m.url == url),
+            None => name == member && self.global_modules.iter().any(|m| m.url == url),
+        }
+    }
+
+    // Looks `name` up as `namespace.member`, or in the modules used
+    // without a namespace.
+    fn module_member<T, F>(&self, name: &str, get: F) -> Option<T>
+        where T: Clone, F: for<'m> Fn(&'m Module, &str) -> Option<&'m T> {
+        match name.find('.') {
+            Some(dot) => self.modules.get(&name[..dot]).and_then(|m|
+                get(m, &name[dot + 1..]).cloned()
+            ),
+            None => self.global_modules.iter().filter_map(|m| get(m, name)).next().cloned(),
         }
     }
 
@@ -30,15 +125,15 @@ impl Context {
                 name
             } => {
                 SassVariable {
-                    name: name,
+                    name,
                     value: Expression::Value(
                         OperatorOrToken::Token(TokenOffset {
                             token: Token::Number {
-                                value: value,
-                                units: units,
+                                value,
+                                units,
                                 computed: true,
                             },
-                            offset: offset,
+                            offset,
                         })
                     ),
                 }
@@ -50,9 +145,23 @@ impl Context {
     }
 
     pub fn get_variable(&self, token_offset: &TokenOffset) -> Option<Expression> {
-        self.variables.get(
-            &token_offset.token.to_string()
-        ).and_then( |sv| Some(sv.value.clone()) )
+        let name = token_offset.token.to_string();
+        if let Some(sv) = self.variables.get(&name) {
+            return Some(sv.value.clone())
+        }
+
+        match name.find(".$") {
+            Some(dot) => {
+                self.modules.get(&name[..dot]).and_then(|m|
+                    m.get_variable(&name[dot + 1..])
+                )
+            },
+            None => {
+                self.global_modules.iter().filter_map(|m|
+                    m.get_variable(&name)
+                ).next()
+            },
+        }
     }
 }
 
@@ -64,6 +173,42 @@ mod tests {
     use sass::variable::SassVariable;
     use operator_or_token::OperatorOrToken;
     use ast::expression::Expression;
+    use module::Module;
+
+    fn red() -> Expression {
+        Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(String::from("red")),
+            offset: None,
+        }))
+    }
+
+    fn name(n: &str) -> TokenOffset {
+        TokenOffset { token: Token::String(String::from(n)), offset: None }
+    }
+
+    fn module_with_color() -> Module {
+        let mut module = Module::new("tokens");
+        module.add_member(SassVariable { name: name("$color"), value: red() });
+        module
+    }
+
+    #[test]
+    fn it_gets_namespaced_module_variables() {
+        let mut context = Context::new();
+        context.add_module(String::from("t"), module_with_color());
+
+        assert_eq!(context.get_variable(&name("t.$color")), Some(red()));
+        assert_eq!(context.get_variable(&name("$color")), None);
+        assert_eq!(context.get_variable(&name("tokens.$color")), None);
+    }
+
+    #[test]
+    fn it_gets_global_module_variables_without_a_namespace() {
+        let mut context = Context::new();
+        context.add_module(String::from("*"), module_with_color());
+
+        assert_eq!(context.get_variable(&name("$color")), Some(red()));
+    }
 
     #[test]
     fn it_sets_number_token_computed_to_true() {
@@ -0,0 +1,218 @@
+use ast::node::Node;
+use ast::root::Root;
+use error::{Result, SassError, ErrorKind};
+use formatter::{parse_all, format_roots};
+use importer::Syntax;
+use parser::Parser;
+use sass::rule::SassRule;
+use token::Token;
+use token_offset::TokenOffset;
+use tokenizer::Tokenizer;
+
+// Rewrites a stylesheet from one syntax to another, laid out the way
+// `format_scss` would. Plain CSS comes in with rules that share the start
+// of their selector nested together; going to CSS is what compiling is
+// for, so that isn't supported here.
+pub fn convert(text: &str, from: Syntax, to: Syntax) -> Result<String> {
+    if to == Syntax::Css {
+        return Err(SassError {
+            offset: 0,
+            kind: ErrorKind::InvalidOutputStyle,
+            message: String::from("Compile the stylesheet to convert it to CSS."),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    let tokenizer = match from {
+        Syntax::Sass => Tokenizer::indented(text, 0),
+        _ => Tokenizer::new(text),
+    };
+    let mut roots = parse_all(Parser::with_tokenizer(tokenizer.keeping_line_comments()))?;
+    if from == Syntax::Css {
+        roots = nest_roots(roots);
+    }
+    Ok(format_roots(&roots, to))
+}
+
+fn nest_roots(roots: Vec<Root>) -> Vec<Root> {
+    let mut nested = vec![];
+    let mut run = vec![];
+    for root in roots.into_iter() {
+        match root {
+            Root::Rule(rule) => run.push(rule),
+            other => {
+                nested.extend(nest(run).into_iter().map(Root::Rule));
+                run = vec![];
+                nested.push(other);
+            },
+        }
+    }
+    nested.extend(nest(run).into_iter().map(Root::Rule));
+    nested
+}
+
+fn nest_children(children: Vec<Node>) -> Vec<Node> {
+    let mut nested = vec![];
+    let mut run = vec![];
+    for child in children.into_iter() {
+        match child {
+            Node::Rule(rule) => run.push(rule),
+            other => {
+                nested.extend(nest(run).into_iter().map(Node::Rule));
+                run = vec![];
+                nested.push(other);
+            },
+        }
+    }
+    nested.extend(nest(run).into_iter().map(Node::Rule));
+    nested
+}
+
+// Groups neighbouring rules under the first part of their selector, so
+// `a b {}` and `a > i {}` become `a { b {} > i {} }`. A rule only joins
+// the group right before it, since moving it any further would change
+// which rules win. Rules with more than one selector are left where they
+// are.
+fn nest(rules: Vec<SassRule>) -> Vec<SassRule> {
+    let mut nested: Vec<SassRule> = vec![];
+    let mut in_group = false;
+    for rule in rules.into_iter() {
+        if rule.selectors.len() != 1 {
+            nested.push(rule);
+            in_group = false;
+            continue;
+        }
+        let offset = rule.selectors[0].offset;
+        let (head, rest) = split_selector(&rule.selectors[0].token.to_string());
+
+        let joins = in_group && nested.last().is_some_and(|group|
+            group.selectors[0].token.to_string() == head
+        );
+        if !joins {
+            nested.push(SassRule {
+                selectors: vec![selector(head, offset)],
+                children: vec![],
+            });
+            in_group = true;
+        }
+        let parent = nested.last_mut().unwrap();
+        match rest {
+            // Properties go ahead of the rules nested in with them.
+            None => {
+                let first_rule = parent.children.iter()
+                                                 .position(|c| matches!(*c, Node::Rule(..)))
+                                                 .unwrap_or(parent.children.len());
+                let after = parent.children.split_off(first_rule);
+                parent.children.extend(rule.children);
+                parent.children.extend(after);
+            },
+            Some(rest) => parent.children.push(Node::Rule(SassRule {
+                selectors: vec![selector(rest, offset)],
+                children: rule.children,
+            })),
+        }
+    }
+
+    for rule in nested.iter_mut() {
+        let children = ::std::mem::take(&mut rule.children);
+        rule.children = nest_children(children);
+    }
+    nested
+}
+
+fn selector(text: String, offset: Option<usize>) -> TokenOffset {
+    TokenOffset {
+        token: Token::String(text),
+        offset,
+    }
+}
+
+// Splits a selector after its first compound selector. A leading
+// combinator stays with the compound selector after it, and anything in
+// brackets, parentheses or quotes is left whole. Pseudo classes stay put,
+// since a nested `&:hover` would be read back as a property.
+fn split_selector(selector: &str) -> (String, Option<String>) {
+    let selector = selector.trim();
+    let start = if selector.starts_with(['>', '+', '~']) {
+        selector.len() - selector[1..].trim_start().len()
+    } else {
+        0
+    };
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in selector.char_indices().skip_while(|&(i, _)| i < start) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {},
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') => depth += 1,
+            (None, ')') | (None, ']') => depth -= 1,
+            (None, c) if depth == 0 && c.is_whitespace() => {
+                let rest = selector[i..].trim();
+                return (String::from(&selector[..i]), Some(String::from(rest)))
+            },
+            _ => {},
+        }
+    }
+    (String::from(selector), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use importer::Syntax;
+
+    #[test]
+    fn it_converts_sass_to_scss_and_back() {
+        let sass = "// Colors\n$c: red\n\na,\nb\n  color: $c\n\n  em\n    width: 1px\n";
+        let scss = "// Colors\n$c: red;\n\na,\nb {\n  color: $c;\n\n  em {\n    width: 1px;\n  }\n}\n";
+        assert_eq!(convert(sass, Syntax::Sass, Syntax::Scss), Ok(String::from(scss)));
+        assert_eq!(convert(scss, Syntax::Scss, Syntax::Sass), Ok(String::from(sass)));
+    }
+
+    #[test]
+    fn it_nests_css_by_shared_selectors() {
+        let css = "a b { color: red; }\na > i { width: 1px; }\na { margin: 0; }\n\
+                   a:hover b { color: blue; }\np, q { color: green; }";
+        assert_eq!(
+            convert(css, Syntax::Css, Syntax::Scss),
+            Ok(String::from(
+                "a {\n  margin: 0;\n\n  b {\n    color: red;\n  }\n\n  \
+                   > i {\n    width: 1px;\n  }\n}\n\n\
+                 a:hover {\n  b {\n    color: blue;\n  }\n}\n\n\
+                 p,\nq {\n  color: green;\n}\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_only_nests_a_rule_with_the_group_right_before_it() {
+        let css = ".a .x{c:1}.b .x{c:2}.a .x{c:3}";
+        assert_eq!(
+            convert(css, Syntax::Css, Syntax::Scss),
+            Ok(String::from(
+                ".a {\n  .x {\n    c: 1;\n  }\n}\n\n\
+                 .b {\n  .x {\n    c: 2;\n  }\n}\n\n\
+                 .a {\n  .x {\n    c: 3;\n  }\n}\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_reads_css_without_a_semicolon_before_a_closing_brace() {
+        assert_eq!(
+            convert(".a{color:red}\n.b{margin:0;width:1px}", Syntax::Css, Syntax::Scss),
+            Ok(String::from(".a {\n  color: red;\n}\n\n.b {\n  margin: 0;\n  width: 1px;\n}\n"))
+        );
+    }
+
+    #[test]
+    fn it_splits_selectors() {
+        assert_eq!(split_selector("a"), (String::from("a"), None));
+        assert_eq!(split_selector("a  b c"), (String::from("a"), Some(String::from("b c"))));
+        assert_eq!(split_selector("a[title=\"x y\"] b"),
+                   (String::from("a[title=\"x y\"]"), Some(String::from("b"))));
+        assert_eq!(split_selector("> i b"), (String::from("> i"), Some(String::from("b"))));
+    }
+}
@@ -1,20 +1,94 @@
+use source::SourceFiles;
+
 use std::result;
 use std::io;
-use std::error::{self, Error};
+use std::error;
 use std::fmt;
 
 pub type Result<T> = result::Result<T, SassError>;
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,Clone,PartialEq)]
 pub struct SassError {
     pub message: String,
     pub kind: ErrorKind,
     pub offset: usize,
+    // Filled in from `offset` once the error reaches the compiler, which
+    // knows which file it's in.
+    pub location: Option<Box<Location>>,
+    // The offset and name of each mixin or function call the error
+    // happened inside, innermost first.
+    pub calls: Vec<(usize, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub url: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub source_line: String,
+    // Where the error is, followed by each call and each `@import` or `@use`
+    // that led to it, ending with the stylesheet being compiled.
+    pub stack: Vec<Frame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub url: String,
+    pub line: usize,
+    pub column: usize,
+    pub name: String,
 }
 
 impl fmt::Display for SassError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?} at {}: {}", self.kind, self.offset, self.message)
+        let location = match self.location {
+            Some(ref location) => location,
+            None => return write!(f, "{:?} at {}: {}", self.kind, self.offset, self.message),
+        };
+
+        let line_number = location.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        writeln!(f, "Error: {}", self.message)?;
+        writeln!(f, "{} \u{2577}", gutter)?;
+        writeln!(f, "{} \u{2502} {}", line_number, location.source_line)?;
+        writeln!(f, "{} \u{2502} {}{}",
+            gutter,
+            " ".repeat(location.column - 1),
+            "^".repeat(location.length),
+        )?;
+        write!(f, "{} \u{2575}", gutter)?;
+
+        let frames = location.stack.iter().map(|frame|
+            format!("{} {}:{}", frame.url, frame.line, frame.column)
+        ).collect::<Vec<_>>();
+        let width = frames.iter().map(|fr| fr.len()).max().unwrap_or(0);
+        for (frame, position) in location.stack.iter().zip(frames.iter()) {
+            write!(f, "\n  {:width$}  {}", position, frame.name, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+impl SassError {
+    // Records that the error happened inside a call to `name` at `offset`.
+    pub fn called_from(mut self, offset: Option<usize>, name: &str) -> SassError {
+        self.calls.push((offset.unwrap_or(0), format!("{}()", name)));
+        self
+    }
+
+    // Fills in where the error is, for one that came from `text` alone
+    // rather than from a compilation.
+    pub fn in_file(self, url: &str, text: &str) -> SassError {
+        let mut sources = SourceFiles::new();
+        sources.add(url, text, None);
+        let location = self.location.clone().or_else(||
+            sources.locate(self.offset, &self.calls).map(Box::new)
+        );
+        SassError {
+            location,
+            ..self
+        }
     }
 }
 
@@ -23,7 +97,7 @@ impl error::Error for SassError {
         &self.message
     }
 
-    fn cause(&self) -> Option<&error::Error> {
+    fn cause(&self) -> Option<&dyn error::Error> {
         Some(self)
     }
 }
@@ -32,17 +106,31 @@ impl From<io::Error> for SassError {
     fn from(err: io::Error) -> SassError {
         SassError {
             offset: 0,
-            message: String::from(err.description()),
-            kind: ErrorKind::IoError
+            message: err.to_string(),
+            kind: ErrorKind::IoError,
+            location: None,
+            calls: vec![],
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ErrorKind {
     IoError,
     InvalidOutputStyle,
     TokenizerError,
     ParserError,
     UnexpectedEof,
+    ModuleError,
+    ImportError,
+    TypeError,
+    UndefinedVariable,
+    IncompatibleUnits,
+    FunctionError,
+    // Arguments that don't match what a mixin or function takes.
+    ArgumentError,
+    UndefinedMixin,
+    UndefinedFunction,
+    // Raised by `@error` in the stylesheet.
+    UserError,
 }
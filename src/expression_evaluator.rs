@@ -4,7 +4,15 @@ use operator::Operator;
 use operator_offset::OperatorOffset;
 use operator_or_token::OperatorOrToken;
 use token_offset::TokenOffset;
-use ast::expression::Expression;
+use ast::expression::{Expression, split_at_depth};
+use sass::arguments::ArgumentInvocation;
+use error::{Result, SassError, ErrorKind};
+use tokenizer::Tokenizer;
+use sass::message_rule::unquote;
+use ast::calculation::{Calculation, is_calculation};
+
+use std::iter::Peekable;
+use std::vec::IntoIter;
 
 pub struct ExpressionEvaluator<'a> {
     context: &'a Context,
@@ -15,27 +23,99 @@ pub struct ExpressionEvaluator<'a> {
 }
 
 impl<'a> ExpressionEvaluator<'a> {
-    pub fn evaluate(expr: Expression, context: &Context) -> Expression {
+    pub fn evaluate(expr: Expression, context: &Context) -> Result<Expression> {
         match expr {
             Expression::Value(OperatorOrToken::Token(t @ TokenOffset {
                 token: Token::String(_), ..
-            })) => {
-                context.get_variable(&t)
-                       .unwrap_or(Expression::Value(
-                            OperatorOrToken::Token(t)
-                       ))
-            },
+            })) => ExpressionEvaluator::lookup(t, context),
             Expression::List(exprs) => {
                 let evaluator = ExpressionEvaluator::new(context);
                 evaluator.evaluate_list(exprs)
             },
-            other => other,
+            other => Ok(other),
+        }
+    }
+
+    // Strings that name a variable are replaced by its value; any other
+    // string is a plain CSS value, with any `#{...}` in it filled in.
+    fn lookup(t: TokenOffset, context: &Context) -> Result<Expression> {
+        if let Some(value) = context.get_variable(&t) {
+            return value.without_slash()
+        }
+
+        let name = t.token.to_string();
+        if name.to_lowercase().starts_with("calc(") && !name.contains("#{") {
+            return Calculation::evaluate_text(&name, t.offset, context)
+        }
+        if name.contains("#{") {
+            return Ok(Expression::Value(OperatorOrToken::Token(TokenOffset {
+                token: Token::String(ExpressionEvaluator::interpolate(&name, t.offset, context)?),
+                offset: t.offset,
+            })))
+        }
+        // `-$x`, which the tokenizer keeps as one string.
+        if name.starts_with("-$") {
+            let variable = TokenOffset { token: Token::String(name[1..].to_string()), offset: t.offset };
+            let value = ExpressionEvaluator::lookup(variable, context)?;
+            let token = match value {
+                Expression::Value(OperatorOrToken::Token(TokenOffset {
+                    token: Token::Number { value, units, computed }, ..
+                })) => Token::Number { value: -value, units, computed },
+                other => Token::String(format!("-{}", other)),
+            };
+            return Ok(Expression::Value(OperatorOrToken::Token(TokenOffset { token, offset: t.offset })))
+        }
+        if name.starts_with("$") || name.contains(".$") {
+            Err(SassError {
+                offset: t.offset.unwrap_or(0),
+                kind: ErrorKind::UndefinedVariable,
+                message: format!("Undefined variable `{}`.", name),
+                location: None,
+                calls: vec![],
+            })
+        } else {
+            Ok(Expression::Value(OperatorOrToken::Token(t)))
         }
     }
 
-    pub fn new(context: &Context) -> ExpressionEvaluator {
+    // Fills in each `#{...}` in `text`, like the special functions the
+    // tokenizer keeps whole, with its value unquoted.
+    pub fn interpolate(text: &str, offset: Option<usize>, context: &Context) -> Result<String> {
+        let mut interpolated = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("#{") {
+            interpolated.push_str(&rest[..start]);
+            let inside = &rest[start + 2..];
+            let mut brace_level = 0;
+            let end = inside.char_indices().position(|(_, c)| {
+                match c {
+                    '{' => brace_level += 1,
+                    '}' if brace_level == 0 => return true,
+                    '}' => brace_level -= 1,
+                    _ => {},
+                }
+                false
+            }).map(|i| inside.char_indices().nth(i).unwrap().0);
+            let end = end.ok_or_else(|| SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ParserError,
+                message: String::from("Expected `}` to end the interpolation."),
+                location: None,
+                calls: vec![],
+            })?;
+            let tokens = Tokenizer::starting_at(&inside[..end], offset.unwrap_or(0))
+                             .collect::<Result<Vec<_>>>()?;
+            let value = ExpressionEvaluator::evaluate(Expression::from_tokens(tokens), context)?;
+            interpolated.push_str(&unquote(&value.to_string()));
+            rest = &inside[end + 1..];
+        }
+        interpolated.push_str(rest);
+        Ok(interpolated)
+    }
+
+    pub fn new(context: &Context) -> ExpressionEvaluator<'_> {
         ExpressionEvaluator {
-            context: context,
+            context,
             paren_level: 0,
             last_was_an_operator: true,
             value_stack: Vec::new(),
@@ -43,46 +123,52 @@ impl<'a> ExpressionEvaluator<'a> {
         }
     }
 
-    pub fn evaluate_list(mut self, exprs: Vec<Expression>) -> Expression {
+    pub fn evaluate_list(mut self, exprs: Vec<Expression>) -> Result<Expression> {
+        if let Some(map) = self.evaluate_map(&exprs)? {
+            return Ok(map)
+        }
+        if exprs.iter().any(|e| e.is_comma()) {
+            let offset = exprs.iter().find(|e| e.is_comma()).and_then(|e| e.offset());
+            let elements = split_at_depth(&exprs, Operator::Comma);
+            if elements.len() > 1 {
+                let mut evaluated = vec![];
+                for element in elements.into_iter().filter(|e| !e.is_empty()) {
+                    let mut evaluator = ExpressionEvaluator::new(self.context);
+                    evaluator.paren_level = self.paren_level;
+                    evaluated.push(evaluator.evaluate_list(element)?);
+                }
+                return Ok(Expression::comma_list(evaluated, offset))
+            }
+        }
 
         // Split into value stacks and operator stacks
-        let mut exprs = exprs.into_iter();
+        let mut exprs = exprs.into_iter().peekable();
 
         while let Some(part) = exprs.next() {
             debug!("Processing list item {:#?}", part);
 
-            if part.is_number() {
-                if !self.last_was_an_operator {
-                    debug!("Number, last_was_an_operator=false, paren_level={}", self.paren_level);
-
-                    if self.paren_level > 0 {
-                        self.do_math_until_left_paren();
-                    }
-                }
-                self.push_on_value_stack(part);
-
-            } else if part.is_right_paren() {
+            if part.is_right_paren() {
                 debug!("RIGHT PAREN");
                 debug!("op stack = {:#?}", self.op_stack);
 
-                self.do_math_until_left_paren();
+                self.do_math_until_left_paren()?;
                 self.op_stack.pop();
 
                 self.last_was_an_operator = false;
                 self.paren_level -= 1;
             } else if part.is_left_paren() {
-                let oo = part.extract_operator_offset();
+                let oo = part.extract_operator_offset()?;
                 debug!("Push on op stack Leftparen");
                 self.op_stack.push(oo);
                 self.last_was_an_operator = true;
                 self.paren_level += 1;
             } else if part.is_operator() {
-                let oo = part.extract_operator_offset();
+                let oo = part.extract_operator_offset()?;
                 if let Some(&last_operator) = self.op_stack.last() {
                     if last_operator
                            .operator
                            .same_or_greater_precedence(oo.operator) {
-                        self.math_machine();
+                        self.math_machine()?;
                     }
                 }
                 debug!("Push on op stack {:#?}", oo);
@@ -91,15 +177,43 @@ impl<'a> ExpressionEvaluator<'a> {
 
             } else if part.is_string() {
 
-                let t = part.extract_token_offset();
-                let var_eval = self.context.get_variable(&t)
-                                .unwrap_or(Expression::Value(
-                                    OperatorOrToken::Token(t)
-                                ));
-                self.push_on_value_stack(var_eval);
+                let t = part.extract_token_offset()?;
+                let name = t.token.to_string();
+                let is_call = exprs.peek().is_some_and(|e| e.is_left_paren()) &&
+                              self.context.is_function(&name);
+                let is_calculation = exprs.peek().is_some_and(|e| e.is_left_paren()) &&
+                                     is_calculation(&name);
+                let is_css_function = exprs.peek().is_some_and(|e| is_opening_paren_of(&t, e));
+
+                if is_call {
+                    exprs.next();
+                    let args = ArgumentInvocation::parse(
+                        ExpressionEvaluator::call_arguments(&mut exprs), t.offset
+                    )?;
+                    let result = self.call_function(&name, t.offset, args)?;
+                    self.push_on_value_stack(result.without_slash()?)?;
+                } else if is_calculation {
+                    exprs.next();
+                    let args = ExpressionEvaluator::call_arguments(&mut exprs).into_iter().filter_map(|a|
+                        match a {
+                            Expression::Value(v) => Some(v),
+                            _ => None,
+                        }
+                    ).collect();
+                    let result = Calculation::evaluate(&name, args, t.offset, self.context)?;
+                    self.push_on_value_stack(result)?;
+                } else if is_css_function {
+                    exprs.next();
+                    let args = ExpressionEvaluator::call_arguments(&mut exprs);
+                    let result = self.css_function(&name, t.offset, args)?;
+                    self.push_on_value_stack(result)?;
+                } else {
+                    let var_eval = ExpressionEvaluator::lookup(t, self.context)?;
+                    self.push_on_value_stack(var_eval)?;
+                }
 
             } else {
-                self.push_on_value_stack(part);
+                self.push_on_value_stack(part)?;
             }
         }
 
@@ -109,16 +223,175 @@ impl<'a> ExpressionEvaluator<'a> {
 
         // Process the stacks
         while !self.op_stack.is_empty() {
-            self.math_machine()
+            self.math_machine()?;
         }
 
-        self.value_stack.pop().unwrap()
+        self.get_value(None)
+    }
+
+    // A map literal like `(key: value, ...)`, with each value evaluated.
+    fn evaluate_map(&self, exprs: &[Expression]) -> Result<Option<Expression>> {
+        let entries = match Expression::List(exprs.to_vec()).map_entries() {
+            Some(entries) => entries,
+            None => return Ok(None),
+        };
+        let mut evaluated = vec![];
+        for (key, value) in entries.into_iter() {
+            evaluated.push((key, ExpressionEvaluator::evaluate(value, self.context)?));
+        }
+        Ok(Some(Expression::map(evaluated, exprs[0].offset())))
+    }
+
+    // Takes everything up to the parenthesis closing a function call.
+    fn call_arguments(exprs: &mut Peekable<IntoIter<Expression>>) -> Vec<Expression> {
+        let mut args = vec![];
+        let mut depth = 0;
+
+        for part in exprs.by_ref() {
+            if part.is_right_paren() && depth == 0 {
+                break
+            }
+            if part.is_left_paren() {
+                depth += 1;
+            } else if part.is_right_paren() {
+                depth -= 1;
+            }
+            args.push(part);
+        }
+        args
     }
 
-    fn push_on_value_stack(&mut self, expr: Expression) {
+    fn call_function(&self, name: &str, offset: Option<usize>, args: ArgumentInvocation) -> Result<Expression> {
+        if let Some(function) = self.context.get_function_rule(name) {
+            let args = args.evaluate(self.context)?;
+            return function.call(args, self.context, offset)
+        }
+        if name == "keywords" {
+            return self.keywords(offset, args)
+        }
+        if self.context.is_builtin(name, "sass:math", "div") {
+            return self.math_div(offset, args)
+        }
+        if name == "content-exists" {
+            let exists = if self.context.content.is_some() { "true" } else { "false" };
+            return Ok(Expression::Value(OperatorOrToken::Token(TokenOffset {
+                token: Token::String(String::from(exists)),
+                offset,
+            })))
+        }
+
+        let args = args.evaluate(self.context)?;
+        if let Some((keyword, _)) = args.named.first() {
+            return Err(SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ArgumentError,
+                message: format!("No argument named {}.", keyword),
+                location: None,
+                calls: vec![],
+            })
+        }
+        let args = args.positional.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let result = self.context.functions[name].call(&args).map_err(|e|
+            SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::FunctionError,
+                message: format!("Error in function {}(): {}", name, e.message),
+                location: None,
+                calls: vec![],
+            }
+        )?;
+
+        let tokens = Tokenizer::new(&result).filter_map(|t| t.ok()).map(|t|
+            match t {
+                OperatorOrToken::Token(TokenOffset { token, .. }) => {
+                    OperatorOrToken::Token(TokenOffset { token, offset })
+                },
+                OperatorOrToken::Operator(OperatorOffset { operator, .. }) => {
+                    OperatorOrToken::Operator(OperatorOffset { operator, offset })
+                },
+            }
+        ).collect();
+        Ok(Expression::from_tokens(tokens))
+    }
+
+    // A plain CSS function, like `url($image)` or `translate($x, 0)`, stays
+    // as it is with its arguments evaluated.
+    fn css_function(&self, name: &str, offset: Option<usize>, args: Vec<Expression>) -> Result<Expression> {
+        // A member of a module that isn't there, like a hidden one, is an
+        // error rather than plain CSS.
+        if let Some(dot) = name.find('.') {
+            if self.context.modules.contains_key(&name[..dot]) {
+                return Err(SassError {
+                    offset: offset.unwrap_or(0),
+                    kind: ErrorKind::UndefinedFunction,
+                    message: format!("Undefined function `{}`.", name),
+                    location: None,
+                    calls: vec![],
+                })
+            }
+        }
+        let mut evaluated = vec![];
+        for arg in split_at_depth(&args, Operator::Comma).into_iter().filter(|a| !a.is_empty()) {
+            let value = ExpressionEvaluator::evaluate(Expression::from_parts(arg), self.context)?;
+            evaluated.push(value.to_string());
+        }
+        Ok(Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(format!("{}({})", name, evaluated.join(", "))),
+            offset,
+        })))
+    }
+
+    // `keywords($args)` is a map of the keyword arguments passed into the
+    // argument list `$args`, without their `$`s.
+    fn keywords(&self, offset: Option<usize>, args: ArgumentInvocation) -> Result<Expression> {
+        let name = match (args.positional.len(), args.positional.first()) {
+            (1, Some(argument)) => argument.to_string(),
+            _ => return Err(SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ArgumentError,
+                message: String::from("keywords() takes one argument list, like `$args`."),
+                location: None,
+                calls: vec![],
+            }),
+        };
+        let keywords = self.context.keywords(&name).ok_or_else(|| SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::TypeError,
+            message: format!("{} is not an argument list.", name),
+            location: None,
+            calls: vec![],
+        })?;
+        let entries = keywords.into_iter()
+                              .map(|(key, value)| (String::from(key.trim_start_matches('$')), value))
+                              .collect();
+        Ok(Expression::map(entries, offset))
+    }
+
+    // `math.div($number1, $number2)` always divides, unlike `/`.
+    fn math_div(&self, offset: Option<usize>, args: ArgumentInvocation) -> Result<Expression> {
+        let args = args.evaluate(self.context)?;
+        let mut positional = args.positional.into_iter();
+        match (args.named.is_empty(), positional.next(), positional.next(), positional.next()) {
+            (true, Some(first), Some(second), None) => Expression::divide(
+                OperatorOffset { operator: Operator::Slash, offset }, first, second
+            ),
+            _ => Err(SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ArgumentError,
+                message: String::from("math.div() takes two numbers, like `math.div($a, $b)`."),
+                location: None,
+                calls: vec![],
+            }),
+        }
+    }
+
+    // A value right after another one starts a space-separated list, which
+    // binds more loosely than any math before it: `12px/1.5 $font`.
+    fn push_on_value_stack(&mut self, expr: Expression) -> Result<()> {
         if self.last_was_an_operator {
             self.value_stack.push(expr);
         } else {
+            self.do_math_until_left_paren()?;
             let list = Expression::create_list(
                 self.value_stack.pop(),
                 expr,
@@ -126,28 +399,31 @@ impl<'a> ExpressionEvaluator<'a> {
             self.value_stack.push(list);
         }
         self.last_was_an_operator = false;
+        Ok(())
     }
 
-    fn do_math_until_left_paren(&mut self) {
+    fn do_math_until_left_paren(&mut self) -> Result<()> {
         while !self.op_stack.is_empty() &&
               self.op_stack.last().unwrap().operator != Operator::LeftParen {
-            self.math_machine();
+            self.math_machine()?;
         }
+        Ok(())
     }
 
-    fn math_machine(&mut self) {
+    fn math_machine(&mut self) -> Result<()> {
         debug!("Math machine:");
 
         let op     = self.get_operator();
-        let second = self.get_value();
-        let first  = self.get_value();
+        let second = self.get_value(Some(op))?;
+        let first  = self.get_value(Some(op))?;
 
         let math_result = Expression::apply_math(
             op, first, second, self.context, self.paren_level,
-        );
+        )?;
         debug!("Math result: {:#?}", math_result);
 
         self.value_stack.push(math_result);
+        Ok(())
     }
 
     fn get_operator(&mut self) -> OperatorOffset {
@@ -158,12 +434,29 @@ impl<'a> ExpressionEvaluator<'a> {
         op
     }
 
-    fn get_value(&mut self) -> Expression {
-        // TODO: Turn this into a SassError
-        let val = self.value_stack.pop()
-                      .expect("Expected an argument on the value stack");
+    // `operator` is the operator that needs the value, if any.
+    fn get_value(&mut self, operator: Option<OperatorOffset>) -> Result<Expression> {
+        let val = self.value_stack.pop().ok_or_else(|| SassError {
+            offset: operator.and_then(|o| o.offset).unwrap_or(0),
+            kind: ErrorKind::ParserError,
+            message: match operator {
+                Some(o) => format!("Expected a value on each side of `{}`.", o),
+                None => String::from("Expected a value."),
+            },
+            location: None,
+            calls: vec![],
+        })?;
         debug!("val = {:#?}", val);
-        val
+        Ok(val)
+    }
+}
+
+// Whether `next` is the `(` right after the name `name`, as in `url(`,
+// rather than a value in parentheses after a word.
+fn is_opening_paren_of(name: &TokenOffset, next: &Expression) -> bool {
+    next.is_left_paren() && match (name.offset, next.offset()) {
+        (Some(start), Some(paren)) => start + name.token.to_string().len() == paren,
+        _ => false,
     }
 }
 
@@ -177,6 +470,9 @@ mod tests {
     use operator_offset::OperatorOffset;
     use context::Context;
     use ast::expression::Expression;
+    use error::{Result, SassError, ErrorKind};
+    use sass::variable::SassVariable;
+    use tokenizer::Tokenizer;
 
     fn one() -> OperatorOrToken {
         OperatorOrToken::Token(
@@ -238,16 +534,18 @@ mod tests {
         let fake_context = Context::new();
         assert_eq!(
             ExpressionEvaluator::evaluate(ex, &fake_context),
-            Expression::Value(OperatorOrToken::Token(
-                TokenOffset {
-                    token: Token::Number {
-                        value: 1.0,
-                        units: None,
-                        computed: true,
-                    },
-                    offset: None,
-                }
-            ))
+            Ok(
+                Expression::Value(OperatorOrToken::Token(
+                    TokenOffset {
+                        token: Token::Number {
+                            value: 1.0,
+                            units: None,
+                            computed: true,
+                        },
+                        offset: None,
+                    }
+                ))
+            )
         );
     }
 
@@ -267,34 +565,36 @@ mod tests {
         let fake_context = Context::new();
         assert_eq!(
             ExpressionEvaluator::evaluate(ex, &fake_context),
-            Expression::List(vec![
-                Expression::Value(OperatorOrToken::Token(
-                    TokenOffset {
-                        token: Token::String(String::from("10.5")),
-                        offset: None,
-                    }
-                )),
-                Expression::Value(OperatorOrToken::Token(
-                    TokenOffset {
-                        token: Token::Number {
-                            value: 2.0,
-                            units: None,
-                            computed: false,
-                        },
-                        offset: None,
-                    }
-                )),
-                Expression::Value(OperatorOrToken::Token(
-                    TokenOffset {
-                        token: Token::Number {
-                            value: 2.0,
-                            units: None,
-                            computed: false,
-                        },
-                        offset: None,
-                    }
-                ))
-            ])
+            Ok(
+                Expression::List(vec![
+                    Expression::Value(OperatorOrToken::Token(
+                        TokenOffset {
+                            token: Token::String(String::from("10.5")),
+                            offset: None,
+                        }
+                    )),
+                    Expression::Value(OperatorOrToken::Token(
+                        TokenOffset {
+                            token: Token::Number {
+                                value: 2.0,
+                                units: None,
+                                computed: false,
+                            },
+                            offset: None,
+                        }
+                    )),
+                    Expression::Value(OperatorOrToken::Token(
+                        TokenOffset {
+                            token: Token::Number {
+                                value: 2.0,
+                                units: None,
+                                computed: false,
+                            },
+                            offset: None,
+                        }
+                    ))
+                ])
+            )
         );
     }
 
@@ -310,13 +610,131 @@ mod tests {
         let fake_context = Context::new();
         assert_eq!(
             ExpressionEvaluator::evaluate(ex, &fake_context),
-            Expression::List(vec![
-                Expression::Value(one()),
-                Expression::Value(slash()),
-                Expression::Value(one()),
-                Expression::Value(slash()),
-                Expression::Value(two()),
-            ])
+            Ok(
+                Expression::List(vec![
+                    Expression::Value(one()),
+                    Expression::Value(slash()),
+                    Expression::Value(one()),
+                    Expression::Value(slash()),
+                    Expression::Value(two()),
+                ])
+            )
+        );
+    }
+
+    fn value(token: Token) -> Expression {
+        Expression::Value(OperatorOrToken::Token(
+            TokenOffset { token, offset: Some(3) }
+        ))
+    }
+
+    fn number(n: f64, units: &str) -> Expression {
+        value(Token::Number {
+            value: n, units: Some(units.into()), computed: false
+        })
+    }
+
+    #[test]
+    fn it_fills_in_interpolation_in_special_functions() {
+        let fake_context = Context::new();
+        assert_eq!(
+            ExpressionEvaluator::evaluate(value(Token::String("calc(100% - #{1 + 2}px)".into())), &fake_context),
+            Ok(value(Token::String("calc(100% - 3px)".into())))
+        );
+    }
+
+    fn evaluate_text(text: &str, context: &Context) -> String {
+        let tokens = Tokenizer::new(text).collect::<Result<Vec<_>>>().unwrap();
+        ExpressionEvaluator::evaluate(Expression::from_tokens(tokens), context).unwrap().to_string()
+    }
+
+    #[test]
+    fn it_keeps_url_around_sassscript_arguments() {
+        let mut context = Context::new();
+        context.add_variable(SassVariable {
+            name: TokenOffset { token: Token::String("$f".into()), offset: None },
+            value: value(Token::StringLiteral("\"a.png\"".into())),
+        });
+        assert_eq!(evaluate_text("url($f)", &context), "url(\"a.png\")");
+        assert_eq!(evaluate_text("url(a.png) no-repeat", &context), "url(a.png) no-repeat");
+    }
+
+    #[test]
+    fn it_evaluates_var_fallbacks() {
+        let mut context = Context::new();
+        context.add_variable(SassVariable {
+            name: TokenOffset { token: Token::String("$x".into()), offset: None },
+            value: number(2.0, "px"),
+        });
+        assert_eq!(evaluate_text("var(--a, $x * 2)", &context), "var(--a, 4px)");
+        assert_eq!(evaluate_text("var(--b)", &context), "var(--b)");
+    }
+
+    #[test]
+    fn it_negates_variables() {
+        let mut context = Context::new();
+        context.add_variable(SassVariable {
+            name: TokenOffset { token: Token::String("$x".into()), offset: None },
+            value: number(10.0, "px"),
+        });
+        context.add_variable(SassVariable {
+            name: TokenOffset { token: Token::String("$s".into()), offset: None },
+            value: value(Token::String("foo".into())),
+        });
+        assert_eq!(evaluate_text("-$x", &context), "-10px");
+        assert_eq!(evaluate_text("-$x / 2", &context), "-5px");
+        assert_eq!(evaluate_text("1 - $x", &context), "-9px");
+        assert_eq!(evaluate_text("-$s", &context), "-foo");
+    }
+
+    #[test]
+    fn it_errors_on_undefined_variables() {
+        let fake_context = Context::new();
+        assert_eq!(
+            ExpressionEvaluator::evaluate(value(Token::String("$nope".into())), &fake_context),
+            Err(SassError {
+                offset: 3,
+                kind: ErrorKind::UndefinedVariable,
+                message: String::from("Undefined variable `$nope`."),
+                location: None,
+                calls: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn it_errors_on_incompatible_units() {
+        let ex = Expression::List(vec![number(1.0, "px"), Expression::Value(plus()), number(1.0, "em")]);
+        let fake_context = Context::new();
+        assert_eq!(
+            ExpressionEvaluator::evaluate(ex, &fake_context),
+            Err(SassError {
+                offset: 3,
+                kind: ErrorKind::IncompatibleUnits,
+                message: String::from("Incompatible units px and em."),
+                location: None,
+                calls: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn it_errors_on_math_with_strings_or_missing_values() {
+        let fake_context = Context::new();
+        let ex = Expression::List(vec![
+            Expression::Value(one()),
+            Expression::Value(plus()),
+            value(Token::String("blue".into())),
+        ]);
+        assert_eq!(
+            ExpressionEvaluator::evaluate(ex, &fake_context).map_err(|e| e.kind),
+            Err(ErrorKind::TypeError)
+        );
+
+        let ex = Expression::List(vec![Expression::Value(one()), Expression::Value(plus())]);
+        assert_eq!(
+            ExpressionEvaluator::evaluate(ex, &fake_context).map_err(|e| e.kind),
+            Err(ErrorKind::ParserError)
         );
     }
 }
@@ -0,0 +1,423 @@
+use ast::expression::Expression;
+use ast::node::Node;
+use ast::root::Root;
+use error::Result;
+use importer::Syntax;
+use operator::Operator;
+use operator_or_token::OperatorOrToken;
+use parser::Parser;
+use sass::forward_rule::{SassForward, ForwardVisibility};
+use sass::import_rule::SassImport;
+use sass::arguments::{ArgumentDeclaration, ArgumentInvocation};
+use sass::at_root::{SassAtRoot, AtRootQuery};
+use sass::message_rule::{SassMessage, MessageKind};
+use sass::media::SassMedia;
+use sass::mixin::SassInclude;
+use sass::rule::SassRule;
+use sass::use_rule::SassUse;
+use sass::variable::SassVariable;
+use token::Token;
+use tokenizer::Tokenizer;
+
+// Writes SCSS back out from its unevaluated AST, so the same stylesheet
+// always comes out the same way: two space indentation, one selector per
+// line, one space after colons and around operators, and a blank line
+// around each rule. Comments are kept.
+pub fn format_scss(text: &str) -> Result<String> {
+    let roots = parse_all(Parser::with_tokenizer(
+        Tokenizer::new(text).keeping_line_comments()
+    ))?;
+    Ok(format_roots(&roots, Syntax::Scss))
+}
+
+// Every root, or the first error. Nothing can be formatted from a
+// stylesheet that didn't parse, so there's no point recovering.
+pub fn parse_all(parser: Parser) -> Result<Vec<Root>> {
+    let mut roots = vec![];
+    for root in parser {
+        roots.push(root?);
+    }
+    Ok(roots)
+}
+
+// Lays `roots` out as SCSS, or as the indented syntax when `syntax` is
+// `Syntax::Sass`, where the braces and semicolons are left off.
+pub fn format_roots(roots: &[Root], syntax: Syntax) -> String {
+    let mut formatted = String::new();
+    let mut previous_was_rule = false;
+    for (i, root) in roots.iter().enumerate() {
+        let is_rule = match *root {
+            Root::Rule(..) | Root::Mixin(..) | Root::Function(..) => true,
+            Root::Include(ref include) => include.content.is_some(),
+            Root::AtRoot(..) | Root::Supports(..) | Root::Media(..) => true,
+            _ => false,
+        };
+        if i > 0 && (is_rule || previous_was_rule) {
+            formatted.push('\n');
+        }
+        format_root(&mut formatted, root, syntax);
+        previous_was_rule = is_rule;
+    }
+    formatted
+}
+
+fn format_root(formatted: &mut String, root: &Root, syntax: Syntax) {
+    match *root {
+        Root::Rule(ref rule) => format_rule(formatted, rule, 0, syntax),
+        Root::Variable(ref variable) => format_variable(formatted, variable, 0, syntax),
+        Root::Comment(ref comment) => line(formatted, 0, &comment.content.token.to_string()),
+        Root::Use(ref sass_use) => statement(formatted, 0, use_rule(sass_use), syntax),
+        Root::Forward(ref sass_forward) => {
+            statement(formatted, 0, forward_rule(sass_forward), syntax)
+        },
+        Root::Import(ref imports) => statement(formatted, 0, import_rule(imports), syntax),
+        Root::Message(ref message) => statement(formatted, 0, message_rule(message), syntax),
+        Root::Mixin(ref mixin) => {
+            let header = format!("@mixin {}{}", mixin.name.token, declaration(&mixin.parameters));
+            format_block(formatted, &header, &mixin.children, 0, syntax);
+        },
+        Root::Function(ref function) => {
+            let header = format!("@function {}{}", function.name.token, declaration(&function.parameters));
+            format_block(formatted, &header, &function.children, 0, syntax);
+        },
+        Root::Include(ref include) => format_include(formatted, include, 0, syntax),
+        Root::AtRoot(ref at_root) => format_at_root(formatted, at_root, 0, syntax),
+        Root::Supports(ref supports) => {
+            let header = format!("@supports {}", supports.condition);
+            format_block(formatted, &header, &supports.children, 0, syntax)
+        },
+        Root::Media(ref media) => format_block(formatted, &media_header(media), &media.children, 0, syntax),
+    }
+}
+
+fn format_rule(formatted: &mut String, rule: &SassRule, depth: usize, syntax: Syntax) {
+    let last = rule.selectors.len().saturating_sub(1);
+    for (i, selector) in rule.selectors.iter().enumerate() {
+        let end = match (i == last, syntax) {
+            (false, _) => ",",
+            (true, Syntax::Sass) => "",
+            (true, _) => " {",
+        };
+        line(formatted, depth, &format!("{}{}", selector.token, end));
+    }
+    format_children(formatted, &rule.children, depth, syntax);
+    if syntax != Syntax::Sass {
+        line(formatted, depth, "}");
+    }
+}
+
+// An at-rule with a block, like a mixin or `@supports`, and its body.
+fn format_block(formatted: &mut String, header: &str, children: &[Node], depth: usize, syntax: Syntax) {
+    match syntax {
+        Syntax::Sass => line(formatted, depth, header),
+        _ => line(formatted, depth, &format!("{} {{", header)),
+    }
+    format_children(formatted, children, depth, syntax);
+    if syntax != Syntax::Sass {
+        line(formatted, depth, "}");
+    }
+}
+
+fn format_children(formatted: &mut String, children: &[Node], depth: usize, syntax: Syntax) {
+    for (i, child) in children.iter().enumerate() {
+        match *child {
+            Node::Rule(ref child_rule) => {
+                if i > 0 {
+                    formatted.push('\n');
+                }
+                format_rule(formatted, child_rule, depth + 1, syntax);
+            },
+            Node::Property(ref name, ref value) => {
+                let property = format!("{}: {}", name.token, expression(value));
+                statement(formatted, depth + 1, property, syntax);
+            },
+            Node::Variable(ref variable) => {
+                format_variable(formatted, variable, depth + 1, syntax)
+            },
+            Node::Comment(ref comment) => {
+                line(formatted, depth + 1, &comment.content.token.to_string());
+            },
+            Node::Import(ref imports) => {
+                statement(formatted, depth + 1, import_rule(imports), syntax)
+            },
+            Node::Message(ref message) => {
+                statement(formatted, depth + 1, message_rule(message), syntax)
+            },
+            Node::Include(ref include) => format_include(formatted, include, depth + 1, syntax),
+            Node::AtRoot(ref at_root) => {
+                if i > 0 {
+                    formatted.push('\n');
+                }
+                format_at_root(formatted, at_root, depth + 1, syntax);
+            },
+            Node::Supports(ref supports) => {
+                if i > 0 {
+                    formatted.push('\n');
+                }
+                let header = format!("@supports {}", supports.condition);
+                format_block(formatted, &header, &supports.children, depth + 1, syntax)
+            },
+            Node::Media(ref media) => {
+                if i > 0 {
+                    formatted.push('\n');
+                }
+                format_block(formatted, &media_header(media), &media.children, depth + 1, syntax)
+            },
+            Node::Content(ref content) => {
+                let rule = format!("@content{}", invocation(&content.arguments));
+                statement(formatted, depth + 1, rule, syntax)
+            },
+            Node::Return(ref value) => {
+                statement(formatted, depth + 1, format!("@return {}", expression(value)), syntax)
+            },
+        }
+    }
+}
+
+fn format_include(formatted: &mut String, include: &SassInclude, depth: usize, syntax: Syntax) {
+    let rule = format!("@include {}{}", include.name.token, invocation(&include.arguments));
+    match include.content {
+        Some(ref content) if content.parameters.parameters.is_empty() &&
+                             content.parameters.rest.is_none() => {
+            format_block(formatted, &rule, &content.children, depth, syntax)
+        },
+        Some(ref content) => {
+            let header = format!("{} using {}", rule, declaration(&content.parameters));
+            format_block(formatted, &header, &content.children, depth, syntax)
+        },
+        None => statement(formatted, depth, rule, syntax),
+    }
+}
+
+fn format_variable(formatted: &mut String, variable: &SassVariable, depth: usize, syntax: Syntax) {
+    let assignment = format!("{}: {}", variable.name.token, expression(&variable.value));
+    statement(formatted, depth, assignment, syntax);
+}
+
+fn statement(formatted: &mut String, depth: usize, mut content: String, syntax: Syntax) {
+    if syntax != Syntax::Sass {
+        content.push(';');
+    }
+    line(formatted, depth, &content);
+}
+
+fn line(formatted: &mut String, depth: usize, content: &str) {
+    formatted.push_str(&"  ".repeat(depth));
+    formatted.push_str(content);
+    formatted.push('\n');
+}
+
+fn use_rule(sass_use: &SassUse) -> String {
+    let mut rule = format!("@use {}", sass_use.url.token);
+    if let Some(ref namespace) = sass_use.namespace {
+        rule.push_str(&format!(" as {}", namespace));
+    }
+    rule.push_str(&configuration(&sass_use.config));
+    rule
+}
+
+fn forward_rule(sass_forward: &SassForward) -> String {
+    let mut rule = format!("@forward {}", sass_forward.url.token);
+    if let Some(ref prefix) = sass_forward.prefix {
+        rule.push_str(&format!(" as {}*", prefix));
+    }
+    match sass_forward.visibility {
+        ForwardVisibility::All => {},
+        ForwardVisibility::Show(ref names) => rule.push_str(&format!(" show {}", names.join(", "))),
+        ForwardVisibility::Hide(ref names) => rule.push_str(&format!(" hide {}", names.join(", "))),
+    }
+    rule.push_str(&configuration(&sass_forward.config));
+    rule
+}
+
+fn configuration(config: &[SassVariable]) -> String {
+    if config.is_empty() {
+        return String::new()
+    }
+    let variables = config.iter()
+                          .map(|v| format!("{}: {}", v.name.token, expression(&v.value)))
+                          .collect::<Vec<_>>();
+    format!(" with ({})", variables.join(", "))
+}
+
+fn import_rule(imports: &[SassImport]) -> String {
+    let urls = imports.iter().map(|import| match import.media {
+        Some(ref media) => format!("{} {}", import.url.token, media),
+        None => import.url.token.to_string(),
+    }).collect::<Vec<_>>();
+    format!("@import {}", urls.join(", "))
+}
+
+// Goes back to `@at-root selector { ... }` where that's what was written.
+fn media_header(media: &SassMedia) -> String {
+    let queries = media.queries.iter().map(|q| q.to_string()).collect::<Vec<_>>();
+    format!("@media {}", queries.join(", "))
+}
+
+fn format_at_root(formatted: &mut String, at_root: &SassAtRoot, depth: usize, syntax: Syntax) {
+    let header = match at_root.query {
+        AtRootQuery::With(ref names) => format!("@at-root (with: {})", names.join(" ")),
+        AtRootQuery::Without(ref names) if !at_root.query.is_default() => {
+            format!("@at-root (without: {})", names.join(" "))
+        },
+        AtRootQuery::Without(..) => match at_root.children.first() {
+            Some(Node::Rule(rule)) if at_root.children.len() == 1 => {
+                let selectors = rule.selectors.iter()
+                                    .map(|s| s.token.to_string())
+                                    .collect::<Vec<_>>();
+                let header = format!("@at-root {}", selectors.join(", "));
+                return format_block(formatted, &header, &rule.children, depth, syntax)
+            },
+            _ => String::from("@at-root"),
+        },
+    };
+    format_block(formatted, &header, &at_root.children, depth, syntax)
+}
+
+fn invocation(arguments: &ArgumentInvocation) -> String {
+    let mut formatted = arguments.positional.iter().map(expression).collect::<Vec<_>>();
+    formatted.extend(arguments.named.iter().map(|(name, value)|
+        format!("{}: {}", name, expression(value))
+    ));
+    if let Some(ref rest) = arguments.rest {
+        formatted.push(format!("{}...", expression(rest)));
+    }
+    if formatted.is_empty() {
+        String::new()
+    } else {
+        format!("({})", formatted.join(", "))
+    }
+}
+
+fn declaration(parameters: &ArgumentDeclaration) -> String {
+    let mut formatted = parameters.parameters.iter().map(|p| match p.default {
+        Some(ref default) => format!("{}: {}", p.name, expression(default)),
+        None => p.name.clone(),
+    }).collect::<Vec<_>>();
+    if let Some(ref rest) = parameters.rest {
+        formatted.push(format!("{}...", rest));
+    }
+    if formatted.is_empty() {
+        String::new()
+    } else {
+        format!("({})", formatted.join(", "))
+    }
+}
+
+fn message_rule(message: &SassMessage) -> String {
+    let name = match message.kind {
+        MessageKind::Debug => "@debug",
+        MessageKind::Warn => "@warn",
+        MessageKind::Error => "@error",
+    };
+    format!("{} {}", name, expression(&message.value))
+}
+
+// Spaces out a value's tokens: tight inside parentheses, before commas and
+// colons, around `/` and between a function name and its arguments, with
+// a single space everywhere else.
+fn expression(value: &Expression) -> String {
+    let mut tokens = vec![];
+    flatten(value, &mut tokens);
+
+    let mut formatted = String::new();
+    let mut previous: Option<&OperatorOrToken> = None;
+    for op_or_token in tokens.into_iter() {
+        let operator = op_or_token.extract_operator();
+        let previous_operator = previous.and_then(|p| p.extract_operator());
+        let function_call = match previous {
+            Some(OperatorOrToken::Token(t)) => match t.token {
+                Token::String(..) => operator == Some(Operator::LeftParen),
+                _ => false,
+            },
+            _ => false,
+        };
+        let tight = previous.is_none() || function_call || matches!((previous_operator, operator),
+            (Some(Operator::LeftParen), _) |
+            (Some(Operator::Slash), _) |
+            (_, Some(Operator::Slash)) |
+            (_, Some(Operator::RightParen)) |
+            (_, Some(Operator::Comma)) |
+            (_, Some(Operator::Colon)));
+        if !tight {
+            formatted.push(' ');
+        }
+        formatted.push_str(&op_or_token.to_string());
+        previous = Some(op_or_token);
+    }
+    formatted
+}
+
+fn flatten<'a>(value: &'a Expression, tokens: &mut Vec<&'a OperatorOrToken>) {
+    match *value {
+        Expression::Value(ref op_or_token) => tokens.push(op_or_token),
+        Expression::List(ref list) => {
+            for element in list.iter() {
+                flatten(element, tokens);
+            }
+        },
+        Expression::Calculation(..) => {}, // only made by evaluating
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_formats_rules_variables_and_comments() {
+        let scss = "// Colors\n$c:red   !default;\n@use \"tokens\"  as t;\n\
+                    a,b{color:$c;  margin :0 auto;\n\
+                    /* nested */ em{width:(1px+2px)*3;font:12px/1.5 darken( $c,10% );}}\n\
+                    @debug   $c;";
+        assert_eq!(
+            format_scss(scss),
+            Ok(String::from(
+                "// Colors\n\
+                 $c: red !default;\n\
+                 @use \"tokens\" as t;\n\
+                 \n\
+                 a,\n\
+                 b {\n  \
+                   color: $c;\n  \
+                   margin: 0 auto;\n  \
+                   /* nested */\n\
+                 \n  \
+                   em {\n    \
+                     width: (1px + 2px) * 3;\n    \
+                     font: 12px/1.5 darken($c, 10%);\n  \
+                   }\n\
+                 }\n\
+                 \n\
+                 @debug $c;\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_keeps_pseudo_selectors_together() {
+        assert_eq!(format_scss("a:hover, b::before{x:y;}"),
+                   Ok(String::from("a:hover,\nb::before {\n  x: y;\n}\n")));
+    }
+
+    #[test]
+    fn it_leaves_formatted_scss_alone() {
+        let scss = "$a: 1px;\n\na {\n  b: $a;\n}\n";
+        assert_eq!(format_scss(scss), Ok(String::from(scss)));
+    }
+
+    #[test]
+    fn it_locates_errors_in_the_file_being_formatted() {
+        let scss = "a {\n  b: c\n";
+        let error = format_scss(scss).unwrap_err().in_file("a.scss", scss);
+        assert_eq!(
+            error.to_string(),
+            "Error: Expected semicolon while parsing a value expression; reached EOF instead.\n  \
+             \u{2577}\n\
+             2 \u{2502}   b: c\n  \
+             \u{2502}      ^\n  \
+             \u{2575}\n  \
+             a.scss 2:6  root stylesheet"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use error::{Result, SassError, ErrorKind};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax {
+    Scss,
+    Sass,
+    Css,
+}
+
+impl Syntax {
+    pub fn for_path(path: &Path) -> Syntax {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("sass") => Syntax::Sass,
+            Some("css") => Syntax::Css,
+            _ => Syntax::Scss,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImporterResult {
+    pub contents: String,
+    pub syntax: Syntax,
+}
+
+// Finds and loads stylesheets. Each stylesheet is identified by a canonical
+// URL: two URLs that canonicalize to the same string are the same file, so
+// it's only loaded once and import cycles can be detected.
+pub trait Importer {
+    // `base` is the canonical URL of the stylesheet containing the `@import`
+    // or `@use`, or `None` for the stylesheet being compiled. Returns `None`
+    // if this importer doesn't know about `url`.
+    fn canonicalize(&self, url: &str, base: Option<&str>) -> Result<Option<String>>;
+
+    fn load(&self, canonical_url: &str) -> Result<ImporterResult>;
+}
+
+pub struct FilesystemImporter {
+    load_paths: Vec<PathBuf>,
+}
+
+impl FilesystemImporter {
+    pub fn new(load_paths: Vec<PathBuf>) -> FilesystemImporter {
+        FilesystemImporter {
+            load_paths,
+        }
+    }
+}
+
+impl Importer for FilesystemImporter {
+    // Looks next to the importing file first and then in each load path,
+    // in order.
+    fn canonicalize(&self, url: &str, base: Option<&str>) -> Result<Option<String>> {
+        let mut bases = match base {
+            Some(b) => vec![Path::new(b).with_file_name(url)],
+            None if Path::new(url).is_file() => vec![PathBuf::from(url)],
+            None => vec![],
+        };
+        bases.extend(self.load_paths.iter().map(|lp| lp.join(url)));
+
+        let found = resolve(bases, &|p: &Path| p.is_file())?;
+        Ok(found.map(|p| {
+            fs::canonicalize(&p).unwrap_or(p).to_string_lossy().into_owned()
+        }))
+    }
+
+    fn load(&self, canonical_url: &str) -> Result<ImporterResult> {
+        let mut file = File::open(canonical_url)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(ImporterResult {
+            contents,
+            syntax: Syntax::for_path(Path::new(canonical_url)),
+        })
+    }
+}
+
+// Serves stylesheets from memory, keyed by path-like URLs such as
+// `src/_colors.scss`, following the same partial, extension and index
+// rules as files on disk.
+pub struct MemoryImporter {
+    files: HashMap<String, String>,
+}
+
+impl Default for MemoryImporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryImporter {
+    pub fn new() -> MemoryImporter {
+        MemoryImporter {
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, url: &str, contents: &str) {
+        self.files.insert(normalize(Path::new(url)), String::from(contents));
+    }
+}
+
+impl Importer for MemoryImporter {
+    fn canonicalize(&self, url: &str, base: Option<&str>) -> Result<Option<String>> {
+        let path = match base {
+            Some(b) => Path::new(b).with_file_name(url),
+            None => PathBuf::from(url),
+        };
+        let found = resolve(vec![path], &|p: &Path|
+            self.files.contains_key(&normalize(p))
+        )?;
+        Ok(found.map(|p| normalize(&p)))
+    }
+
+    fn load(&self, canonical_url: &str) -> Result<ImporterResult> {
+        match self.files.get(canonical_url) {
+            Some(contents) => Ok(ImporterResult {
+                contents: contents.clone(),
+                syntax: Syntax::for_path(Path::new(canonical_url)),
+            }),
+            None => Err(SassError {
+                offset: 0,
+                kind: ErrorKind::ImportError,
+                message: format!("`{}` isn't in memory.", canonical_url),
+                location: None,
+                calls: vec![],
+            }),
+        }
+    }
+}
+
+// Resolves `a/./b/../c` to `a/c` without touching the file system.
+fn normalize(path: &Path) -> String {
+    let mut parts: Vec<String> = vec![];
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir if !parts.is_empty() => { parts.pop(); },
+            other => parts.push(other.as_os_str().to_string_lossy().into_owned()),
+        }
+    }
+    parts.join("/")
+}
+
+fn resolve(bases: Vec<PathBuf>, exists: &dyn Fn(&Path) -> bool) -> Result<Option<PathBuf>> {
+    for base in bases.into_iter() {
+        if let Some(found) = resolve_base(&base, exists)? {
+            return Ok(Some(found))
+        }
+    }
+    Ok(None)
+}
+
+fn resolve_base(base: &Path, exists: &dyn Fn(&Path) -> bool) -> Result<Option<PathBuf>> {
+    if let Some(found) = resolve_with_extensions(base, exists)? {
+        return Ok(Some(found))
+    }
+    resolve_with_extensions(&base.join("index"), exists)
+}
+
+fn resolve_with_extensions(base: &Path, exists: &dyn Fn(&Path) -> bool) -> Result<Option<PathBuf>> {
+    let explicit = base.extension().is_some_and(|ext|
+        ext == "scss" || ext == "sass" || ext == "css"
+    );
+    if explicit {
+        return exactly_one(existing_with_partial(base.to_path_buf(), exists))
+    }
+
+    let mut found = existing_with_partial(with_extension(base, "sass"), exists);
+    found.extend(existing_with_partial(with_extension(base, "scss"), exists));
+    if found.is_empty() {
+        found = existing_with_partial(with_extension(base, "css"), exists);
+    }
+    exactly_one(found)
+}
+
+// `foo.ext` also matches the partial `_foo.ext`.
+fn existing_with_partial(path: PathBuf, exists: &dyn Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let partial = path.file_name().map(|name|
+        path.with_file_name(format!("_{}", name.to_string_lossy()))
+    );
+    let mut candidates = vec![path];
+    candidates.extend(partial);
+    candidates.into_iter().filter(|p| exists(p)).collect()
+}
+
+fn with_extension(base: &Path, extension: &str) -> PathBuf {
+    let name = base.file_name().map(|n| n.to_string_lossy().into_owned())
+                               .unwrap_or_default();
+    base.with_file_name(format!("{}.{}", name, extension))
+}
+
+fn exactly_one(mut found: Vec<PathBuf>) -> Result<Option<PathBuf>> {
+    match found.len() {
+        0 => Ok(None),
+        1 => Ok(found.pop()),
+        _ => Err(SassError {
+            offset: 0,
+            kind: ErrorKind::ImportError,
+            message: format!(
+                "It's not clear which file to import. Found:\n{}",
+                found.iter()
+                     .map(|p| format!("  {}", p.display()))
+                     .collect::<Vec<_>>()
+                     .join("\n"),
+            ),
+            location: None,
+            calls: vec![],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::ErrorKind;
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+    use std::process;
+
+    fn touch_files(dir: &str, names: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("{}-{}", dir, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for name in names {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(&path).unwrap();
+        }
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn canonical(path: PathBuf) -> Result<Option<String>> {
+        Ok(Some(path.to_string_lossy().into_owned()))
+    }
+
+    #[test]
+    fn it_resolves_partials_and_extensions() {
+        let dir = touch_files("sassers-import-partials", &[
+            "_colors.scss", "reset.css", "legacy.sass",
+        ]);
+        let importer = FilesystemImporter::new(vec![]);
+        let base = dir.join("main.scss");
+        let base = base.to_str();
+        assert_eq!(importer.canonicalize("colors", base), canonical(dir.join("_colors.scss")));
+        assert_eq!(importer.canonicalize("colors.scss", base), canonical(dir.join("_colors.scss")));
+        assert_eq!(importer.canonicalize("reset", base), canonical(dir.join("reset.css")));
+        assert_eq!(importer.canonicalize("legacy", base), canonical(dir.join("legacy.sass")));
+        assert_eq!(importer.canonicalize("missing", base), Ok(None));
+    }
+
+    #[test]
+    fn it_resolves_index_files() {
+        let dir = touch_files("sassers-import-index", &["buttons/_index.scss"]);
+        let importer = FilesystemImporter::new(vec![]);
+        assert_eq!(
+            importer.canonicalize("buttons", dir.join("main.scss").to_str()),
+            canonical(dir.join("buttons/_index.scss"))
+        );
+    }
+
+    #[test]
+    fn it_resolves_relative_to_the_importing_file_before_load_paths() {
+        let dir = touch_files("sassers-import-load-paths", &[
+            "src/_grid.scss", "vendor/_grid.scss", "vendor/_reset.scss",
+        ]);
+        let importer = FilesystemImporter::new(vec![dir.join("vendor")]);
+        let base = dir.join("src/main.scss");
+        assert_eq!(importer.canonicalize("grid", base.to_str()), canonical(dir.join("src/_grid.scss")));
+        assert_eq!(importer.canonicalize("reset", base.to_str()), canonical(dir.join("vendor/_reset.scss")));
+        assert_eq!(importer.canonicalize("reset", None), canonical(dir.join("vendor/_reset.scss")));
+    }
+
+    #[test]
+    fn it_errors_on_ambiguous_imports() {
+        let dir = touch_files("sassers-import-ambiguous", &["_grid.scss", "grid.scss"]);
+        let importer = FilesystemImporter::new(vec![]);
+        assert_eq!(
+            importer.canonicalize("grid", dir.join("main.scss").to_str()).map_err(|e| e.kind),
+            Err(ErrorKind::ImportError)
+        );
+    }
+
+    #[test]
+    fn it_loads_from_memory() {
+        let mut importer = MemoryImporter::new();
+        importer.add("src/_colors.scss", "$c: red;");
+        importer.add("legacy.sass", "a\n  b: c");
+
+        assert_eq!(
+            importer.canonicalize("colors", Some("src/main.scss")),
+            Ok(Some(String::from("src/_colors.scss")))
+        );
+        assert_eq!(
+            importer.canonicalize("../legacy", Some("src/main.scss")),
+            Ok(Some(String::from("legacy.sass")))
+        );
+        assert_eq!(importer.canonicalize("colors", None), Ok(None));
+        assert_eq!(
+            importer.load("src/_colors.scss"),
+            Ok(ImporterResult { contents: String::from("$c: red;"), syntax: Syntax::Scss })
+        );
+        assert_eq!(importer.load("legacy.sass").map(|r| r.syntax), Ok(Syntax::Sass));
+    }
+}
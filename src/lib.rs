@@ -1,18 +1,22 @@
 #[macro_use]
 extern crate log;
 
-extern crate regex;
-use std::path::PathBuf;
-use std::fs::File;
-use std::io::Read;
-use regex::Regex;
 use std::io::Write;
 
 mod ast;
+mod compiler;
 mod context;
+mod convert;
 mod error;
 mod expression_evaluator;
+mod formatter;
+mod importer;
+mod logger;
+mod module;
+mod options;
 mod sass;
+mod source;
+mod source_map;
 mod operator;
 mod operator_offset;
 mod operator_or_token;
@@ -22,79 +26,59 @@ mod token;
 mod token_offset;
 mod tokenizer;
 
-use context::Context;
-use error::Result;
+pub use error::{Result, SassError, ErrorKind, Location, Frame};
+pub use importer::{Importer, ImporterResult, Syntax, FilesystemImporter, MemoryImporter};
+pub use logger::{Logger, StderrLogger};
+pub use compiler::Compiler;
+pub use convert::convert;
+pub use formatter::format_scss;
+pub use options::{CompileOptions, OutputStyle, IndentType, LineFeed};
+pub use sass::function::SassFunction;
+
 use tokenizer::Tokenizer;
 use parser::Parser;
-use sass::output_style::{SassOutputStyle, Nested, Compressed, Expanded,
-                         Compact, Debug};
-
-fn resolve_imports(inputpath: &PathBuf) -> Result<String> {
-    let mut file = try!(File::open(&inputpath));
-    let mut sass = String::new();
-
-    try!(file.read_to_string(&mut sass));
 
-    let mut imports_resolved = String::new();
-    for line in sass.split("\n") {
-        let re = Regex::new("@import \"([^\"]*)\";").unwrap();
+use std::fs::File;
+use std::io::Read;
 
-        match re.captures(line) {
-            Some(caps) => {
-                let imported = try!(resolve_imports(&inputpath.with_file_name(caps.at(1).unwrap())));
-                imports_resolved.push_str(&imported);
-            },
-            None => {
-                imports_resolved.push_str(line);
-            },
-        }
-        imports_resolved.push_str("\n");
+// Compiles with the default options in the output style named `style`.
+// The `tokens` and `ast` styles write out what the tokenizer and parser
+// make of the file instead.
+pub fn compile(input_filename: &str, output: &mut dyn Write, style: &str) -> Result<()> {
+    match style {
+        "tokens" | "ast" => {
+            let mut sass = String::new();
+            File::open(input_filename).and_then(|mut f| f.read_to_string(&mut sass))?;
+            dump(&sass, output, style)
+        },
+        other => {
+            let options = CompileOptions::new().style(OutputStyle::from_name(other)?);
+            Compiler::new(options).compile_file(input_filename, output)
+        },
     }
-    Ok(imports_resolved)
 }
 
-pub fn compile(input_filename: &str, output: &mut Write, style: &str) -> Result<()> {
-    let input_path = PathBuf::from(input_filename);
-    let imports_resolved = try!(resolve_imports(&input_path));
-
+pub fn compile_string(input: &str, output: &mut dyn Write, style: &str) -> Result<()> {
     match style {
-        "tokens" => {
-            let mut tokenizer = Tokenizer::new(&imports_resolved);
-            while let Some(token) = tokenizer.next() {
-                try!(writeln!(output, "{:?}", token));
-            }
-        },
-        "ast" => {
-            let mut parser = Parser::new(&imports_resolved);
-            while let Some(root) = parser.next() {
-                try!(writeln!(output, "{:#?}", root));
-            }
-        },
+        "tokens" | "ast" => dump(input, output, style),
         other => {
-            let style: Box<SassOutputStyle> = get_style(other);
-            let mut parser  = Parser::new(&imports_resolved);
-            let mut context = Context::new();
-            while let Some(Ok(ast_root)) = parser.next() {
-                let evaluated = ast_root.evaluate(&mut context);
-                if let Some(root) = evaluated {
-                    let optimized = optimizer::optimize(root);
-                    for r in optimized.into_iter() {
-                        try!(r.stream(output, &*style));
-                    }
-                }
-            }
+            let options = CompileOptions::new().style(OutputStyle::from_name(other)?);
+            Compiler::new(options).compile_string(input, None, output)
         },
     }
-    Ok(())
 }
 
-fn get_style(style: &str) -> Box<SassOutputStyle> {
-    match style {
-        "nested"     => Box::new(Nested {}),
-        "compressed" => Box::new(Compressed {}),
-        "expanded"   => Box::new(Expanded {}),
-        "compact"    => Box::new(Compact {}),
-        "debug"      => Box::new(Debug {}),
-        style        => panic!("Unknown output style {:?}. Please specify one of nested, compressed, expanded, or compact.", style),
+fn dump(sass: &str, output: &mut dyn Write, style: &str) -> Result<()> {
+    if style == "tokens" {
+        let tokenizer = Tokenizer::new(sass);
+        for token in tokenizer {
+            writeln!(output, "{:?}", token)?;
+        }
+    } else {
+        let parser = Parser::new(sass);
+        for root in parser {
+            writeln!(output, "{:#?}", root)?;
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,40 @@
+use error::Location;
+
+use std::io::{self, Write};
+
+// Receives the messages from `@debug` and `@warn` rules. `location` is
+// where the rule is, with the chain of rules that loaded its stylesheet.
+pub trait Logger {
+    fn debug(&self, message: &str, location: Option<&Location>);
+
+    fn warn(&self, message: &str, location: Option<&Location>);
+}
+
+// Writes messages to stderr the way the `sass` command line tool does.
+pub struct StderrLogger;
+
+impl Logger for StderrLogger {
+    fn debug(&self, message: &str, location: Option<&Location>) {
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        let _ = match location {
+            Some(l) => writeln!(stderr, "{}:{} DEBUG: {}", l.url, l.line, message),
+            None => writeln!(stderr, "DEBUG: {}", message),
+        };
+    }
+
+    fn warn(&self, message: &str, location: Option<&Location>) {
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        let _ = writeln!(stderr, "WARNING: {}", message);
+        if let Some(l) = location {
+            for frame in l.stack.iter() {
+                let _ = writeln!(
+                    stderr, "    {} {}:{}  {}",
+                    frame.url, frame.line, frame.column, frame.name
+                );
+            }
+        }
+        let _ = writeln!(stderr);
+    }
+}
@@ -1,6 +1,6 @@
 extern crate sassers;
 extern crate docopt;
-#[macro_use]
+#[cfg_attr(not(test), macro_use)]
 extern crate log;
 extern crate env_logger;
 
@@ -8,16 +8,34 @@ extern crate env_logger;
 fn main() {
     use docopt::Docopt;
     env_logger::init().unwrap();
-    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
-    static USAGE: &'static str = "
+    const VERSION: &str = env!("CARGO_PKG_VERSION");
+    static USAGE: &str = "
 Usage:
-    sassers [-t <style>] <inputfile>
+    sassers [-t <style>] [-I <path>]... [--precision <digits>] [--source-map <path>] [--embed-source-map] [--embed-sources] <inputfile>
+    sassers --check [-I <path>]... <inputfile>
+    sassers --stdin [--indented] [-t <style>] [-I <path>]... [--precision <digits>]
+    sassers fmt [--check] <files>...
+    sassers convert [--from <syntax>] --to <syntax> <inputfile>
     sassers [-vh]
 
 Options:
-    -h, --help                   Show this message
-    -v, --version                Show the version
-    -t <style>, --style <style>  Output style [default: nested]
+    -h, --help                      Show this message
+    -v, --version                   Show the version
+    -t <style>, --style <style>     Output style [default: nested]
+    -I <path>, --load-path <path>   Look for imports in this directory too
+    --precision <digits>            Digits after the decimal point in numbers,
+                                    10 if not given
+    --check                         Report every error instead of compiling,
+                                    or with fmt, list files that need
+                                    formatting instead of rewriting them
+    --source-map <path>             Write a source map to this file
+    --embed-source-map              Put the source map in the CSS instead
+    --embed-sources                 Include the Sass sources in the source map
+    --stdin                         Read the stylesheet from stdin
+    --indented                      Parse stdin as the indented syntax
+    --from <syntax>                 Convert from scss, sass or css, instead of
+                                    going by the file extension
+    --to <syntax>                   Convert to scss or sass
     ";
 
     let args = Docopt::new(USAGE)
@@ -26,13 +44,188 @@ Options:
 
     if args.get_bool("-v") {
         println!("{}", VERSION);
+    } else if args.get_bool("fmt") {
+        if !format_files(&args.get_vec("<files>"), args.get_bool("--check")) {
+            std::process::exit(1);
+        }
+    } else if args.get_bool("convert") {
+        convert(&args);
     } else {
         let style = args.get_str("-t");
         let input_filename = args.get_str("<inputfile>");
+        let load_paths = args.get_vec("-I");
         debug!("input filename = {:?}", input_filename);
 
-        sassers::compile(input_filename, &mut std::io::stdout(), style).unwrap_or_else(|e| {
-            println!("Compilation failed: {}", e.message);
-        });
+        if args.get_bool("--check") {
+            let options = compile_options(&args, &load_paths).unwrap_or_else(|e| fail(&e));
+            let errors = sassers::Compiler::new(options).check_file(input_filename);
+            for e in errors.iter() {
+                eprintln!("{}\n", e);
+            }
+            if !errors.is_empty() {
+                std::process::exit(1);
+            }
+        } else if args.get_bool("--stdin") {
+            compile_stdin(&args, &load_paths);
+        } else if style == "tokens" || style == "ast" {
+            sassers::compile(input_filename, &mut std::io::stdout(), style).unwrap_or_else(|e| fail(&e.to_string()));
+        } else {
+            let options = compile_options(&args, &load_paths).unwrap_or_else(|e| fail(&e));
+            compile_file(&args, options, input_filename);
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+// The same options for every compilation, whether or not any flags are
+// given.
+#[cfg(not(test))]
+fn compile_options(args: &docopt::ArgvMap, load_paths: &[&str]) -> Result<sassers::CompileOptions, String> {
+    use sassers::{CompileOptions, OutputStyle};
+
+    let style = OutputStyle::from_name(args.get_str("-t")).map_err(|e| e.to_string())?;
+    let map_path = args.get_str("--source-map");
+    let mut options = CompileOptions::new().style(style)
+                                           .source_map(!map_path.is_empty() || args.get_bool("--embed-source-map"))
+                                           .source_map_embed(args.get_bool("--embed-source-map"))
+                                           .source_map_contents(args.get_bool("--embed-sources"));
+    if !map_path.is_empty() {
+        options = options.source_map_url(map_path);
+    }
+    match args.get_str("--precision") {
+        "" => {},
+        digits => match digits.parse() {
+            Ok(digits) => options = options.precision(digits),
+            Err(_) => return Err(format!("--precision takes a number of digits, not {:?}.", digits)),
+        },
+    }
+    for path in load_paths.iter() {
+        options = options.load_path(*path);
+    }
+    Ok(options)
+}
+
+#[cfg(not(test))]
+fn compile_file(args: &docopt::ArgvMap, options: sassers::CompileOptions, input_filename: &str) {
+    use sassers::Compiler;
+    use std::fs::File;
+    use std::io::Write;
+
+    let map_path = args.get_str("--source-map");
+    let compiler = Compiler::new(options);
+    match compiler.compile_file_with_source_map(input_filename, &mut std::io::stdout()) {
+        Ok(Some(map)) => {
+            if !map_path.is_empty() && !args.get_bool("--embed-source-map") {
+                if let Err(e) = File::create(map_path).and_then(|mut f| f.write_all(map.as_bytes())) {
+                    fail(&format!("Couldn't write the source map to {}: {}", map_path, e));
+                }
+            }
+        },
+        Ok(None) => {},
+        Err(e) => fail(&e.to_string()),
+    }
+}
+
+#[cfg(not(test))]
+fn compile_stdin(args: &docopt::ArgvMap, load_paths: &[&str]) {
+    use sassers::{Compiler, Syntax};
+    use std::io::Read;
+
+    let mut input = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+        fail(&format!("Couldn't read stdin: {}", e));
+    }
+    let style = args.get_str("-t");
+    if style == "tokens" || style == "ast" {
+        return sassers::compile_string(&input, &mut std::io::stdout(), style).unwrap_or_else(|e| {
+            fail(&e.to_string())
+        })
+    }
+    let options = compile_options(args, load_paths).unwrap_or_else(|e| fail(&e));
+    let syntax = if args.get_bool("--indented") { Syntax::Sass } else { Syntax::Scss };
+    Compiler::new(options.syntax(syntax)).compile_string(&input, None, &mut std::io::stdout()).unwrap_or_else(|e| {
+        fail(&e.to_string())
+    });
+}
+
+// Returns false if any file couldn't be formatted, or with `check`, if any
+// file isn't formatted already.
+#[cfg(not(test))]
+fn format_files(files: &[&str], check: bool) -> bool {
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    let mut ok = true;
+    for path in files.iter() {
+        let mut scss = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut scss)) {
+            println!("Couldn't read {}: {}", path, e);
+            ok = false;
+            continue;
+        }
+        let formatted = match sassers::format_scss(&scss) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                println!("Couldn't format {}:\n{}", path, e.in_file(path, &scss));
+                ok = false;
+                continue;
+            },
+        };
+        if formatted == scss {
+            continue;
+        }
+        if check {
+            println!("{} needs formatting", path);
+            ok = false;
+        } else if let Err(e) = File::create(path).and_then(|mut f| f.write_all(formatted.as_bytes())) {
+            println!("Couldn't write {}: {}", path, e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+#[cfg(not(test))]
+fn convert(args: &docopt::ArgvMap) {
+    use sassers::Syntax;
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    fn syntax(name: &str) -> Syntax {
+        match name {
+            "scss" => Syntax::Scss,
+            "sass" => Syntax::Sass,
+            "css" => Syntax::Css,
+            _ => {
+                println!("Unknown syntax {}; use scss, sass or css", name);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    let input = args.get_str("<inputfile>");
+    let from = match args.get_str("--from") {
+        "" => Syntax::for_path(Path::new(input)),
+        name => syntax(name),
+    };
+    let to = syntax(args.get_str("--to"));
+
+    let mut text = String::new();
+    if let Err(e) = File::open(input).and_then(|mut f| f.read_to_string(&mut text)) {
+        println!("Couldn't read {}: {}", input, e);
+        std::process::exit(1);
+    }
+    match sassers::convert(&text, from, to) {
+        Ok(converted) => print!("{}", converted),
+        Err(e) => {
+            println!("{}", e.in_file(input, &text));
+            std::process::exit(1);
+        },
     }
 }
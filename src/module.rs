@@ -0,0 +1,1222 @@
+use ast::expression::Expression;
+use ast::node::Node;
+use ast::root::Root;
+use context::Context;
+use error::{Result, SassError, ErrorKind};
+use expression_evaluator::ExpressionEvaluator;
+use optimizer;
+use importer::{Importer, Syntax};
+use logger::Logger;
+use parser::Parser;
+use sass::forward_rule::SassForward;
+use sass::function::SassFunction;
+use sass::function_rule::SassFunctionRule;
+use sass::import_rule::SassImport;
+use sass::message_rule::MessageKind;
+use sass::mixin::SassMixin;
+use sass::use_rule::SassUse;
+use sass::output_style::{SassOutputStyle, Streamable, Nested};
+use sass::use_rule::unquote_url;
+use sass::variable::SassVariable;
+use source::SourceFiles;
+use source_map::{MappedOutput, Mapping, SourceMap};
+use token::Token;
+use token_offset::TokenOffset;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub url: String,
+    pub variables: HashMap<String, SassVariable>,
+    pub mixins: HashMap<String, SassMixin>,
+    pub function_rules: HashMap<String, SassFunctionRule>,
+}
+
+// Modules that come with Sass rather than being loaded by an importer. Their
+// members, like `math.div`, are implemented in the evaluator.
+const BUILT_IN_MODULES: &[&str] = &["sass:math"];
+
+// Members starting with `-` or `_` aren't visible outside their module.
+fn is_private(name: &str) -> bool {
+    name.starts_with('-') || name.starts_with('_')
+}
+
+impl Module {
+    pub fn new(url: &str) -> Module {
+        Module {
+            url: String::from(url),
+            variables: HashMap::new(),
+            mixins: HashMap::new(),
+            function_rules: HashMap::new(),
+        }
+    }
+
+    pub fn add_member(&mut self, variable: SassVariable) {
+        self.variables.insert(variable.name_string(), variable);
+    }
+
+    pub fn get_variable(&self, name: &str) -> Option<Expression> {
+        self.variables.get(name).map(|sv| sv.value.clone())
+    }
+}
+
+// A stylesheet as identified by the importer that found it. Stylesheets
+// compiled from a string have no importer, and only have a URL if they're
+// given a base to resolve relative loads against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stylesheet {
+    pub importer: Option<usize>,
+    pub url: Option<String>,
+    pub syntax: Syntax,
+}
+
+impl Stylesheet {
+    pub fn from_string(base_url: Option<&str>) -> Stylesheet {
+        Stylesheet {
+            importer: None,
+            url: base_url.map(String::from),
+            syntax: Syntax::Scss,
+        }
+    }
+
+    fn loaded(importer: usize, url: String, syntax: Syntax) -> Stylesheet {
+        Stylesheet {
+            importer: Some(importer),
+            url: Some(url),
+            syntax,
+        }
+    }
+
+    fn parser<'t>(&self, text: &'t str, start: usize) -> Parser<'t> {
+        match self.syntax {
+            Syntax::Sass => Parser::indented(text, start),
+            Syntax::Scss | Syntax::Css => Parser::starting_at(text, start),
+        }
+    }
+
+    fn url_string(&self) -> String {
+        self.url.clone().unwrap_or_else(|| String::from("-"))
+    }
+}
+
+// Loads each module once per compilation, no matter how many times it is
+// `@use`d or `@forward`ed, and streams its CSS the first time it is loaded.
+pub struct ModuleLoader<'a> {
+    importers: &'a [Box<dyn Importer>],
+    functions: HashMap<String, SassFunction>,
+    logger: &'a dyn Logger,
+    sources: SourceFiles,
+    // Syntax errors the parser recovered from. Once there are any, the rest
+    // of the stylesheet is only parsed, so every one of them is found
+    // without evaluating a broken stylesheet.
+    diagnostics: Vec<SassError>,
+    // Where each selector and property in the output came from, see
+    // `MappedOutput`.
+    mappings: Vec<(usize, usize, usize)>,
+    modules: HashMap<String, Module>,
+    // Plain CSS `@import`s, which go at the top of the output wherever
+    // they're written.
+    css_imports: Vec<SassImport>,
+    // Canonical URLs currently being loaded, outermost first, to catch
+    // import cycles.
+    loading: Vec<String>,
+    // Digits after the decimal point in numbers in the output.
+    pub precision: usize,
+}
+
+// A module being evaluated, along with everything it `@import`s.
+struct Evaluation {
+    context: Context,
+    module: Module,
+    unused_config: Vec<String>,
+    seen_other_rules: bool,
+}
+
+impl Evaluation {
+    fn new(url: &str, config: Vec<SassVariable>,
+           functions: &HashMap<String, SassFunction>) -> Evaluation {
+        let mut context = Context::new();
+        context.functions = functions.clone();
+        let unused_config = config.iter()
+                                  .map(|c| c.name_string())
+                                  .collect::<Vec<_>>();
+        for variable in config.into_iter() {
+            context.add_variable(variable);
+        }
+
+        Evaluation {
+            context,
+            module: Module::new(url),
+            unused_config,
+            seen_other_rules: false,
+        }
+    }
+
+    fn into_module(self) -> Result<Module> {
+        if let Some(name) = self.unused_config.into_iter().next() {
+            return Err(ModuleLoader::not_configurable_error(&name, None))
+        }
+
+        // Mixins and functions can still see the private members of the
+        // module they're defined in.
+        let scope = Rc::new(Module {
+            url: self.module.url.clone(),
+            variables: self.context.variables.clone(),
+            mixins: self.context.mixins.clone(),
+            function_rules: self.context.function_rules.clone(),
+        });
+
+        let mut module = self.module;
+        for (_, variable) in self.context.variables.into_iter() {
+            if !variable.is_private() {
+                module.add_member(variable);
+            }
+        }
+        for (name, mut mixin) in self.context.mixins.into_iter() {
+            if !is_private(&name) {
+                mixin.scope = Some(scope.clone());
+                module.mixins.insert(name, mixin);
+            }
+        }
+        for (name, mut function) in self.context.function_rules.into_iter() {
+            if !is_private(&name) {
+                function.scope = Some(scope.clone());
+                module.function_rules.insert(name, function);
+            }
+        }
+        Ok(module)
+    }
+}
+
+impl<'a> ModuleLoader<'a> {
+    pub fn new(importers: &'a [Box<dyn Importer>],
+               functions: HashMap<String, SassFunction>,
+               logger: &'a dyn Logger) -> ModuleLoader<'a> {
+        ModuleLoader {
+            importers,
+            functions,
+            logger,
+            sources: SourceFiles::new(),
+            diagnostics: vec![],
+            mappings: vec![],
+            modules: BUILT_IN_MODULES.iter()
+                                     .map(|url| (url.to_string(), Module::new(url)))
+                                     .collect(),
+            css_imports: vec![],
+            loading: Vec::new(),
+            precision: 10,
+        }
+    }
+
+    // Finds the stylesheet being compiled, trying each importer in order.
+    pub fn load_entry(&self, url: &str) -> Result<(Stylesheet, String)> {
+        for (i, importer) in self.importers.iter().enumerate() {
+            if let Some(canonical) = importer.canonicalize(url, None)? {
+                let (text, syntax) = self.read(i, &canonical, 0)?;
+                return Ok((Stylesheet::loaded(i, canonical, syntax), text))
+            }
+        }
+        Err(SassError {
+            offset: 0,
+            kind: ErrorKind::ImportError,
+            message: format!("Can't find stylesheet to compile: `{}`.", url),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    // `stylesheet` is where `text` came from; `@use`, `@forward` and
+    // `@import` URLs are resolved relative to it.
+    pub fn stream_text(&mut self, stylesheet: &Stylesheet, text: &str,
+                       config: Vec<SassVariable>,
+                       output: &mut dyn Write, style: &dyn SassOutputStyle)
+                       -> Result<Module> {
+        let start = self.sources.add(&stylesheet.url_string(), text, None);
+        let mut rules = Vec::new();
+        let (module, rule_mappings) = {
+            let mut rules_output = MappedOutput::new(&mut rules);
+            rules_output.precision = self.precision;
+            let module = self.stream_module(stylesheet, text, start, config, &mut rules_output, style)?;
+            (module, rules_output.mappings)
+        };
+
+        let mut output = MappedOutput::new(output);
+        output.precision = self.precision;
+        for sass_import in mem::take(&mut self.css_imports).into_iter() {
+            sass_import.stream(&mut output, style)?;
+        }
+        output.write_mapped(&rules, rule_mappings)?;
+        self.mappings = output.mappings;
+        match self.diagnostics.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(module),
+        }
+    }
+
+    // Every error in `text` and the stylesheets it loads, rather than just
+    // the first one, without writing any CSS.
+    pub fn check_text(&mut self, stylesheet: &Stylesheet, text: &str) -> Vec<SassError> {
+        let result = self.stream_text(stylesheet, text, vec![], &mut io::sink(), &Nested {});
+        let mut errors = self.diagnostics.clone();
+        if let Err(e) = result {
+            if !errors.contains(&e) {
+                errors.push(e);
+            }
+        }
+        errors.into_iter().map(|e| self.locate(e)).collect()
+    }
+
+    // Fills in where an error happened, if it came from a stylesheet this
+    // loader has seen.
+    pub fn locate(&self, error: SassError) -> SassError {
+        let location = error.location.clone().or_else(|| self.sources.locate(error.offset, &error.calls).map(Box::new));
+        SassError {
+            location,
+            ..error
+        }
+    }
+
+    // Maps the output of the last `stream_text` back to the stylesheets it
+    // came from.
+    pub fn source_map(&self, include_sources: bool) -> SourceMap {
+        let mut sources: Vec<String> = vec![];
+        let mut mappings = vec![];
+        for &(generated_line, generated_column, offset) in self.mappings.iter() {
+            if let Some((url, line, column)) = self.sources.position(offset) {
+                let source = match sources.iter().position(|s| s == url) {
+                    Some(i) => i,
+                    None => {
+                        sources.push(String::from(url));
+                        sources.len() - 1
+                    },
+                };
+                mappings.push(Mapping {
+                    generated_line,
+                    generated_column,
+                    source,
+                    line,
+                    column,
+                });
+            }
+        }
+
+        let sources_content = if include_sources {
+            Some(sources.iter().map(|url|
+                String::from(self.sources.contents(url).unwrap_or(""))
+            ).collect())
+        } else {
+            None
+        };
+        SourceMap {
+            sources,
+            sources_content,
+            mappings,
+        }
+    }
+
+    // `start` is the offset `text` was added to `self.sources` at.
+    fn stream_module(&mut self, stylesheet: &Stylesheet, text: &str, start: usize,
+                     config: Vec<SassVariable>,
+                     output: &mut MappedOutput, style: &dyn SassOutputStyle)
+                     -> Result<Module> {
+        let mut evaluation = Evaluation::new(&stylesheet.url_string(), config, &self.functions);
+        self.stream_roots(stylesheet, text, start, &mut evaluation, output, style)?;
+        evaluation.into_module()
+    }
+
+    fn stream_roots(&mut self, stylesheet: &Stylesheet, text: &str, start: usize,
+                    evaluation: &mut Evaluation,
+                    output: &mut MappedOutput, style: &dyn SassOutputStyle)
+                    -> Result<()> {
+        self.loading.push(stylesheet.url_string());
+        let result = self.stream_parsed_roots(stylesheet, text, start, evaluation, output, style);
+        self.loading.pop();
+        result
+    }
+
+    fn stream_parsed_roots(&mut self, stylesheet: &Stylesheet, text: &str, start: usize,
+                           evaluation: &mut Evaluation,
+                           output: &mut MappedOutput, style: &dyn SassOutputStyle)
+                           -> Result<()> {
+        let parser = stylesheet.parser(text, start);
+
+        for parsed in parser {
+            let ast_root = match parsed {
+                Ok(_) if !self.diagnostics.is_empty() => continue,
+                Ok(ast_root) => ast_root,
+                Err(e) => {
+                    self.diagnostics.push(e);
+                    continue
+                },
+            };
+            match ast_root {
+                Root::Use(sass_use) => {
+                    if evaluation.seen_other_rules {
+                        return Err(ModuleLoader::misplaced_rule_error(sass_use.url.offset, "@use"))
+                    }
+                    let config = ModuleLoader::evaluate_config(&sass_use.config, &evaluation.context)?;
+                    let used = self.load(stylesheet, "@use", &sass_use.url, config, output, style)?;
+                    evaluation.context.add_module(sass_use.namespace_string(), used);
+                },
+                Root::Forward(sass_forward) => {
+                    if evaluation.seen_other_rules {
+                        return Err(ModuleLoader::misplaced_rule_error(sass_forward.url.offset, "@forward"))
+                    }
+                    let config = ModuleLoader::evaluate_config(&sass_forward.config, &evaluation.context)?;
+                    let forwarded = self.load(stylesheet, "@forward", &sass_forward.url, config, output, style)?;
+                    ModuleLoader::forward_members(
+                        &sass_forward, forwarded, &mut evaluation.module
+                    );
+                },
+                Root::Import(imports) => {
+                    evaluation.seen_other_rules = true;
+                    for sass_import in imports.into_iter() {
+                        if sass_import.is_plain_css() {
+                            self.css_imports.push(sass_import);
+                        } else {
+                            let (imported, text, start) = self.load_import(stylesheet, &sass_import.url)?;
+                            self.stream_roots(
+                                &imported, &text, start, evaluation, output, style
+                            )?;
+                        }
+                    }
+                },
+                other => {
+                    let other = match other {
+                        Root::Variable(sv) => {
+                            let name = sv.name_string();
+                            if evaluation.unused_config.contains(&name) {
+                                if !sv.is_default() {
+                                    return Err(ModuleLoader::not_configurable_error(
+                                        &name, sv.name.offset
+                                    ))
+                                }
+                                evaluation.unused_config.retain(|n| *n != name);
+                            }
+                            Root::Variable(sv)
+                        },
+                        Root::Rule(mut rule) => {
+                            evaluation.seen_other_rules = true;
+                            rule.children = self.expand_nested_imports(stylesheet, rule.children)?;
+                            Root::Rule(rule)
+                        },
+                        other => other,
+                    };
+
+                    let evaluated = other.evaluate(&mut evaluation.context);
+                    self.log(&evaluation.context);
+                    for root in evaluated?.into_iter() {
+                        for r in optimizer::optimize(root).into_iter() {
+                            r.stream(output, style)?;
+                        }
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn log(&self, context: &Context) {
+        for (kind, message, offset) in context.take_logged().into_iter() {
+            let location = self.sources.locate(offset, &[]);
+            match kind {
+                MessageKind::Debug => self.logger.debug(&message, location.as_ref()),
+                _ => self.logger.warn(&message, location.as_ref()),
+            }
+        }
+    }
+
+    // An `@import` inside a rule brings the imported file's rules and
+    // variables into that rule, so they end up nested under its selectors.
+    fn expand_nested_imports(&mut self, stylesheet: &Stylesheet, children: Vec<Node>)
+                             -> Result<Vec<Node>> {
+        let mut expanded = vec![];
+        for child in children.into_iter() {
+            match child {
+                Node::Import(imports) => {
+                    for sass_import in imports.into_iter() {
+                        if sass_import.is_plain_css() {
+                            return Err(SassError {
+                                offset: sass_import.url.offset.unwrap_or(0),
+                                kind: ErrorKind::ImportError,
+                                message: String::from(
+                                    "Plain CSS imports can't be nested in rules."
+                                ),
+                                location: None,
+                                calls: vec![],
+                            })
+                        }
+                        let (imported, text, start) = self.load_import(stylesheet, &sass_import.url)?;
+
+                        self.loading.push(imported.url_string());
+                        let nodes = self.nested_nodes(&imported, &text, start);
+                        self.loading.pop();
+                        expanded.extend(nodes?);
+                    }
+                },
+                Node::Rule(mut rule) => {
+                    rule.children = self.expand_nested_imports(stylesheet, rule.children)?;
+                    expanded.push(Node::Rule(rule));
+                },
+                other => expanded.push(other),
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn nested_nodes(&mut self, stylesheet: &Stylesheet, text: &str, start: usize)
+                    -> Result<Vec<Node>> {
+        let parser = stylesheet.parser(text, start);
+        let mut nodes = vec![];
+
+        for parsed in parser {
+            let ast_root = match parsed {
+                Ok(ast_root) => ast_root,
+                Err(e) => {
+                    self.diagnostics.push(e);
+                    continue
+                },
+            };
+            nodes.push(match ast_root {
+                Root::Rule(rule) => Node::Rule(rule),
+                Root::Variable(variable) => Node::Variable(variable),
+                Root::Comment(comment) => Node::Comment(comment),
+                Root::Import(imports) => Node::Import(imports),
+                Root::Message(message) => Node::Message(message),
+                Root::Include(include) => Node::Include(include),
+                Root::AtRoot(at_root) => Node::AtRoot(at_root),
+                Root::Supports(supports) => Node::Supports(supports),
+                Root::Media(media) => Node::Media(media),
+                Root::Mixin(SassMixin { name, .. }) |
+                Root::Function(SassFunctionRule { name, .. }) => {
+                    return Err(SassError {
+                        offset: name.offset.unwrap_or(0),
+                        kind: ErrorKind::ModuleError,
+                        message: String::from(
+                            "Mixins and functions can't be defined by an @import nested in a rule."
+                        ),
+                        location: None,
+                        calls: vec![],
+                    })
+                },
+                Root::Use(SassUse { url, .. }) |
+                Root::Forward(SassForward { url, .. }) => {
+                    return Err(SassError {
+                        offset: url.offset.unwrap_or(0),
+                        kind: ErrorKind::ModuleError,
+                        message: String::from(
+                            "Module rules can't be loaded by an @import nested in a rule."
+                        ),
+                        location: None,
+                        calls: vec![],
+                    })
+                },
+            });
+        }
+        self.expand_nested_imports(stylesheet, nodes)
+    }
+
+    fn load_import(&mut self, from: &Stylesheet, url: &TokenOffset)
+                   -> Result<(Stylesheet, String, usize)> {
+        let (importer, canonical) = self.resolve(from, url)?;
+        self.check_for_cycle(&canonical, url)?;
+        let offset = url.offset.unwrap_or(0);
+        let (text, syntax) = self.read(importer, &canonical, offset)?;
+        let start = self.sources.add(&canonical, &text, Some((offset, "@import")));
+        Ok((Stylesheet::loaded(importer, canonical, syntax), text, start))
+    }
+
+    fn check_for_cycle(&self, canonical: &str, url: &TokenOffset) -> Result<()> {
+        if !self.loading.iter().any(|u| u == canonical) {
+            return Ok(())
+        }
+
+        let chain = self.loading.iter()
+                                .map(|u| u.as_str())
+                                .chain(Some(canonical))
+                                .map(|u| format!("  {}", u))
+                                .collect::<Vec<_>>();
+        Err(SassError {
+            offset: url.offset.unwrap_or(0),
+            kind: ErrorKind::ImportError,
+            message: format!(
+                "This file is already being loaded. Import chain:\n{}",
+                chain.join("\n"),
+            ),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    // URLs are tried relative to the importing stylesheet with the importer
+    // that loaded it (or every importer, for a string with a base URL), then
+    // with each importer in order.
+    fn resolve(&self, from: &Stylesheet, url: &TokenOffset) -> Result<(usize, String)> {
+        let offset = url.offset.unwrap_or(0);
+        let url = unquote_url(url);
+        let with_offset = |e: SassError| SassError { offset, ..e };
+
+        if let Some(ref base) = from.url {
+            let relative_importers = match from.importer {
+                Some(i) => vec![i],
+                None => (0..self.importers.len()).collect(),
+            };
+            for i in relative_importers.into_iter() {
+                let relative = self.importers[i].canonicalize(&url, Some(base)).map_err(&with_offset)?;
+                if let Some(canonical) = relative {
+                    return Ok((i, canonical))
+                }
+            }
+        }
+
+        for (i, importer) in self.importers.iter().enumerate() {
+            if let Some(canonical) = importer.canonicalize(&url, None).map_err(&with_offset)? {
+                return Ok((i, canonical))
+            }
+        }
+
+        Err(SassError {
+            offset,
+            kind: ErrorKind::ImportError,
+            message: format!("Can't find stylesheet to import: `{}`.", url),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    fn read(&self, importer: usize, canonical: &str, offset: usize) -> Result<(String, Syntax)> {
+        let loaded = self.importers[importer].load(canonical)
+                                .map_err(|e| SassError { offset, ..e })?;
+        Ok((loaded.contents, loaded.syntax))
+    }
+
+    // `config` has already been evaluated, see `evaluate_config`.
+    fn load(&mut self, from: &Stylesheet, rule: &'static str, url: &TokenOffset,
+            config: Vec<SassVariable>, output: &mut MappedOutput, style: &dyn SassOutputStyle)
+            -> Result<Module> {
+        let name = unquote_url(url);
+        if BUILT_IN_MODULES.contains(&&name[..]) {
+            if !config.is_empty() {
+                return Err(SassError {
+                    offset: url.offset.unwrap_or(0),
+                    kind: ErrorKind::ModuleError,
+                    message: format!("Built-in module `{}` can't be configured using `with`.", name),
+                    location: None,
+                    calls: vec![],
+                })
+            }
+            return Ok(self.modules[&name].clone())
+        }
+
+        let (importer, canonical) = self.resolve(from, url)?;
+        self.check_for_cycle(&canonical, url)?;
+
+        if let Some(module) = self.modules.get(&canonical) {
+            if !config.is_empty() {
+                return Err(SassError {
+                    offset: url.offset.unwrap_or(0),
+                    kind: ErrorKind::ModuleError,
+                    message: format!(
+                        "`{}` was already loaded, so it can't be configured using `with`.",
+                        unquote_url(url),
+                    ),
+                    location: None,
+                    calls: vec![],
+                })
+            }
+            return Ok(module.clone())
+        }
+
+        let offset = url.offset.unwrap_or(0);
+        let (text, syntax) = self.read(importer, &canonical, offset)?;
+        let start = self.sources.add(&canonical, &text, Some((offset, rule)));
+        let stylesheet = Stylesheet::loaded(importer, canonical.clone(), syntax);
+        let module = self.stream_module(
+            &stylesheet, &text, start, config, output, style
+        )?;
+        self.modules.insert(canonical, module.clone());
+        Ok(module)
+    }
+
+    // Configuration is evaluated where it is written, not in the module.
+    fn evaluate_config(config: &[SassVariable], context: &Context) -> Result<Vec<SassVariable>> {
+        let mut evaluated = vec![];
+        for c in config.iter() {
+            let c = c.clone().without_default_flag();
+            evaluated.push(SassVariable {
+                value: ExpressionEvaluator::evaluate(c.value, context)?,
+                name: c.name,
+            });
+        }
+        Ok(evaluated)
+    }
+
+    fn forward_members(sass_forward: &SassForward, forwarded: Module, module: &mut Module) {
+        for (name, variable) in forwarded.variables.into_iter() {
+            if sass_forward.forwards(&name) {
+                let mut variable = variable;
+                variable.name.token = Token::String(
+                    sass_forward.forwarded_name(&name)
+                );
+                module.add_member(variable);
+            }
+        }
+        for (name, mixin) in forwarded.mixins.into_iter() {
+            if sass_forward.forwards(&name) {
+                module.mixins.insert(sass_forward.forwarded_name(&name), mixin);
+            }
+        }
+        for (name, function) in forwarded.function_rules.into_iter() {
+            if sass_forward.forwards(&name) {
+                module.function_rules.insert(sass_forward.forwarded_name(&name), function);
+            }
+        }
+    }
+
+    fn misplaced_rule_error(offset: Option<usize>, rule: &str) -> SassError {
+        SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::ModuleError,
+            message: format!("{} rules must be written before any other rules.", rule),
+            location: None,
+            calls: vec![],
+        }
+    }
+
+    fn not_configurable_error(name: &str, offset: Option<usize>) -> SassError {
+        SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::ModuleError,
+            message: format!(
+                "`{}` was not declared with !default in the configured module.",
+                name,
+            ),
+            location: None,
+            calls: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use importer::{FilesystemImporter, MemoryImporter};
+    use error::Location;
+    use logger::StderrLogger;
+    use sass::output_style::Nested;
+
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+
+    fn compile_files(files: &[(&str, &str)]) -> Result<String> {
+        let mut importer = MemoryImporter::new();
+        for &(url, contents) in files {
+            importer.add(url, contents);
+        }
+        compile_with(vec![Box::new(importer)], "main.scss")
+    }
+
+    fn compile_with(importers: Vec<Box<dyn Importer>>, url: &str) -> Result<String> {
+        let mut output = Vec::new();
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &StderrLogger);
+        let (stylesheet, text) = loader.load_entry(url)?;
+        loader.stream_text(&stylesheet, &text, vec![], &mut output, &Nested {})?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn it_uses_namespaced_module_variables() {
+        let files = [
+            ("_tokens.scss", "$color: red;\n$-secret: blue;\n"),
+            ("main.scss", "@use \"tokens\" as t;\na { color: t.$color; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("a {\n  color: red; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_does_not_export_private_members() {
+        let files = [
+            ("_tokens.scss", "$-secret: blue;\n"),
+            ("main.scss", "@use \"tokens\";\na { color: tokens.$-secret; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Err(SassError {
+                offset: 26,
+                kind: ErrorKind::UndefinedVariable,
+                message: String::from("Undefined variable `tokens.$-secret`."),
+                location: None,
+                calls: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn it_configures_default_variables_with_with() {
+        let files = [
+            ("_tokens.scss", "$color: red !default;\nb { color: $color; }\n"),
+            ("main.scss", "$brand: green;\n@use \"tokens\" with ($color: $brand);\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("b {\n  color: green; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_errors_when_configuring_a_variable_without_default() {
+        let files = [
+            ("_tokens.scss", "$color: red;\n"),
+            ("main.scss", "@use \"tokens\" with ($color: green);\n"),
+        ];
+        assert_eq!(
+            compile_files(&files).map_err(|e| e.kind),
+            Err(ErrorKind::ModuleError)
+        );
+    }
+
+    #[test]
+    fn it_loads_built_in_modules_without_an_importer() {
+        let files = [("main.scss", "@use \"sass:math\" as m;\n@use \"sass:math\";\na { width: m.div(10px, 2); }\n")];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("a {\n  width: 5px; }\n\n"))
+        );
+
+        let files = [("main.scss", "@use \"sass:math\" with ($a: 1);\n")];
+        assert_eq!(
+            compile_files(&files).map_err(|e| e.kind),
+            Err(ErrorKind::ModuleError)
+        );
+    }
+
+    #[test]
+    fn it_only_loads_modules_at_the_top_level() {
+        let files = [
+            ("_x.scss", "b { c: d; }\n"),
+            ("main.scss", ".a { @use \"x\"; e: f; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files).map_err(|e| (e.kind, e.offset)),
+            Err((ErrorKind::ModuleError, 5))
+        );
+    }
+
+    #[test]
+    fn it_errors_on_calls_to_members_a_module_does_not_have() {
+        let files = [
+            ("_math.scss", "@function double($x) { @return $x * 2; }\n"),
+            ("_fwd.scss", "@forward \"math\" hide double;\n"),
+            ("main.scss", "@use \"fwd\";\na { b: fwd.double(1px); c: fwd.triple(1px); }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files).map_err(|e| e.message),
+            Err(String::from("Undefined function `fwd.double`."))
+        );
+    }
+
+    #[test]
+    fn it_evaluates_each_module_once() {
+        let files = [
+            ("_base.scss", "b { color: red; }\n"),
+            ("_list.scss", "@use \"base\";\n$gap: 1px;\n"),
+            ("main.scss", "@use \"base\";\n@use \"list\";\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("b {\n  color: red; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_forwards_members_with_prefix_and_visibility() {
+        let files = [
+            ("src/_list.scss", "$gap: 1px;\n$width: 2px;\n"),
+            ("_index.scss", "@forward \"src/list\" as list-* hide $width;\n"),
+            ("main.scss", "@use \"index\" as i;\na { margin: i.$list-gap; padding: i.$list-width; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files).map_err(|e| e.kind),
+            Err(ErrorKind::UndefinedVariable)
+        );
+
+        let files = [
+            ("src/_list.scss", "$gap: 1px;\n$width: 2px;\n"),
+            ("_index.scss", "@forward \"src/list\" as list-* hide $width;\n"),
+            ("main.scss", "@use \"index\" as i;\na { margin: i.$list-gap; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("a {\n  margin: 1px; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_includes_mixins_and_calls_functions_from_modules() {
+        let files = [
+            ("_theme.scss", "$-unit: 4px;\n\
+                             @function space($n: 1) { @return $n * $-unit; }\n\
+                             @mixin pad($x, $y: space()) { padding: $y $x; }\n"),
+            ("main.scss", "@use \"theme\";\n\
+                           a { @include theme.pad(theme.space(2)); margin: theme.space(3); }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("a {\n  padding: 4px 8px;\n  margin: 12px; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_evaluates_content_blocks_in_the_including_scope() {
+        let files = [
+            ("_layout.scss", "@mixin wide { .wide { @content(2px); } i { has: content-exists(); } }\n"),
+            ("main.scss", "@use \"layout\";\n\
+                           $color: red;\n\
+                           a { @include layout.wide using ($w) { color: $color; width: $w; } }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from(
+                "a .wide {\n  color: red;\n  width: 2px; }\n\na i {\n  has: true; }\n\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_imports_partials_into_the_same_scope() {
+        let files = [
+            ("_colors.scss", "$color: red;\n"),
+            ("_sizes.scss", "$size: 1px;\n"),
+            ("main.scss", "@import \"colors\", \"sizes\";\na { color: $color; width: $size; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from("a {\n  color: red;\n  width: 1px; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_resolves_imports_in_a_string_against_its_base_url() {
+        let mut importer = MemoryImporter::new();
+        importer.add("src/_colors.scss", "$color: red;\n");
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(importer)];
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &StderrLogger);
+        let text = "@import \"colors\";\na { color: $color; }\n";
+
+        let mut output = Vec::new();
+        let based = Stylesheet::from_string(Some("src/main.scss"));
+        assert!(loader.stream_text(&based, text, vec![], &mut output, &Nested {}).is_ok());
+        assert_eq!(String::from_utf8(output).unwrap(), "a {\n  color: red; }\n\n");
+
+        let unbased = Stylesheet::from_string(None);
+        assert_eq!(
+            loader.stream_text(&unbased, text, vec![], &mut Vec::new(), &Nested {})
+                  .map_err(|e| e.message),
+            Err(String::from("Can't find stylesheet to import: `colors`."))
+        );
+    }
+
+    #[test]
+    fn it_imports_from_load_paths() {
+        let dir = env::temp_dir().join(format!("sassers-import-load-path-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for &(name, contents) in &[
+            ("vendor/_grid.scss", "g { float: left; }\n"),
+            ("src/main.scss", "@import \"grid\";\n"),
+        ] {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        }
+        let importer = FilesystemImporter::new(vec![dir.join("vendor")]);
+        assert_eq!(
+            compile_with(vec![Box::new(importer)], dir.join("src/main.scss").to_str().unwrap()),
+            Ok(String::from("g {\n  float: left; }\n\n"))
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn it_leaves_plain_css_imports_in_the_output() {
+        let files = [
+            ("main.scss", "@import url(http://x.com/a.css), \"b.css\";\n@import \"print\" print;\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from(
+                "@import url(http://x.com/a.css);\n@import \"b.css\";\n@import \"print\" print;\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_puts_plain_css_imports_before_any_rules() {
+        let files = [
+            ("_partial.scss", "p { a: b; }\n@import \"reset.css\";\n"),
+            ("main.scss", "@import \"partial\";\ns { c: d; }\n@import \"foo.css\";\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from(
+                "@import \"reset.css\";\n@import \"foo.css\";\np {\n  a: b; }\n\ns {\n  c: d; }\n\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_errors_when_an_import_cannot_be_found() {
+        let files = [
+            ("main.scss", "@import \"missing\";\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Err(SassError {
+                offset: 8,
+                kind: ErrorKind::ImportError,
+                message: String::from("Can't find stylesheet to import: `missing`."),
+                location: None,
+                calls: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn it_nests_imported_rules_under_the_importing_rule() {
+        let files = [
+            ("_buttons.scss", "$pad: 2px;\n.btn { padding: $pad; }\n"),
+            ("main.scss", ".theme { @import \"buttons\"; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from(".theme .btn {\n  padding: 2px; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_loads_indented_syntax_files() {
+        let files = [
+            ("_theme.sass", "$pad: 2px\n\n.card\n  padding: $pad\n  .title\n    margin: 0\n"),
+            ("main.scss", "@import \"theme\";\na { padding: $pad; }\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Ok(String::from(
+                ".card {\n  padding: 2px; }\n  .card .title {\n    margin: 0; }\n\n\
+                 a {\n  padding: 2px; }\n\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_errors_on_declarations_of_empty_lists() {
+        let files = [("main.scss", "@mixin m($rest...) {\n  rest: $rest;\n}\na {\n  @include m;\n}\n")];
+        let error = compile_files(&files).unwrap_err();
+        assert_eq!((error.kind, error.offset), (ErrorKind::TypeError, 23));
+        assert_eq!(error.message, "() isn't a valid CSS value.");
+    }
+
+    #[test]
+    fn it_reads_indented_syntax_mixin_shorthands() {
+        let files = [
+            ("_mixins.sass", "=pad($size)\n  padding: $size\n"),
+            ("main.sass", "@import \"mixins\"\n.card\n  +pad(2px)\n"),
+        ];
+        let mut importer = MemoryImporter::new();
+        for &(url, contents) in files.iter() {
+            importer.add(url, contents);
+        }
+        assert_eq!(
+            compile_with(vec![Box::new(importer)], "main.sass"),
+            Ok(String::from(".card {\n  padding: 2px; }\n\n"))
+        );
+    }
+
+    #[test]
+    fn it_errors_on_import_cycles() {
+        let files = [
+            ("_a.scss", "@import \"b\";\n"),
+            ("_b.scss", "@import \"a\";\n"),
+            ("main.scss", "@import \"a\";\n"),
+        ];
+        assert_eq!(
+            compile_files(&files),
+            Err(SassError {
+                offset: 36,
+                kind: ErrorKind::ImportError,
+                message: String::from(
+                    "This file is already being loaded. Import chain:\n  \
+                     main.scss\n  _a.scss\n  _b.scss\n  _a.scss"
+                ),
+                location: None,
+                calls: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn it_reports_every_syntax_error() {
+        let mut importer = MemoryImporter::new();
+        importer.add("_b.scss", "b {\n  c: 1.2.3;\n}\n");
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(importer)];
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &StderrLogger);
+        let stylesheet = Stylesheet::from_string(None);
+        let errors = loader.check_text(&stylesheet, "@import \"b\";\na { : d; }\n$e f;\n");
+
+        assert_eq!(
+            errors.iter().map(|e| {
+                let location = e.location.as_ref().unwrap();
+                (location.url.as_str(), location.line, location.column)
+            }).collect::<Vec<_>>(),
+            vec![("_b.scss", 2, 6), ("-", 2, 5), ("-", 3, 4)]
+        );
+    }
+
+    struct RecordingLogger {
+        messages: RefCell<Vec<String>>,
+    }
+
+    impl Logger for RecordingLogger {
+        fn debug(&self, message: &str, location: Option<&Location>) {
+            let l = location.unwrap();
+            self.messages.borrow_mut().push(format!("{}:{} DEBUG: {}", l.url, l.line, message));
+        }
+
+        fn warn(&self, message: &str, location: Option<&Location>) {
+            let l = location.unwrap();
+            self.messages.borrow_mut().push(format!("{}:{} WARNING: {}", l.url, l.line, message));
+        }
+    }
+
+    #[test]
+    fn it_logs_debug_and_warn_messages() {
+        let mut importer = MemoryImporter::new();
+        importer.add("_old.scss", "$size: 2px;\n@warn \"old is deprecated\";\n");
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(importer)];
+        let logger = RecordingLogger { messages: RefCell::new(vec![]) };
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &logger);
+        let text = "@import \"old\";\n@debug $size;\na {\n  @warn \"nested\";\n  b: c;\n}\n";
+
+        let mut output = Vec::new();
+        let stylesheet = Stylesheet::from_string(Some("main.scss"));
+        assert!(loader.stream_text(&stylesheet, text, vec![], &mut output, &Nested {}).is_ok());
+        assert_eq!(String::from_utf8(output).unwrap(), "a {\n  b: c; }\n\n");
+        assert_eq!(
+            *logger.messages.borrow(),
+            vec![
+                String::from("_old.scss:2 WARNING: old is deprecated"),
+                String::from("main.scss:2 DEBUG: 2px"),
+                String::from("main.scss:4 WARNING: nested"),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_stops_at_error_rules() {
+        let files = [
+            ("_check.scss", "a {\n  @error \"Expected a size, got auto.\";\n}\n"),
+            ("main.scss", "@import \"check\";\nb { c: d; }\n"),
+        ];
+        let mut importer = MemoryImporter::new();
+        for &(url, contents) in files.iter() {
+            importer.add(url, contents);
+        }
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(importer)];
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &StderrLogger);
+        let (stylesheet, text) = loader.load_entry("main.scss").unwrap();
+        let error = loader.stream_text(&stylesheet, &text, vec![], &mut Vec::new(), &Nested {})
+                          .map_err(|e| loader.locate(e))
+                          .unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::UserError);
+        assert_eq!(error.message, "Expected a size, got auto.");
+        assert_eq!(
+            error.location.unwrap().stack.iter().map(|f| (f.url.as_str(), f.line)).collect::<Vec<_>>(),
+            vec![("_check.scss", 2), ("main.scss", 1)]
+        );
+    }
+
+    #[test]
+    fn it_reports_the_calls_an_error_happened_inside() {
+        let files = [
+            ("_lib.scss", "@mixin m {\n  @error \"boom\";\n}\n"),
+            ("main.scss", "@import \"lib\";\n@mixin n {\n  @include m;\n}\na {\n  @include n;\n}\n"),
+        ];
+        let mut importer = MemoryImporter::new();
+        for &(url, contents) in files.iter() {
+            importer.add(url, contents);
+        }
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(importer)];
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &StderrLogger);
+        let (stylesheet, text) = loader.load_entry("main.scss").unwrap();
+        let error = loader.stream_text(&stylesheet, &text, vec![], &mut Vec::new(), &Nested {})
+                          .map_err(|e| loader.locate(e))
+                          .unwrap_err();
+
+        assert_eq!(
+            error.location.unwrap().stack.iter().map(|f| (f.url.as_str(), f.line, f.name.as_str())).collect::<Vec<_>>(),
+            vec![
+                ("_lib.scss", 2, "m()"),
+                ("main.scss", 3, "n()"),
+                ("main.scss", 6, "root stylesheet"),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_locates_errors_in_the_file_they_came_from() {
+        let mut importer = MemoryImporter::new();
+        importer.add("_b.scss", "b {\n  c: $nope;\n}\n");
+        importer.add("main.scss", "@import \"b\";\n");
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(importer)];
+        let mut loader = ModuleLoader::new(&importers, HashMap::new(), &StderrLogger);
+        let (stylesheet, text) = loader.load_entry("main.scss").unwrap();
+        let error = loader.stream_text(&stylesheet, &text, vec![], &mut Vec::new(), &Nested {})
+                          .map_err(|e| loader.locate(e))
+                          .unwrap_err();
+
+        assert_eq!(
+            format!("{}", error),
+            "Error: Undefined variable `$nope`.\n  \
+               ╷\n\
+             2 │   c: $nope;\n  \
+               │      ^^^^^\n  \
+               ╵\n  \
+             _b.scss 2:6    @import\n  \
+             main.scss 1:9  root stylesheet"
+        );
+    }
+
+    #[test]
+    fn it_errors_on_nested_import_cycles() {
+        let files = [
+            ("_self.scss", "a { @import \"self\"; }\n"),
+            ("main.scss", "@import \"self\";\n"),
+        ];
+        assert_eq!(
+            compile_files(&files).map_err(|e| e.kind),
+            Err(ErrorKind::ImportError)
+        );
+    }
+
+    #[test]
+    fn it_errors_on_module_cycles() {
+        let files = [
+            ("_a.scss", "@use \"main\";\n"),
+            ("main.scss", "@use \"a\";\n"),
+        ];
+        assert_eq!(
+            compile_files(&files).map_err(|e| e.kind),
+            Err(ErrorKind::ImportError)
+        );
+    }
+}
@@ -2,6 +2,7 @@ use token::Token;
 use token_offset::TokenOffset;
 use operator::Operator;
 use operator_offset::OperatorOffset;
+use error::{Result, SassError, ErrorKind};
 
 use std::ops::{Add, Sub, Mul, Div, Rem};
 use std::fmt;
@@ -21,12 +22,9 @@ impl OperatorOrToken {
     }
 
     pub fn computed_number(&self) -> bool {
-        match *self {
-            OperatorOrToken::Token(TokenOffset {
-                token: Token::Number { computed: true, .. }, ..
-            }) => true,
-            _ => false,
-        }
+        matches!(*self, OperatorOrToken::Token(TokenOffset {
+            token: Token::Number { computed: true, .. }, ..
+        }))
     }
 
     pub fn extract_token(&self) -> Option<Token> {
@@ -47,9 +45,9 @@ impl OperatorOrToken {
 }
 
 impl Add for OperatorOrToken {
-    type Output = OperatorOrToken;
+    type Output = Result<OperatorOrToken>;
 
-    fn add(self, other: OperatorOrToken) -> OperatorOrToken {
+    fn add(self, other: OperatorOrToken) -> Result<OperatorOrToken> {
         match (self, other) {
             (
                 OperatorOrToken::Token(TokenOffset {
@@ -60,20 +58,23 @@ impl Add for OperatorOrToken {
                     token: other_token, ..
                 })
             ) => {
-                OperatorOrToken::Token(TokenOffset {
-                    token: self_token + other_token,
+                let token = (self_token + other_token).map_err(|e|
+                    SassError { offset: off.unwrap_or(0), ..e }
+                )?;
+                Ok(OperatorOrToken::Token(TokenOffset {
+                    token,
                     offset: off,
-                })
+                }))
             },
-            (s, other) => panic!("Cannot add: {:?} + {:?}", s, other),
+            (s, other) => Err(undefined_operation(s, "+", other)),
         }
     }
 }
 
 impl Sub for OperatorOrToken {
-    type Output = OperatorOrToken;
+    type Output = Result<OperatorOrToken>;
 
-    fn sub(self, other: OperatorOrToken) -> OperatorOrToken {
+    fn sub(self, other: OperatorOrToken) -> Result<OperatorOrToken> {
         match (self, other) {
             (
                 OperatorOrToken::Token(TokenOffset {
@@ -84,20 +85,23 @@ impl Sub for OperatorOrToken {
                     token: other_token, ..
                 })
             ) => {
-                OperatorOrToken::Token(TokenOffset {
-                    token: self_token - other_token,
+                let token = (self_token - other_token).map_err(|e|
+                    SassError { offset: off.unwrap_or(0), ..e }
+                )?;
+                Ok(OperatorOrToken::Token(TokenOffset {
+                    token,
                     offset: off,
-                })
+                }))
             },
-            (s, other) => panic!("Cannot subtract: {:?} - {:?}", s, other),
+            (s, other) => Err(undefined_operation(s, "-", other)),
         }
     }
 }
 
 impl Mul for OperatorOrToken {
-    type Output = OperatorOrToken;
+    type Output = Result<OperatorOrToken>;
 
-    fn mul(self, other: OperatorOrToken) -> OperatorOrToken {
+    fn mul(self, other: OperatorOrToken) -> Result<OperatorOrToken> {
         match (self, other) {
             (
                 OperatorOrToken::Token(TokenOffset {
@@ -108,20 +112,23 @@ impl Mul for OperatorOrToken {
                     token: other_token, ..
                 })
             ) => {
-                OperatorOrToken::Token(TokenOffset {
-                    token: self_token * other_token,
+                let token = (self_token * other_token).map_err(|e|
+                    SassError { offset: off.unwrap_or(0), ..e }
+                )?;
+                Ok(OperatorOrToken::Token(TokenOffset {
+                    token,
                     offset: off,
-                })
+                }))
             },
-            (s, other) => panic!("Cannot multiply: {:?} * {:?}", s, other),
+            (s, other) => Err(undefined_operation(s, "*", other)),
         }
     }
 }
 
 impl Div for OperatorOrToken {
-    type Output = OperatorOrToken;
+    type Output = Result<OperatorOrToken>;
 
-    fn div(self, other: OperatorOrToken) -> OperatorOrToken {
+    fn div(self, other: OperatorOrToken) -> Result<OperatorOrToken> {
         match (self, other) {
             (
                 OperatorOrToken::Token(TokenOffset {
@@ -132,20 +139,23 @@ impl Div for OperatorOrToken {
                     token: other_token, ..
                 })
             ) => {
-                OperatorOrToken::Token(TokenOffset {
-                    token: self_token / other_token,
+                let token = (self_token / other_token).map_err(|e|
+                    SassError { offset: off.unwrap_or(0), ..e }
+                )?;
+                Ok(OperatorOrToken::Token(TokenOffset {
+                    token,
                     offset: off,
-                })
+                }))
             },
-            (s, other) => panic!("Cannot divide: {:?} / {:?}", s, other),
+            (s, other) => Err(undefined_operation(s, "/", other)),
         }
     }
 }
 
 impl Rem for OperatorOrToken {
-    type Output = OperatorOrToken;
+    type Output = Result<OperatorOrToken>;
 
-    fn rem(self, other: OperatorOrToken) -> OperatorOrToken {
+    fn rem(self, other: OperatorOrToken) -> Result<OperatorOrToken> {
         match (self, other) {
             (
                 OperatorOrToken::Token(TokenOffset {
@@ -156,16 +166,29 @@ impl Rem for OperatorOrToken {
                     token: other_token, ..
                 })
             ) => {
-                OperatorOrToken::Token(TokenOffset {
-                    token: self_token % other_token,
+                let token = (self_token % other_token).map_err(|e|
+                    SassError { offset: off.unwrap_or(0), ..e }
+                )?;
+                Ok(OperatorOrToken::Token(TokenOffset {
+                    token,
                     offset: off,
-                })
+                }))
             },
-            (s, other) => panic!("Cannot find the remainder: {:?} % {:?}", s, other),
+            (s, other) => Err(undefined_operation(s, "%", other)),
         }
     }
 }
 
+fn undefined_operation(first: OperatorOrToken, operator: &str, second: OperatorOrToken) -> SassError {
+    SassError {
+        offset: first.offset().or(second.offset()).unwrap_or(0),
+        kind: ErrorKind::TypeError,
+        message: format!("Undefined operation `{} {} {}`.", first, operator, second),
+        location: None,
+        calls: vec![],
+    }
+}
+
 impl fmt::Display for OperatorOrToken {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
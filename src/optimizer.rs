@@ -1,16 +1,57 @@
+use ast::node::Node;
 use ast::root::Root;
 use sass::output_style::Streamable;
 
-pub fn optimize(root: Root) -> Vec<Box<Streamable>> {
+pub fn optimize(root: Root) -> Vec<Box<dyn Streamable>> {
     match root {
         Root::Rule(rule) => {
-            let mut result: Vec<Box<Streamable>> = Vec::new();
-            for r in rule.optimize().into_iter() {
-                result.push(Box::new(Root::Rule(r)));
+            let selectors = rule.selectors.clone();
+            let mut result: Vec<Box<dyn Streamable>> = Vec::new();
+            for node in rule.split_bubbled(&selectors).into_iter() {
+                optimize_part(node, &mut result);
             }
             result
         },
+        Root::Supports(supports) => vec![Box::new(Root::Supports(supports.optimize()))],
+        Root::Media(media) => optimized_media(media.optimize()),
         Root::Comment(c) => vec![Box::new(Root::Comment(c))],
         Root::Variable(..) => unreachable!(), // variables get evaluated before optimization
+        Root::Use(..) | Root::Forward(..) | Root::Import(..) => unreachable!(), // so do module and import rules
+        Root::Message(..) => unreachable!(), // and messages
+        Root::Mixin(..) | Root::Function(..) | Root::Include(..) => unreachable!(), // and mixins
+        Root::AtRoot(..) => unreachable!(), // and @at-root
+    }
+}
+
+// One of the parts a rule is split into around the blocks in it.
+fn optimize_part(node: Node, result: &mut Vec<Box<dyn Streamable>>) {
+    match node {
+        Node::Rule(part) => {
+            for r in part.optimize().into_iter() {
+                result.push(Box::new(Root::Rule(r)));
+            }
+        },
+        Node::Supports(supports) => result.push(Box::new(Root::Supports(supports.optimize()))),
+        Node::Media(media) => result.extend(optimized_media(media.optimize())),
+        Node::AtRoot(at_root) => {
+            for child in at_root.children.into_iter() {
+                optimize_part(child, result);
+            }
+        },
+        _ => {},
+    }
+}
+
+// A media block comes out of optimization with the blocks merged out of it
+// and the rules that left it.
+fn optimized_media(nodes: Vec<Node>) -> Vec<Box<dyn Streamable>> {
+    let mut result: Vec<Box<dyn Streamable>> = Vec::new();
+    for node in nodes.into_iter() {
+        match node {
+            Node::Media(media) => result.push(Box::new(Root::Media(media))),
+            Node::Rule(rule) => result.push(Box::new(Root::Rule(rule))),
+            _ => {},
+        }
     }
+    result
 }
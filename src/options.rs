@@ -0,0 +1,228 @@
+use error::{Result, SassError, ErrorKind};
+use importer::{Importer, Syntax};
+use logger::{Logger, StderrLogger};
+use sass::function::SassFunction;
+use sass::output_style::{SassOutputStyle, Nested, Compressed, Expanded,
+                         Compact, Debug};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputStyle {
+    Nested,
+    Expanded,
+    Compact,
+    Compressed,
+    // Shows the structure of the output rather than writing CSS.
+    Debug,
+}
+
+impl OutputStyle {
+    pub fn from_name(name: &str) -> Result<OutputStyle> {
+        match name {
+            "nested"     => Ok(OutputStyle::Nested),
+            "expanded"   => Ok(OutputStyle::Expanded),
+            "compact"    => Ok(OutputStyle::Compact),
+            "compressed" => Ok(OutputStyle::Compressed),
+            "debug"      => Ok(OutputStyle::Debug),
+            other        => Err(SassError {
+                offset: 0,
+                kind: ErrorKind::InvalidOutputStyle,
+                message: format!(
+                    "Unknown output style {:?}. Please specify one of nested, compressed, expanded, or compact.",
+                    other,
+                ),
+                location: None,
+                calls: vec![],
+            }),
+        }
+    }
+
+    pub fn output_style(&self) -> Box<dyn SassOutputStyle> {
+        match *self {
+            OutputStyle::Nested     => Box::new(Nested {}),
+            OutputStyle::Expanded   => Box::new(Expanded {}),
+            OutputStyle::Compact    => Box::new(Compact {}),
+            OutputStyle::Compressed => Box::new(Compressed {}),
+            OutputStyle::Debug      => Box::new(Debug {}),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndentType {
+    Space,
+    Tab,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineFeed {
+    Lf,
+    Crlf,
+    Cr,
+    Lfcr,
+}
+
+impl LineFeed {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LineFeed::Lf   => "\n",
+            LineFeed::Crlf => "\r\n",
+            LineFeed::Cr   => "\r",
+            LineFeed::Lfcr => "\n\r",
+        }
+    }
+}
+
+// Everything that can be configured about a compilation. Start from
+// `CompileOptions::new()`, which matches the command line defaults, and
+// chain the setters for anything that should be different.
+pub struct CompileOptions {
+    pub style: OutputStyle,
+    pub load_paths: Vec<PathBuf>,
+    // Tried in order before the file system.
+    pub importers: Vec<Box<dyn Importer>>,
+    // Digits after the decimal point in numbers.
+    pub precision: usize,
+    pub source_map: bool,
+    // Put the source map in a data URL in the CSS instead of its own file.
+    pub source_map_embed: bool,
+    // Include the contents of each stylesheet in the source map.
+    pub source_map_contents: bool,
+    // Where the source map will be served from, for the `sourceMappingURL`
+    // comment at the end of the CSS.
+    pub source_map_url: Option<String>,
+    // Mark the output as UTF-8 when it contains non-ASCII characters.
+    pub charset: bool,
+    pub indent_type: IndentType,
+    pub indent_width: usize,
+    pub linefeed: LineFeed,
+    pub functions: HashMap<String, SassFunction>,
+    // The syntax of stylesheets compiled from a string. Files get theirs
+    // from their extension.
+    pub syntax: Syntax,
+    // Gets the messages from `@debug` and `@warn`.
+    pub logger: Box<dyn Logger>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompileOptions {
+    pub fn new() -> CompileOptions {
+        CompileOptions {
+            style: OutputStyle::Nested,
+            load_paths: vec![],
+            importers: vec![],
+            precision: 10,
+            source_map: false,
+            source_map_embed: false,
+            source_map_contents: false,
+            source_map_url: None,
+            charset: true,
+            indent_type: IndentType::Space,
+            indent_width: 2,
+            linefeed: LineFeed::Lf,
+            functions: HashMap::new(),
+            syntax: Syntax::Scss,
+            logger: Box::new(StderrLogger),
+        }
+    }
+
+    pub fn style(mut self, style: OutputStyle) -> CompileOptions {
+        self.style = style;
+        self
+    }
+
+    pub fn load_path<P: Into<PathBuf>>(mut self, path: P) -> CompileOptions {
+        self.load_paths.push(path.into());
+        self
+    }
+
+    pub fn importer<I: Importer + 'static>(mut self, importer: I) -> CompileOptions {
+        self.importers.push(Box::new(importer));
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> CompileOptions {
+        self.precision = precision;
+        self
+    }
+
+    pub fn source_map(mut self, source_map: bool) -> CompileOptions {
+        self.source_map = source_map;
+        self
+    }
+
+    pub fn source_map_embed(mut self, embed: bool) -> CompileOptions {
+        self.source_map_embed = embed;
+        self
+    }
+
+    pub fn source_map_contents(mut self, contents: bool) -> CompileOptions {
+        self.source_map_contents = contents;
+        self
+    }
+
+    pub fn source_map_url(mut self, url: &str) -> CompileOptions {
+        self.source_map_url = Some(String::from(url));
+        self
+    }
+
+    pub fn charset(mut self, charset: bool) -> CompileOptions {
+        self.charset = charset;
+        self
+    }
+
+    pub fn indent_type(mut self, indent_type: IndentType) -> CompileOptions {
+        self.indent_type = indent_type;
+        self
+    }
+
+    pub fn indent_width(mut self, indent_width: usize) -> CompileOptions {
+        self.indent_width = indent_width;
+        self
+    }
+
+    pub fn linefeed(mut self, linefeed: LineFeed) -> CompileOptions {
+        self.linefeed = linefeed;
+        self
+    }
+
+    // `name` can be called like a function in the stylesheet, e.g.
+    // `width: double(2px)`.
+    pub fn function<F>(mut self, name: &str, function: F) -> CompileOptions
+        where F: Fn(&[String]) -> Result<String> + 'static {
+        self.functions.insert(String::from(name), SassFunction::new(function));
+        self
+    }
+
+    pub fn syntax(mut self, syntax: Syntax) -> CompileOptions {
+        self.syntax = syntax;
+        self
+    }
+
+    pub fn logger<L: Logger + 'static>(mut self, logger: L) -> CompileOptions {
+        self.logger = Box::new(logger);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::ErrorKind;
+
+    #[test]
+    fn it_parses_output_style_names() {
+        assert_eq!(OutputStyle::from_name("compact"), Ok(OutputStyle::Compact));
+        assert_eq!(
+            OutputStyle::from_name("fancy").map_err(|e| e.kind),
+            Err(ErrorKind::InvalidOutputStyle)
+        );
+    }
+}
@@ -10,20 +10,51 @@ use ast::node::Node;
 use sass::rule::SassRule;
 use sass::variable::SassVariable;
 use sass::comment::SassComment;
+use sass::use_rule::SassUse;
+use sass::forward_rule::{SassForward, ForwardVisibility};
+use sass::import_rule::SassImport;
+use sass::message_rule::{SassMessage, MessageKind};
+use sass::mixin::{SassMixin, SassInclude, SassContentBlock, SassContent};
+use sass::function_rule::SassFunctionRule;
+use sass::arguments::{ArgumentDeclaration, ArgumentInvocation};
+use sass::at_root::{SassAtRoot, AtRootQuery};
+use sass::supports::{SassSupports, SupportsCondition};
+use sass::media::{SassMedia, MediaQuery, MediaFeature};
 use error::{Result, SassError, ErrorKind};
 
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
 pub struct Parser<'a> {
     pub tokenizer: Tokenizer<'a>,
+    // Errors the parser recovered from while parsing a rule body. `next`
+    // returns them before the rule, which waits in `held`.
+    errors: Vec<SassError>,
+    held: Option<Root>,
 }
 
 impl<'a> Iterator for Parser<'a> {
     type Item = Result<Root>;
 
     fn next(&mut self) -> Option<Result<Root>> {
+        if !self.errors.is_empty() {
+            return Some(Err(self.errors.remove(0)))
+        }
+        if let Some(root) = self.held.take() {
+            return Some(Ok(root))
+        }
+
         let mut current_sass_rule = SassRule::new();
         let mut ambiguous_holding_pen: Vec<TokenOffset> = vec![];
 
-        while let Some(Ok(op_or_token)) = self.tokenizer.next() {
+        while let Some(op_or_token) = self.tokenizer.next() {
+            let op_or_token = match op_or_token {
+                Ok(t) => t,
+                Err(e) => {
+                    self.skip_statement();
+                    return Some(Err(e))
+                },
+            };
             match op_or_token {
                 OperatorOrToken::Token(TokenOffset {
                     token: Token::String(ref string_val), offset: off,
@@ -33,14 +64,21 @@ impl<'a> Iterator for Parser<'a> {
                         offset: off,
                     };
 
-                    if let Err(e) = Parser::expect_operator(
-                        Operator::Colon,
-                        self.tokenizer.next()
-                    ) { return Some(Err(e)) };
+                    let next = self.tokenizer.next();
+                    let ended = is_semicolon(&next);
+                    if let Err(e) = Parser::expect_operator(Operator::Colon, next) {
+                        if !ended {
+                            self.skip_statement();
+                        }
+                        return Some(Err(e))
+                    };
 
                     let variable_value = match Expression::parse(&mut self.tokenizer) {
                         Ok(e) => e,
-                        Err(e) => return Some(Err(e)),
+                        Err(e) => {
+                            self.skip_statement();
+                            return Some(Err(e))
+                        },
                     };
                     return Some(Ok(Root::Variable(
                         SassVariable {
@@ -49,6 +87,82 @@ impl<'a> Iterator for Parser<'a> {
                         }
                     )))
                 },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@use" => {
+                    return Some(self.parse_use(off))
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@forward" => {
+                    return Some(self.parse_forward(off))
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@import" => {
+                    return Some(self.parse_import(off).map(Root::Import))
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if message_kind(string_val).is_some() => {
+                    let kind = message_kind(string_val).unwrap();
+                    return Some(self.parse_message(kind, off).map(Root::Message))
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@mixin" || string_val == "@function" => {
+                    let is_mixin = string_val == "@mixin";
+                    let (name, parameters, children) = match self.parse_callable(off) {
+                        Ok(parsed) => parsed,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    let root = if is_mixin {
+                        Root::Mixin(SassMixin {
+                            name,
+                            parameters,
+                            children,
+                            scope: None,
+                        })
+                    } else {
+                        Root::Function(SassFunctionRule {
+                            name,
+                            parameters,
+                            children,
+                            scope: None,
+                        })
+                    };
+                    if !self.errors.is_empty() {
+                        self.held = Some(root);
+                        return Some(Err(self.errors.remove(0)))
+                    }
+                    return Some(Ok(root))
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if ambiguous_holding_pen.is_empty() && unsupported_keyframes(string_val, off).is_some() => {
+                    self.skip_statement();
+                    return Some(Err(unsupported_keyframes(string_val, off).unwrap()))
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@include" || string_val == "@at-root" ||
+                     string_val == "@supports" || string_val == "@media" => {
+                    let parsed = match &string_val[..] {
+                        "@include" => self.parse_include(off).map(Root::Include),
+                        "@at-root" => self.parse_at_root(off).map(Root::AtRoot),
+                        "@media" => self.parse_media(off).map(Root::Media),
+                        _ => self.parse_supports(off).map(Root::Supports),
+                    };
+                    let root = match parsed {
+                        Ok(root) => root,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    if !self.errors.is_empty() {
+                        self.held = Some(root);
+                        return Some(Err(self.errors.remove(0)))
+                    }
+                    return Some(Ok(root))
+                },
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::LeftCurlyBrace, ..
                 }) => {
@@ -59,8 +173,15 @@ impl<'a> Iterator for Parser<'a> {
                         Ok(body) => body,
                         Err(e) => return Some(Err(e)),
                     };
+                    if !self.errors.is_empty() {
+                        self.held = Some(Root::Rule(current_sass_rule));
+                        return Some(Err(self.errors.remove(0)))
+                    }
                     return Some(Ok(Root::Rule(current_sass_rule)))
                 },
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Semicolon, ..
+                }) if ambiguous_holding_pen.is_empty() => {},
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::Comma, ..
                 }) => {
@@ -82,7 +203,7 @@ impl<'a> Iterator for Parser<'a> {
                     match ambiguous_holding_pen.pop() {
                         Some(held) => {
                             ambiguous_holding_pen.push(
-                                held.combine(&op_or_token)
+                                self.combine(held, &op_or_token)
                             );
                         },
                         None => {
@@ -97,10 +218,109 @@ impl<'a> Iterator for Parser<'a> {
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(text: &str) -> Parser {
+    pub fn new(text: &str) -> Parser<'_> {
+        Parser::starting_at(text, 0)
+    }
+
+    pub fn starting_at(text: &str, start: usize) -> Parser<'_> {
+        Parser::with_tokenizer(Tokenizer::starting_at(text, start))
+    }
+
+    // Parses the indented syntax into the same AST as SCSS.
+    pub fn indented(text: &str, start: usize) -> Parser<'_> {
+        Parser::with_tokenizer(Tokenizer::indented(text, start))
+    }
+
+    pub fn with_tokenizer(tokenizer: Tokenizer) -> Parser {
         Parser {
-            tokenizer: Tokenizer::new(&text),
+            tokenizer,
+            errors: vec![],
+            held: None,
+        }
+    }
+
+    // Selectors are spaced out token by token, apart from pseudo selectors
+    // like `a:hover`, `::before` and `:not(.b)` written without spaces.
+    fn combine(&self, held: TokenOffset, next: &OperatorOrToken) -> TokenOffset {
+        let pseudo = match next.extract_operator() {
+            Some(Operator::Colon) | Some(Operator::LeftParen) | Some(Operator::RightParen) => true,
+            _ => held.token.to_string().ends_with([':', '(', ')']),
+        };
+        if pseudo && !self.tokenizer.after_whitespace() {
+            TokenOffset {
+                token: Token::String(format!("{}{}", held, next)),
+                offset: held.offset,
+            }
+        } else {
+            held.combine(next)
+        }
+    }
+
+    // A declaration's value, up to the `;` that ends it, or up to the `}`
+    // closing the block when the last declaration leaves its `;` off.
+    fn parse_declaration_value(&mut self) -> Result<Expression> {
+        let mut tokens = vec![];
+        loop {
+            match self.tokenizer.next() {
+                Some(Ok(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Semicolon, ..
+                }))) => break,
+                Some(Ok(close @ OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::RightCurlyBrace, ..
+                }))) => {
+                    self.tokenizer.push_back(close);
+                    break
+                },
+                Some(Ok(OperatorOrToken::Token(TokenOffset {
+                    token: Token::Comment(_), ..
+                }))) => {},
+                Some(Ok(t)) => tokens.push(t),
+                Some(Err(e)) => return Err(e),
+                // Reports the missing `;` just as `Expression::parse` does.
+                None => return Expression::parse(&mut tokens.into_iter().map(Ok)),
+            }
         }
+        Ok(Expression::from_tokens(tokens))
+    }
+
+    // Throws away the rest of a statement that failed to parse, so parsing
+    // can pick up again at the next one. Stops after a `;` or a whole
+    // `{ ... }` block, or before the `}` that closes the enclosing rule.
+    fn skip_statement(&mut self) {
+        let mut depth = 0;
+        while let Some(op_or_token) = self.tokenizer.next() {
+            match op_or_token {
+                Ok(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Semicolon, ..
+                })) if depth == 0 => return,
+                Ok(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::LeftCurlyBrace, ..
+                })) => depth += 1,
+                Ok(close @ OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::RightCurlyBrace, ..
+                })) => {
+                    if depth == 0 {
+                        self.tokenizer.push_back(close);
+                        return
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        return
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // Keeps parsing the rest of a rule body after `error`, unless there's
+    // nothing left to parse.
+    fn recover(&mut self, error: SassError) -> Result<()> {
+        if error.kind == ErrorKind::UnexpectedEof {
+            return Err(error)
+        }
+        self.errors.push(error);
+        Ok(())
     }
 
     pub fn expect_operator(expected: Operator, actual: Option<Result<OperatorOrToken>>) -> Result<OperatorOrToken> {
@@ -112,6 +332,8 @@ impl<'a> Parser<'a> {
                     "Expected to see `{}`, instead reached EOF.",
                     expected,
                 ),
+                location: None,
+                calls: vec![],
             }),
             Some(res) => {
                 match res {
@@ -134,6 +356,8 @@ impl<'a> Parser<'a> {
                                 expected,
                                 actual_token,
                             ),
+                            location: None,
+                            calls: vec![],
                         })
                     },
                     Ok(OperatorOrToken::Operator(OperatorOffset {
@@ -147,6 +371,8 @@ impl<'a> Parser<'a> {
                                 expected,
                                 actual_operator,
                             ),
+                            location: None,
+                            calls: vec![],
                         })
                     }
                 }
@@ -154,12 +380,798 @@ impl<'a> Parser<'a> {
         }
     }
 
+    pub fn parse_use(&mut self, offset: Option<usize>) -> Result<Root> {
+        let mut tokens = self.tokens_until_semicolon(offset)?.into_iter().peekable();
+        let url = Parser::expect_url(tokens.next(), offset)?;
+        let mut namespace = None;
+        let mut config = vec![];
+
+        while let Some(op_or_token) = tokens.next() {
+            match op_or_token.extract_token() {
+                Some(Token::String(ref s)) if s == "as" => {
+                    namespace = match tokens.next() {
+                        Some(OperatorOrToken::Operator(OperatorOffset {
+                            operator: Operator::Star, ..
+                        })) => Some(String::from("*")),
+                        Some(OperatorOrToken::Token(TokenOffset {
+                            token: Token::String(ns), ..
+                        })) => Some(ns),
+                        other => return Err(
+                            Parser::unexpected_error("a namespace", other, offset)
+                        ),
+                    };
+                },
+                Some(Token::String(ref s)) if s == "with" => {
+                    config = Parser::parse_configuration(&mut tokens, offset)?;
+                },
+                _ => return Err(
+                    Parser::unexpected_error("`as` or `with`", Some(op_or_token), offset)
+                ),
+            }
+        }
+
+        Ok(Root::Use(SassUse {
+            url,
+            namespace,
+            config,
+        }))
+    }
+
+    pub fn parse_forward(&mut self, offset: Option<usize>) -> Result<Root> {
+        let mut tokens = self.tokens_until_semicolon(offset)?.into_iter().peekable();
+        let url = Parser::expect_url(tokens.next(), offset)?;
+        let mut prefix = None;
+        let mut visibility = ForwardVisibility::All;
+        let mut config = vec![];
+
+        while let Some(op_or_token) = tokens.next() {
+            match op_or_token.extract_token() {
+                Some(Token::String(ref s)) if s == "as" => {
+                    // `as list-*` tokenizes as `list-` followed by `*`.
+                    prefix = match (tokens.next(), tokens.next()) {
+                        (
+                            Some(OperatorOrToken::Token(TokenOffset {
+                                token: Token::String(p), ..
+                            })),
+                            Some(OperatorOrToken::Operator(OperatorOffset {
+                                operator: Operator::Star, ..
+                            })),
+                        ) => Some(p),
+                        (other, _) => return Err(
+                            Parser::unexpected_error("a prefix like `list-*`", other, offset)
+                        ),
+                    };
+                },
+                Some(Token::String(ref s)) if s == "show" || s == "hide" => {
+                    let mut names = vec![];
+                    while let Some(next) = tokens.peek().and_then(|t| t.extract_token()) {
+                        if next == Token::String(String::from("with")) {
+                            break
+                        }
+                        names.push(next.to_string());
+                        tokens.next();
+                        if tokens.peek().and_then(|t| t.extract_operator()) == Some(Operator::Comma) {
+                            tokens.next();
+                        }
+                    }
+                    visibility = if s == "show" {
+                        ForwardVisibility::Show(names)
+                    } else {
+                        ForwardVisibility::Hide(names)
+                    };
+                },
+                Some(Token::String(ref s)) if s == "with" => {
+                    config = Parser::parse_configuration(&mut tokens, offset)?;
+                },
+                _ => return Err(Parser::unexpected_error(
+                    "`as`, `show`, `hide` or `with`", Some(op_or_token), offset
+                )),
+            }
+        }
+
+        Ok(Root::Forward(SassForward {
+            url,
+            prefix,
+            visibility,
+            config,
+        }))
+    }
+
+    // `@import "a", "b" screen;` is one import per comma-separated URL, each
+    // with any media query that follows it.
+    pub fn parse_import(&mut self, offset: Option<usize>) -> Result<Vec<SassImport>> {
+        let tokens = self.tokens_until_semicolon(offset)?;
+        let mut imports = vec![];
+        let mut segment = vec![];
+        let mut paren_level = 0;
+
+        for op_or_token in tokens.into_iter() {
+            match op_or_token.extract_operator() {
+                Some(Operator::Comma) if paren_level == 0 => {
+                    imports.push(Parser::import_from(segment, offset)?);
+                    segment = vec![];
+                    continue
+                },
+                Some(Operator::LeftParen) => paren_level += 1,
+                Some(Operator::RightParen) => paren_level -= 1,
+                _ => {},
+            }
+            segment.push(op_or_token);
+        }
+        imports.push(Parser::import_from(segment, offset)?);
+        Ok(imports)
+    }
+
+    fn import_from(segment: Vec<OperatorOrToken>, offset: Option<usize>) -> Result<SassImport> {
+        let mut segment = segment.into_iter();
+        let url = match segment.next() {
+            Some(OperatorOrToken::Token(t @ TokenOffset {
+                token: Token::StringLiteral(_), ..
+            })) => t,
+            Some(OperatorOrToken::Token(t @ TokenOffset {
+                token: Token::String(_), ..
+            })) if t.token.to_string().starts_with("url(") => t,
+            other => return Err(Parser::unexpected_error("a URL to import", other, offset)),
+        };
+        let media = segment.collect::<Vec<_>>();
+
+        Ok(SassImport {
+            url,
+            media: if media.is_empty() { None } else { Some(join_tokens(&media)) },
+        })
+    }
+
+    pub fn parse_message(&mut self, kind: MessageKind, offset: Option<usize>) -> Result<SassMessage> {
+        match Expression::parse(&mut self.tokenizer) {
+            Ok(value) => Ok(SassMessage {
+                kind,
+                value,
+                offset,
+            }),
+            Err(e) => {
+                self.skip_statement();
+                Err(e)
+            },
+        }
+    }
+
+    // `@mixin` and `@function` share a syntax: a name, optional parameters
+    // in parentheses, and a body.
+    fn parse_callable(&mut self, offset: Option<usize>)
+                      -> Result<(TokenOffset, ArgumentDeclaration, Vec<Node>)> {
+        let mut tokens = vec![];
+        loop {
+            match self.tokenizer.next() {
+                Some(Ok(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::LeftCurlyBrace, ..
+                }))) => break,
+                Some(Ok(t)) => tokens.push(t),
+                Some(Err(e)) => {
+                    self.skip_statement();
+                    return Err(e)
+                },
+                None => return Err(SassError {
+                    offset: offset.unwrap_or(0),
+                    kind: ErrorKind::UnexpectedEof,
+                    message: String::from("Expected to see `{`, instead reached EOF."),
+                    location: None,
+                    calls: vec![],
+                }),
+            }
+        }
+
+        let (name, parameters) = match Parser::name_and_arguments(tokens, offset) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                // Skip the body too.
+                self.tokenizer.push_back(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::LeftCurlyBrace,
+                    offset,
+                }));
+                self.skip_statement();
+                return Err(e)
+            },
+        };
+        let parameters = ArgumentDeclaration::parse(parameters, offset)?;
+        let children = self.parse_body()?;
+        Ok((name, parameters, children))
+    }
+
+    pub fn parse_include(&mut self, offset: Option<usize>) -> Result<SassInclude> {
+        let (mut tokens, has_block) = self.tokens_until_statement_end(offset)?;
+        // The block is parsed first so it's skipped over even if the rest
+        // of the `@include` is wrong.
+        let children = if has_block { Some(self.parse_body()?) } else { None };
+
+        let using = tokens.iter().position(|t|
+            t.extract_token() == Some(Token::String(String::from("using")))
+        );
+        let content = match (children, using) {
+            (Some(children), Some(i)) => {
+                let parameters = tokens.split_off(i + 1)
+                                       .into_iter()
+                                       .map(Expression::Value)
+                                       .collect();
+                tokens.pop();
+                let parameters = Parser::parenthesized(parameters, offset)?;
+                Some(SassContentBlock {
+                    parameters: ArgumentDeclaration::parse(parameters, offset)?,
+                    children,
+                })
+            },
+            (Some(children), None) => Some(SassContentBlock {
+                parameters: ArgumentDeclaration::new(),
+                children,
+            }),
+            (None, Some(i)) => return Err(Parser::unexpected_error(
+                "`;`", Some(tokens.swap_remove(i)), offset
+            )),
+            (None, None) => None,
+        };
+
+        let (name, arguments) = Parser::name_and_arguments(tokens, offset)?;
+        Ok(SassInclude {
+            name,
+            arguments: ArgumentInvocation::parse(arguments, offset)?,
+            content,
+        })
+    }
+
+    pub fn parse_at_root(&mut self, offset: Option<usize>) -> Result<SassAtRoot> {
+        let (tokens, has_block) = self.tokens_until_statement_end(offset)?;
+        if !has_block {
+            return Err(Parser::unexpected_error("`{`", None, offset))
+        }
+        let children = self.parse_body()?;
+
+        let is_query = tokens.first().and_then(|t| t.extract_operator()) == Some(Operator::LeftParen);
+        if is_query {
+            return Ok(SassAtRoot {
+                query: Parser::at_root_query(tokens, offset)?,
+                children,
+            })
+        }
+        if tokens.is_empty() {
+            return Ok(SassAtRoot {
+                query: AtRootQuery::new(),
+                children,
+            })
+        }
+
+        // `@at-root selector { ... }` is short for a block with just that
+        // rule in it.
+        let mut selectors: Vec<TokenOffset> = vec![];
+        let mut after_comma = true;
+        for op_or_token in tokens.into_iter() {
+            if op_or_token.extract_operator() == Some(Operator::Comma) {
+                after_comma = true;
+            } else if after_comma {
+                selectors.push(op_or_token.into());
+                after_comma = false;
+            } else {
+                let held = selectors.pop().unwrap();
+                selectors.push(held.combine(&op_or_token));
+            }
+        }
+        Ok(SassAtRoot {
+            query: AtRootQuery::new(),
+            children: vec![Node::Rule(SassRule {
+                selectors,
+                children,
+            })],
+        })
+    }
+
+    // `(with: names...)` or `(without: names...)`.
+    fn at_root_query(tokens: Vec<OperatorOrToken>, offset: Option<usize>) -> Result<AtRootQuery> {
+        let mut tokens = tokens.into_iter();
+        tokens.next();
+        let with = match tokens.next() {
+            Some(OperatorOrToken::Token(TokenOffset {
+                token: Token::String(ref s), ..
+            })) if s == "with" || s == "without" => s == "with",
+            other => return Err(Parser::unexpected_error("`with` or `without`", other, offset)),
+        };
+        match tokens.next() {
+            Some(OperatorOrToken::Operator(OperatorOffset {
+                operator: Operator::Colon, ..
+            })) => {},
+            other => return Err(Parser::unexpected_error("`:`", other, offset)),
+        }
+
+        let mut names = vec![];
+        loop {
+            match tokens.next() {
+                Some(OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(name), ..
+                })) => names.push(name),
+                Some(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::RightParen, ..
+                })) if !names.is_empty() => break,
+                other => return Err(Parser::unexpected_error("a rule name", other, offset)),
+            }
+        }
+        if let Some(extra) = tokens.next() {
+            return Err(Parser::unexpected_error("`{`", Some(extra), offset))
+        }
+        Ok(if with { AtRootQuery::With(names) } else { AtRootQuery::Without(names) })
+    }
+
+    pub fn parse_supports(&mut self, offset: Option<usize>) -> Result<SassSupports> {
+        let tokens = self.tokens_until_block(offset)?;
+        let children = self.parse_body()?;
+
+        let mut tokens = tokens.into_iter().peekable();
+        let condition = Parser::supports_condition(&mut tokens, offset)?;
+        if let Some(extra) = tokens.next() {
+            return Err(Parser::unexpected_error("`{`", Some(extra), offset))
+        }
+        Ok(SassSupports {
+            condition,
+            children,
+            offset,
+        })
+    }
+
+    pub fn parse_media(&mut self, offset: Option<usize>) -> Result<SassMedia> {
+        let tokens = self.tokens_until_block(offset)?;
+        let children = self.parse_body()?;
+
+        let interpolated = tokens.windows(2).any(|pair|
+            is_hash(Some(&pair[0])) && pair[1].extract_operator() == Some(Operator::LeftCurlyBrace)
+        );
+        let queries = if interpolated {
+            vec![MediaQuery {
+                interpolated: Some(join_interpolated(tokens)),
+                ..MediaQuery::new()
+            }]
+        } else {
+            Parser::media_queries(tokens, offset)?
+        };
+        Ok(SassMedia {
+            queries,
+            children,
+            offset,
+        })
+    }
+
+    // `query, query, ...`, all the way to the end of `tokens`.
+    pub fn media_queries(tokens: Vec<OperatorOrToken>, offset: Option<usize>) -> Result<Vec<MediaQuery>> {
+        let mut queries = vec![];
+        let mut tokens = tokens.into_iter().peekable();
+        loop {
+            queries.push(Parser::media_query(&mut tokens, offset)?);
+            match tokens.next() {
+                Some(ref t) if t.extract_operator() == Some(Operator::Comma) => {},
+                None => break,
+                other => return Err(Parser::unexpected_error("`{`", other, offset)),
+            }
+        }
+        Ok(queries)
+    }
+
+    // `[not | only] type [and (feature)]...` or `(feature) [and (feature)]...`.
+    fn media_query(tokens: &mut Peekable<IntoIter<OperatorOrToken>>, offset: Option<usize>)
+                   -> Result<MediaQuery> {
+        let mut query = MediaQuery::new();
+        if is_word(tokens.peek(), "not") || is_word(tokens.peek(), "only") {
+            query.modifier = tokens.next().map(|t| t.to_string());
+        }
+        let starts_with_type = matches!(tokens.peek(), Some(&OperatorOrToken::Token(TokenOffset {
+            token: Token::String(..), ..
+        })));
+        if starts_with_type {
+            query.media_type = tokens.next().map(|t| t.to_string());
+            if !is_word(tokens.peek(), "and") {
+                return Ok(query)
+            }
+            tokens.next();
+        } else if query.modifier.is_some() {
+            return Err(Parser::unexpected_error("a media type", tokens.next(), offset))
+        }
+
+        loop {
+            match tokens.next() {
+                Some(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::LeftParen, ..
+                })) => {},
+                other => return Err(Parser::unexpected_error("`(`", other, offset)),
+            }
+            let inside = Parser::until_closing_paren(tokens, offset)?;
+            let colon = inside.iter().position(|t| t.extract_operator() == Some(Operator::Colon));
+            query.features.push(match colon {
+                Some(i) => {
+                    let value = inside[i + 1..].iter()
+                                               .filter(|t| !is_interpolation_mark(t))
+                                               .cloned()
+                                               .collect();
+                    MediaFeature::Declaration(join_tokens(&inside[..i]), Expression::from_tokens(value))
+                },
+                None => MediaFeature::Raw(format!("({})", join_tokens(&inside))),
+            });
+            if !is_word(tokens.peek(), "and") {
+                return Ok(query)
+            }
+            tokens.next();
+        }
+    }
+
+    // The tokens up to the `{` that opens a block, going past any in `#{`.
+    fn tokens_until_block(&mut self, offset: Option<usize>) -> Result<Vec<OperatorOrToken>> {
+        let mut tokens: Vec<OperatorOrToken> = vec![];
+        let mut interpolation_level = 0;
+        loop {
+            let op_or_token = match self.tokenizer.next() {
+                Some(Ok(t)) => t,
+                Some(Err(e)) => {
+                    self.skip_statement();
+                    return Err(e)
+                },
+                None => return Err(SassError {
+                    offset: offset.unwrap_or(0),
+                    kind: ErrorKind::UnexpectedEof,
+                    message: String::from("Expected to see `{`, instead reached EOF."),
+                    location: None,
+                    calls: vec![],
+                }),
+            };
+            match op_or_token.extract_operator() {
+                Some(Operator::LeftCurlyBrace) if is_hash(tokens.last()) => interpolation_level += 1,
+                Some(Operator::LeftCurlyBrace) if interpolation_level == 0 => break,
+                Some(Operator::RightCurlyBrace) if interpolation_level > 0 => interpolation_level -= 1,
+                Some(Operator::Semicolon) if interpolation_level == 0 => {
+                    return Err(Parser::unexpected_error("`{`", Some(op_or_token), offset))
+                },
+                _ => {},
+            }
+            tokens.push(op_or_token);
+        }
+        Ok(tokens)
+    }
+
+    // `not (a)`, `(a) and (b) and ...` or `(a) or (b) or ...`.
+    fn supports_condition(tokens: &mut Peekable<IntoIter<OperatorOrToken>>, offset: Option<usize>)
+                          -> Result<SupportsCondition> {
+        if is_word(tokens.peek(), "not") {
+            tokens.next();
+            let negated = Parser::supports_in_parens(tokens, offset)?;
+            return Ok(SupportsCondition::Not(Box::new(negated)))
+        }
+
+        let mut conditions = vec![Parser::supports_in_parens(tokens, offset)?];
+        let mut operator: Option<String> = None;
+        while is_word(tokens.peek(), "and") || is_word(tokens.peek(), "or") {
+            let word = tokens.next().unwrap().to_string();
+            if operator.as_ref().is_some_and(|o| *o != word) {
+                return Err(SassError {
+                    offset: offset.unwrap_or(0),
+                    kind: ErrorKind::ParserError,
+                    message: String::from("Use parentheses to mix `and` and `or`."),
+                    location: None,
+                    calls: vec![],
+                })
+            }
+            operator = Some(word);
+            conditions.push(Parser::supports_in_parens(tokens, offset)?);
+        }
+        Ok(match operator.as_ref().map(|o| &o[..]) {
+            Some("and") => SupportsCondition::And(conditions),
+            Some(_) => SupportsCondition::Or(conditions),
+            None => conditions.pop().unwrap(),
+        })
+    }
+
+    // `(condition)`, `(name: value)`, `#{...}` or a function like
+    // `selector(a > b)`.
+    fn supports_in_parens(tokens: &mut Peekable<IntoIter<OperatorOrToken>>, offset: Option<usize>)
+                          -> Result<SupportsCondition> {
+        match tokens.next() {
+            Some(OperatorOrToken::Operator(OperatorOffset {
+                operator: Operator::LeftParen, ..
+            })) => {
+                let inside = Parser::until_closing_paren(tokens, offset)?;
+                let mut paren_level = 0;
+                let colon = inside.iter().position(|t| match t.extract_operator() {
+                    Some(Operator::LeftParen) => { paren_level += 1; false },
+                    Some(Operator::RightParen) => { paren_level -= 1; false },
+                    Some(Operator::Colon) => paren_level == 0,
+                    _ => false,
+                });
+                match colon {
+                    Some(i) => {
+                        let value = inside[i + 1..].iter()
+                                                   .filter(|t| !is_interpolation_mark(t))
+                                                   .cloned()
+                                                   .collect();
+                        let mut name = inside;
+                        name.truncate(i);
+                        let name = match name.first() {
+                            Some(t) if is_hash(Some(t)) => {
+                                Parser::supports_in_parens(&mut name.into_iter().peekable(), offset)?
+                            },
+                            Some(t) if t.to_string().starts_with('$') => {
+                                SupportsCondition::Script(Expression::from_tokens(name))
+                            },
+                            _ => SupportsCondition::Raw(join_tokens(&name)),
+                        };
+                        Ok(SupportsCondition::Declaration(Box::new(name), Expression::from_tokens(value)))
+                    },
+                    None => {
+                        let mut inside = inside.into_iter().peekable();
+                        let condition = Parser::supports_condition(&mut inside, offset)?;
+                        if let Some(extra) = inside.next() {
+                            return Err(Parser::unexpected_error("`)`", Some(extra), offset))
+                        }
+                        Ok(SupportsCondition::Parenthesized(Box::new(condition)))
+                    },
+                }
+            },
+            Some(hash @ OperatorOrToken::Token(..)) if is_hash(Some(&hash)) => {
+                match tokens.next() {
+                    Some(OperatorOrToken::Operator(OperatorOffset {
+                        operator: Operator::LeftCurlyBrace, ..
+                    })) => {},
+                    other => return Err(Parser::unexpected_error("`{`", other, offset)),
+                }
+                let mut value = vec![];
+                loop {
+                    match tokens.next() {
+                        Some(OperatorOrToken::Operator(OperatorOffset {
+                            operator: Operator::RightCurlyBrace, ..
+                        })) => break,
+                        Some(t) => value.push(t),
+                        None => return Err(Parser::unexpected_error("`}`", None, offset)),
+                    }
+                }
+                Ok(SupportsCondition::Interpolation(Expression::from_tokens(value)))
+            },
+            Some(OperatorOrToken::Token(TokenOffset {
+                token: Token::String(name), ..
+            })) if tokens.peek().and_then(|t| t.extract_operator()) == Some(Operator::LeftParen) => {
+                tokens.next();
+                let arguments = Parser::until_closing_paren(tokens, offset)?;
+                Ok(SupportsCondition::Raw(format!("{}({})", name, join_tokens(&arguments))))
+            },
+            other => Err(Parser::unexpected_error("a condition like `(display: grid)`", other, offset)),
+        }
+    }
+
+    // The tokens up to the `)` matching one that's just been read.
+    fn until_closing_paren(tokens: &mut Peekable<IntoIter<OperatorOrToken>>, offset: Option<usize>)
+                           -> Result<Vec<OperatorOrToken>> {
+        let mut inside = vec![];
+        let mut paren_level = 0;
+        loop {
+            let op_or_token = match tokens.next() {
+                Some(t) => t,
+                None => return Err(Parser::unexpected_error("`)`", None, offset)),
+            };
+            match op_or_token.extract_operator() {
+                Some(Operator::RightParen) if paren_level == 0 => return Ok(inside),
+                Some(Operator::LeftParen) => paren_level += 1,
+                Some(Operator::RightParen) => paren_level -= 1,
+                _ => {},
+            }
+            inside.push(op_or_token);
+        }
+    }
+
+    pub fn parse_content(&mut self, offset: Option<usize>) -> Result<SassContent> {
+        let arguments = self.tokens_until_semicolon(offset)?
+                            .into_iter()
+                            .map(Expression::Value)
+                            .collect();
+        let arguments = Parser::parenthesized(arguments, offset)?;
+        Ok(SassContent {
+            arguments: ArgumentInvocation::parse(arguments, offset)?,
+            offset,
+        })
+    }
+
+    // Splits `name(arguments...)` into the name and what's between the
+    // parentheses.
+    fn name_and_arguments(tokens: Vec<OperatorOrToken>, offset: Option<usize>)
+                          -> Result<(TokenOffset, Vec<Expression>)> {
+        let mut tokens = tokens.into_iter();
+        let name = match tokens.next() {
+            Some(OperatorOrToken::Token(t @ TokenOffset {
+                token: Token::String(_), ..
+            })) => t,
+            other => return Err(Parser::unexpected_error("a name", other, offset)),
+        };
+
+        let arguments = Parser::parenthesized(
+            tokens.map(Expression::Value).collect(), name.offset
+        )?;
+        Ok((name, arguments))
+    }
+
+    // What's between the parentheses in `(arguments...)`, which can be
+    // left off entirely.
+    fn parenthesized(mut arguments: Vec<Expression>, offset: Option<usize>)
+                     -> Result<Vec<Expression>> {
+        if arguments.is_empty() {
+            return Ok(arguments)
+        }
+        let closed = arguments.last().is_some_and(|a| a.is_right_paren());
+        if !arguments[0].is_left_paren() || !closed {
+            let unexpected = arguments.remove(0).extract_operator_offset()
+                                      .map(OperatorOrToken::Operator)
+                                      .ok();
+            return Err(Parser::unexpected_error("`(`", unexpected, offset))
+        }
+        arguments.pop();
+        arguments.remove(0);
+        Ok(arguments)
+    }
+
+    // Reads a statement up to the `;` that ends it or the `{` that opens
+    // its block, saying which it was.
+    fn tokens_until_statement_end(&mut self, offset: Option<usize>)
+                                  -> Result<(Vec<OperatorOrToken>, bool)> {
+        let mut tokens = vec![];
+        while let Some(op_or_token) = self.tokenizer.next() {
+            let op_or_token = match op_or_token {
+                Ok(t) => t,
+                Err(e) => {
+                    self.skip_statement();
+                    return Err(e)
+                },
+            };
+            match op_or_token {
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Semicolon, ..
+                }) => return Ok((tokens, false)),
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::LeftCurlyBrace, ..
+                }) => return Ok((tokens, true)),
+                other => tokens.push(other),
+            }
+        }
+        Err(SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::UnexpectedEof,
+            message: String::from("Expected to see `;` or `{`, instead reached EOF."),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    fn tokens_until_semicolon(&mut self, offset: Option<usize>) -> Result<Vec<OperatorOrToken>> {
+        let mut tokens = vec![];
+        while let Some(op_or_token) = self.tokenizer.next() {
+            let op_or_token = match op_or_token {
+                Ok(t) => t,
+                Err(e) => {
+                    self.skip_statement();
+                    return Err(e)
+                },
+            };
+            match op_or_token {
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Semicolon, ..
+                }) => return Ok(tokens),
+                other => tokens.push(other),
+            }
+        }
+        Err(SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::UnexpectedEof,
+            message: String::from("Expected to see `;`, instead reached EOF."),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    fn expect_url(actual: Option<OperatorOrToken>, offset: Option<usize>) -> Result<TokenOffset> {
+        match actual {
+            Some(OperatorOrToken::Token(t @ TokenOffset {
+                token: Token::StringLiteral(_), ..
+            })) => Ok(t),
+            other => Err(Parser::unexpected_error("a quoted URL", other, offset)),
+        }
+    }
+
+    // Parses `($name: value, ...)` as written after `with`.
+    fn parse_configuration(tokens: &mut Peekable<IntoIter<OperatorOrToken>>, offset: Option<usize>) -> Result<Vec<SassVariable>> {
+        match tokens.next() {
+            Some(OperatorOrToken::Operator(OperatorOffset {
+                operator: Operator::LeftParen, ..
+            })) => {},
+            other => return Err(Parser::unexpected_error("`(`", other, offset)),
+        }
+
+        let mut config = vec![];
+        loop {
+            let name = match tokens.next() {
+                Some(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::RightParen, ..
+                })) => return Ok(config),
+                Some(OperatorOrToken::Token(t @ TokenOffset {
+                    token: Token::String(_), ..
+                })) if t.token.to_string().starts_with("$") => t,
+                other => return Err(
+                    Parser::unexpected_error("a variable name", other, offset)
+                ),
+            };
+
+            match tokens.next() {
+                Some(OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Colon, ..
+                })) => {},
+                other => return Err(Parser::unexpected_error("`:`", other, offset)),
+            }
+
+            let mut value = vec![];
+            let mut paren_level = 0;
+            loop {
+                let op_or_token = match tokens.next() {
+                    Some(t) => t,
+                    None => return Err(Parser::unexpected_error("`)`", None, offset)),
+                };
+                match op_or_token.extract_operator() {
+                    Some(Operator::RightParen) if paren_level == 0 => {
+                        config.push(SassVariable {
+                            name,
+                            value: Expression::from_tokens(value),
+                        });
+                        return Ok(config)
+                    },
+                    Some(Operator::Comma) if paren_level == 0 => break,
+                    Some(Operator::LeftParen) => paren_level += 1,
+                    Some(Operator::RightParen) => paren_level -= 1,
+                    _ => {},
+                }
+                value.push(op_or_token);
+            }
+            config.push(SassVariable {
+                name,
+                value: Expression::from_tokens(value),
+            });
+        }
+    }
+
+    fn unexpected_error(expected: &str, actual: Option<OperatorOrToken>, offset: Option<usize>) -> SassError {
+        match actual {
+            Some(op_or_token) => SassError {
+                offset: op_or_token.offset().or(offset).unwrap_or(0),
+                kind: ErrorKind::ParserError,
+                message: format!(
+                    "Expected to see {}, instead saw `{}`.",
+                    expected,
+                    op_or_token,
+                ),
+                location: None,
+                calls: vec![],
+            },
+            None => SassError {
+                offset: offset.unwrap_or(0),
+                kind: ErrorKind::ParserError,
+                message: format!(
+                    "Expected to see {}, instead reached the end of the rule.",
+                    expected,
+                ),
+                location: None,
+                calls: vec![],
+            },
+        }
+    }
+
     pub fn parse_body(&mut self) -> Result<Vec<Node>> {
         let mut rule_stack = vec![];
         let mut body = vec![];
         let mut ambiguous_holding_pen = vec![];
 
-        while let Some(Ok(op_or_token)) = self.tokenizer.next() {
+        while let Some(op_or_token) = self.tokenizer.next() {
+            let op_or_token = match op_or_token {
+                Ok(t) => t,
+                Err(e) => {
+                    self.recover(e)?;
+                    self.skip_statement();
+                    ambiguous_holding_pen = vec![];
+                    continue
+                },
+            };
             match op_or_token {
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::RightCurlyBrace, ..
@@ -179,6 +1191,114 @@ impl<'a> Parser<'a> {
                         }
                     }
                 },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@import" && ambiguous_holding_pen.is_empty() => {
+                    let child = match self.parse_import(off) {
+                        Ok(imports) => Node::Import(imports),
+                        Err(e) => {
+                            self.recover(e)?;
+                            continue
+                        },
+                    };
+                    if rule_stack.is_empty() {
+                        body.push(child);
+                    } else {
+                        // TODO: mut ref to last?
+                        let mut rule = rule_stack.pop().unwrap();
+                        rule.children.push(child);
+                        rule_stack.push(rule);
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if ambiguous_holding_pen.is_empty() && unsupported_keyframes(string_val, off).is_some() => {
+                    self.recover(unsupported_keyframes(string_val, off).unwrap())?;
+                    self.skip_statement();
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if (string_val == "@use" || string_val == "@forward") && ambiguous_holding_pen.is_empty() => {
+                    self.recover(SassError {
+                        offset: off.unwrap_or(0),
+                        kind: ErrorKind::ModuleError,
+                        message: format!("{} rules must be written at the top level.", string_val),
+                        location: None,
+                        calls: vec![],
+                    })?;
+                    self.skip_statement();
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@include" && ambiguous_holding_pen.is_empty() => {
+                    match self.parse_include(off) {
+                        Ok(include) => add_child(&mut body, &mut rule_stack, Node::Include(include)),
+                        Err(e) => self.recover(e)?,
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@at-root" && ambiguous_holding_pen.is_empty() => {
+                    match self.parse_at_root(off) {
+                        Ok(at_root) => add_child(&mut body, &mut rule_stack, Node::AtRoot(at_root)),
+                        Err(e) => self.recover(e)?,
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@supports" && ambiguous_holding_pen.is_empty() => {
+                    match self.parse_supports(off) {
+                        Ok(supports) => add_child(&mut body, &mut rule_stack, Node::Supports(supports)),
+                        Err(e) => self.recover(e)?,
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@media" && ambiguous_holding_pen.is_empty() => {
+                    match self.parse_media(off) {
+                        Ok(media) => add_child(&mut body, &mut rule_stack, Node::Media(media)),
+                        Err(e) => self.recover(e)?,
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if string_val == "@content" && ambiguous_holding_pen.is_empty() => {
+                    match self.parse_content(off) {
+                        Ok(content) => add_child(&mut body, &mut rule_stack, Node::Content(content)),
+                        Err(e) => self.recover(e)?,
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), ..
+                }) if string_val == "@return" && ambiguous_holding_pen.is_empty() => {
+                    match Expression::parse(&mut self.tokenizer) {
+                        Ok(value) => add_child(&mut body, &mut rule_stack, Node::Return(value)),
+                        Err(e) => {
+                            self.recover(e)?;
+                            self.skip_statement();
+                        },
+                    }
+                },
+                OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(ref string_val), offset: off,
+                }) if message_kind(string_val).is_some() && ambiguous_holding_pen.is_empty() => {
+                    let kind = message_kind(string_val).unwrap();
+                    let child = match self.parse_message(kind, off) {
+                        Ok(message) => Node::Message(message),
+                        Err(e) => {
+                            self.recover(e)?;
+                            continue
+                        },
+                    };
+                    if rule_stack.is_empty() {
+                        body.push(child);
+                    } else {
+                        // TODO: mut ref to last?
+                        let mut rule = rule_stack.pop().unwrap();
+                        rule.children.push(child);
+                        rule_stack.push(rule);
+                    }
+                },
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::LeftCurlyBrace, ..
                 }) => {
@@ -187,10 +1307,52 @@ impl<'a> Parser<'a> {
                     ambiguous_holding_pen = vec![];
                     rule_stack.push(rule);
                 },
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Colon, ..
+                }) if ambiguous_holding_pen.len() == 1 &&
+                      ambiguous_holding_pen[0].token.to_string().starts_with("--") => {
+                    // Custom properties are kept as written, apart from
+                    // any interpolation.
+                    let name = ambiguous_holding_pen.pop().unwrap();
+                    let value = Expression::Value(OperatorOrToken::Token(TokenOffset {
+                        token: Token::String(self.tokenizer.declaration_value()),
+                        offset: name.offset,
+                    }));
+                    add_child(&mut body, &mut rule_stack, Node::Property(name, value));
+                },
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Colon, ..
+                }) if !ambiguous_holding_pen.is_empty() &&
+                      !ambiguous_holding_pen[0].token.to_string().starts_with("$") &&
+                      self.tokenizer.opens_block() => {
+                    // A nested selector with a pseudo class, like `&:hover {`.
+                    let held = ambiguous_holding_pen.pop().unwrap();
+                    ambiguous_holding_pen.push(self.combine(held, &op_or_token));
+                },
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::Colon, ..
                 }) => {
-                    let value = try!(Expression::parse(&mut self.tokenizer));
+                    let value = match self.parse_declaration_value() {
+                        Ok(value) => value,
+                        Err(e) => {
+                            self.recover(e)?;
+                            self.skip_statement();
+                            ambiguous_holding_pen = vec![];
+                            continue
+                        },
+                    };
+                    if value == Expression::List(vec![]) {
+                        let declaration = ambiguous_holding_pen.first().and_then(|t| t.offset);
+                        self.recover(SassError {
+                            offset: declaration.or(op_or_token.offset()).unwrap_or(0),
+                            kind: ErrorKind::ParserError,
+                            message: String::from("Expected a value."),
+                            location: None,
+                            calls: vec![],
+                        })?;
+                        ambiguous_holding_pen = vec![];
+                        continue
+                    }
 
                     let child = match ambiguous_holding_pen.pop() {
                         Some(name_lexeme) => {
@@ -199,7 +1361,7 @@ impl<'a> Parser<'a> {
                                     Node::Variable(
                                         SassVariable {
                                             name: name_lexeme.clone(),
-                                            value: value,
+                                            value,
                                         }
                                     )
                                 },
@@ -207,25 +1369,31 @@ impl<'a> Parser<'a> {
                                     Node::Property(name_lexeme, value)
                                 },
                                 other => {
-                                    return Err(SassError {
+                                    self.recover(SassError {
                                         offset: name_lexeme.offset.unwrap_or(0),
                                         kind: ErrorKind::ParserError,
                                         message: format!(
-                                            "Expected to have seen a property or variable name, instead saw {:?}", other
+                                       "Expected to have seen a property or variable name, instead saw {:?}", other
                                         ),
-                                    })
+                                        location: None,
+                                        calls: vec![],
+                                    })?;
+                                    continue
                                 },
                             }
                         },
                         None => {
-                            return Err(SassError {
+                            self.recover(SassError {
                                 offset: op_or_token.offset().unwrap_or(0),
                                 kind: ErrorKind::ParserError,
                                 message: String::from(
-                                    "Expected to have seen a property or \
-                                     variable name, did not see any"
+                               "Expected to have seen a property or \
+                                variable name, did not see any"
                                 ),
-                            })
+                                location: None,
+                                calls: vec![],
+                            })?;
+                            continue
                         }
                     };
                     if rule_stack.is_empty() {
@@ -237,12 +1405,22 @@ impl<'a> Parser<'a> {
                         rule_stack.push(rule);
                     }
                 },
+                OperatorOrToken::Operator(OperatorOffset {
+                    operator: Operator::Semicolon, ..
+                }) if ambiguous_holding_pen.is_empty() => {},
                 OperatorOrToken::Operator(OperatorOffset {
                     operator: Operator::Comma, ..
                 }) => {
-                    // TODO: else return error
-                    if let Some(Ok(after_comma)) = self.tokenizer.next() {
-                        ambiguous_holding_pen.push(after_comma.into());
+                    match self.tokenizer.next() {
+                        Some(Ok(after_comma)) => {
+                            ambiguous_holding_pen.push(after_comma.into());
+                        },
+                        Some(Err(e)) => {
+                            self.recover(e)?;
+                            self.skip_statement();
+                            ambiguous_holding_pen = vec![];
+                        },
+                        None => {},
                     }
                 },
                 OperatorOrToken::Token(content @ TokenOffset {
@@ -252,14 +1430,14 @@ impl<'a> Parser<'a> {
                        ambiguous_holding_pen.is_empty() {
                         body.push(
                             Node::Comment(
-                                SassComment { content: content }
+                                SassComment { content }
                             )
                         );
                     } else if !rule_stack.is_empty() {
                         // TODO: mut ref to last?
                         let mut rule = rule_stack.pop().unwrap();
                         rule.children.push(Node::Comment(
-                            SassComment { content: content }
+                            SassComment { content }
                         ));
                         rule_stack.push(rule);
                     }
@@ -268,7 +1446,7 @@ impl<'a> Parser<'a> {
                     match ambiguous_holding_pen.pop() {
                         Some(held) => {
                             ambiguous_holding_pen.push(
-                                held.combine(&op_or_token)
+                                self.combine(held, &op_or_token)
                             );
                         },
                         None => {
@@ -279,22 +1457,147 @@ impl<'a> Parser<'a> {
             }
         }
 
+        // An error already in the body, like an unterminated string, is
+        // usually why it never ended.
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0))
+        }
         Err(SassError {
             offset: 0,
             kind: ErrorKind::UnexpectedEof,
             message: String::from(
                 "Expected to see rule body ending in `}`, instead reached EOF."
             ),
+            location: None,
+            calls: vec![],
         })
     }
 }
 
+fn add_child(body: &mut Vec<Node>, rule_stack: &mut [SassRule], child: Node) {
+    match rule_stack.last_mut() {
+        Some(rule) => rule.children.push(child),
+        None => body.push(child),
+    }
+}
+
+fn is_word(op_or_token: Option<&OperatorOrToken>, word: &str) -> bool {
+    match op_or_token {
+        Some(&OperatorOrToken::Token(TokenOffset {
+            token: Token::String(ref s), ..
+        })) => s == word,
+        _ => false,
+    }
+}
+
+// The `#` of `#{...}`.
+fn is_hash(op_or_token: Option<&OperatorOrToken>) -> bool {
+    is_word(op_or_token, "#")
+}
+
+fn is_interpolation_mark(op_or_token: &OperatorOrToken) -> bool {
+    is_hash(Some(op_or_token)) || matches!(
+        op_or_token.extract_operator(),
+        Some(Operator::LeftCurlyBrace) | Some(Operator::RightCurlyBrace)
+    )
+}
+
+fn message_kind(at_rule: &str) -> Option<MessageKind> {
+    match at_rule {
+        "@debug" => Some(MessageKind::Debug),
+        "@warn" => Some(MessageKind::Warn),
+        "@error" => Some(MessageKind::Error),
+        _ => None,
+    }
+}
+
+// `@keyframes` blocks hold rules that aren't nested in it the way style
+// rules are, which the output styles have no way to write out.
+fn unsupported_keyframes(at_rule: &str, offset: Option<usize>) -> Option<SassError> {
+    let name = at_rule.strip_prefix('@')?;
+    if name != "keyframes" && !(name.starts_with('-') && name.ends_with("-keyframes")) {
+        return None
+    }
+    Some(SassError {
+        offset: offset.unwrap_or(0),
+        kind: ErrorKind::ParserError,
+        message: format!("{} rules aren't supported.", at_rule),
+        location: None,
+        calls: vec![],
+    })
+}
+
+fn is_semicolon(op_or_token: &Option<Result<OperatorOrToken>>) -> bool {
+    matches!(*op_or_token, Some(Ok(OperatorOrToken::Operator(OperatorOffset {
+        operator: Operator::Semicolon, ..
+    }))))
+}
+
+// Writes tokens back out the way they'd appear in CSS, e.g. a media query
+// like `screen and (min-width: 100px)`.
+// As for `join_tokens`, with each `#{...}` written the way it was.
+fn join_interpolated(tokens: Vec<OperatorOrToken>) -> String {
+    let mut joined = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(t) = tokens.next() {
+        let opens = is_hash(Some(&t)) && tokens.peek().is_some_and(|next|
+            next.extract_operator() == Some(Operator::LeftCurlyBrace)
+        );
+        if !opens {
+            joined.push(t);
+            continue
+        }
+        tokens.next();
+        let mut inside = vec![];
+        let mut depth = 0;
+        for t in tokens.by_ref() {
+            match t.extract_operator() {
+                Some(Operator::LeftCurlyBrace) => depth += 1,
+                Some(Operator::RightCurlyBrace) if depth == 0 => break,
+                Some(Operator::RightCurlyBrace) => depth -= 1,
+                _ => {},
+            }
+            inside.push(t);
+        }
+        joined.push(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(format!("#{{{}}}", join_tokens(&inside))),
+            offset: t.offset(),
+        }));
+    }
+    join_tokens(&joined)
+}
+
+fn join_tokens(tokens: &[OperatorOrToken]) -> String {
+    let mut joined = String::new();
+    let mut previous: Option<Operator> = None;
+    for op_or_token in tokens.iter() {
+        let operator = op_or_token.extract_operator();
+        let tight = match (previous, operator) {
+            (None, _) if joined.is_empty() => true,
+            (Some(Operator::LeftParen), _) => true,
+            (_, Some(Operator::RightParen)) |
+            (_, Some(Operator::Colon)) |
+            (_, Some(Operator::Comma)) => true,
+            _ => false,
+        };
+        if !tight {
+            joined.push(' ');
+        }
+        joined.push_str(&op_or_token.to_string());
+        previous = operator;
+    }
+    joined
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use sass::rule::SassRule;
     use sass::comment::SassComment;
     use sass::variable::SassVariable;
+    use sass::use_rule::SassUse;
+    use sass::forward_rule::{SassForward, ForwardVisibility};
+    use sass::import_rule::SassImport;
     use ast::expression::Expression;
     use ast::root::Root;
     use ast::node::Node;
@@ -477,6 +1780,26 @@ mod tests {
         assert_eq!(parser.next(), None);
     }
 
+    #[test]
+    fn it_returns_imports_nested_in_rules() {
+        let mut parser = Parser::new(".theme { @import \"buttons\"; }");
+        assert_eq!(parser.next(), Some(Ok(Root::Rule(
+            SassRule {
+                selectors: vec![
+                    TokenOffset { token: Token::String(".theme".into()), offset: Some(0) },
+                ],
+                children: vec![Node::Import(vec![SassImport {
+                    url: TokenOffset {
+                        token: Token::StringLiteral("\"buttons\"".into()),
+                        offset: Some(17),
+                    },
+                    media: None,
+                }])],
+            }
+        ))));
+        assert_eq!(parser.next(), None);
+    }
+
     #[test]
     fn it_returns_a_comment() {
         let mut parser = Parser::new("/* hi */");
@@ -486,6 +1809,75 @@ mod tests {
         assert_eq!(parser.next(), None);
     }
 
+    #[test]
+    fn it_returns_use_rules() {
+        let mut parser = Parser::new("@use \"tokens\" as t with ($gap: 1px 2px);");
+        assert_eq!(parser.next(), Some(Ok(Root::Use(SassUse {
+            url: TokenOffset {
+                token: Token::StringLiteral("\"tokens\"".into()),
+                offset: Some(5),
+            },
+            namespace: Some("t".into()),
+            config: vec![SassVariable {
+                name: TokenOffset { token: Token::String("$gap".into()), offset: Some(25) },
+                value: Expression::List(vec![
+                    Expression::Value(OperatorOrToken::Token(TokenOffset {
+                        token: Token::Number { value: 1.0, units: Some("px".into()), computed: false },
+                        offset: Some(31),
+                    })),
+                    Expression::Value(OperatorOrToken::Token(TokenOffset {
+                        token: Token::Number { value: 2.0, units: Some("px".into()), computed: false },
+                        offset: Some(35),
+                    })),
+                ]),
+            }],
+        }))));
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_returns_forward_rules() {
+        let mut parser = Parser::new("@forward \"src/list\" as list-* hide list-reset, $gap;");
+        assert_eq!(parser.next(), Some(Ok(Root::Forward(SassForward {
+            url: TokenOffset {
+                token: Token::StringLiteral("\"src/list\"".into()),
+                offset: Some(9),
+            },
+            prefix: Some("list-".into()),
+            visibility: ForwardVisibility::Hide(vec!["list-reset".into(), "$gap".into()]),
+            config: vec![],
+        }))));
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_returns_import_rules_with_media_queries() {
+        let mut parser = Parser::new("@import \"a\", url(b.css) screen and (min-width: 10px);");
+        assert_eq!(parser.next(), Some(Ok(Root::Import(vec![
+            SassImport {
+                url: TokenOffset { token: Token::StringLiteral("\"a\"".into()), offset: Some(8) },
+                media: None,
+            },
+            SassImport {
+                url: TokenOffset { token: Token::String("url(b.css)".into()), offset: Some(13) },
+                media: Some("screen and (min-width: 10px)".into()),
+            },
+        ]))));
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_errors_with_unquoted_use_url() {
+        let mut parser = Parser::new("@use tokens;");
+        assert_eq!(parser.next(), Some(Err(SassError {
+            offset: 5,
+            kind: ErrorKind::ParserError,
+            message: String::from("Expected to see a quoted URL, instead saw `tokens`."),
+            location: None,
+            calls: vec![],
+        })));
+    }
+
     #[test]
     fn it_errors_with_malformed_variable_declaration() {
         let mut parser = Parser::new("$var no-colon;");
@@ -493,6 +1885,136 @@ mod tests {
             offset: 5,
             kind: ErrorKind::ParserError,
             message: String::from("Expected to see `:`, instead saw `no-colon`."),
+            location: None,
+            calls: vec![],
         })));
     }
+
+    #[test]
+    fn it_errors_at_a_declaration_without_a_value() {
+        let mut parser = Parser::new(".a {\n  b: ;\n}");
+        assert_eq!(parser.next(), Some(Err(SassError {
+            offset: 7,
+            kind: ErrorKind::ParserError,
+            message: String::from("Expected a value."),
+            location: None,
+            calls: vec![],
+        })));
+    }
+
+    #[test]
+    fn it_recovers_from_errors_in_a_rule_body() {
+        let mut parser = Parser::new("a { b: 1.2.3; : c; color: blue; }");
+        assert_eq!(
+            parser.next().map(|r| r.map_err(|e| e.offset)),
+            Some(Err(7))
+        );
+        assert_eq!(
+            parser.next().map(|r| r.map_err(|e| e.offset)),
+            Some(Err(14))
+        );
+        assert_eq!(parser.next(), Some(Ok(Root::Rule(
+            SassRule {
+                selectors: vec![TokenOffset { token: Token::String("a".into()), offset: Some(0) }],
+                children: vec![Node::Property(
+                    TokenOffset { token: Token::String("color".into()), offset: Some(19) },
+                    Expression::Value(OperatorOrToken::Token(
+                        TokenOffset {
+                            token: Token::String("blue".into()),
+                            offset: Some(26),
+                        }
+                    )),
+                )],
+            }
+        ))));
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_keeps_parsing_after_a_top_level_error() {
+        let mut parser = Parser::new("$var no-colon;\n$x 1.2.3 { a: b; }\n@use \"tokens\";");
+        assert_eq!(
+            parser.next().map(|r| r.map_err(|e| e.offset)),
+            Some(Err(5))
+        );
+        assert_eq!(
+            parser.next().map(|r| r.map_err(|e| e.offset)),
+            Some(Err(18))
+        );
+        match parser.next() {
+            Some(Ok(Root::Use(..))) => {},
+            other => panic!("Expected the @use rule, got {:?}", other),
+        }
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_parses_content_blocks_with_parameters() {
+        let mut parser = Parser::new("@include hover(1) using ($c) { color: $c; }");
+        let include = match parser.next() {
+            Some(Ok(Root::Include(include))) => include,
+            other => panic!("Expected an @include, got {:?}", other),
+        };
+        assert_eq!(include.arguments.positional.len(), 1);
+        let content = include.content.unwrap();
+        assert_eq!(content.parameters.parameters[0].name, "$c");
+        assert_eq!(content.children.len(), 1);
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_parses_at_root_queries() {
+        let mut parser = Parser::new("@at-root (without: media rule) { a { b: c; } }");
+        match parser.next() {
+            Some(Ok(Root::AtRoot(at_root))) => assert_eq!(
+                at_root.query,
+                AtRootQuery::Without(vec![String::from("media"), String::from("rule")])
+            ),
+            other => panic!("Expected @at-root, got {:?}", other),
+        }
+
+        let mut parser = Parser::new("@at-root (within: rule) { a { b: c; } }");
+        assert_eq!(
+            parser.next().map(|r| r.map_err(|e| e.message)),
+            Some(Err(String::from("Expected to see `with` or `without`, instead saw `within`.")))
+        );
+    }
+
+    #[test]
+    fn it_errors_on_keyframes() {
+        let mut parser = Parser::new(".a { @at-root { @keyframes fade { from { b: c; } } } }\nd { e: f; }");
+        assert_eq!(
+            parser.next().map(|r| r.map_err(|e| (e.offset, e.message))),
+            Some(Err((16, String::from("@keyframes rules aren't supported."))))
+        );
+        // `.a`, without the `@keyframes`, then `d`.
+        assert!(matches!(parser.next(), Some(Ok(Root::Rule(..)))));
+        assert!(matches!(parser.next(), Some(Ok(Root::Rule(..)))));
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn it_parses_supports_conditions() {
+        let condition = |text: &str| match Parser::new(text).next() {
+            Some(Ok(Root::Supports(supports))) => Ok(supports.condition.to_string()),
+            Some(Err(e)) => Err(e.message),
+            other => panic!("Expected @supports, got {:?}", other),
+        };
+        assert_eq!(
+            condition("@supports (a: b) and (not (c: d)) { }"),
+            Ok(String::from("(a: b) and (not (c: d))"))
+        );
+        assert_eq!(
+            condition("@supports not (#{$p}: $v) { }"),
+            Ok(String::from("not (#{$p}: $v)"))
+        );
+        assert_eq!(
+            condition("@supports selector(a > b) or #{$c} { }"),
+            Ok(String::from("selector(a > b) or #{$c}"))
+        );
+        assert_eq!(
+            condition("@supports (a: b) and (c: d) or (e: f) { }"),
+            Err(String::from("Use parentheses to mix `and` and `or`."))
+        );
+    }
 }
@@ -0,0 +1,333 @@
+use ast::expression::{Expression, split_at_depth};
+use context::Context;
+use error::{Result, SassError, ErrorKind};
+use expression_evaluator::ExpressionEvaluator;
+use operator::Operator;
+use operator_or_token::OperatorOrToken;
+use sass::variable::SassVariable;
+use token::Token;
+use token_offset::TokenOffset;
+
+// A parameter in `@mixin name($a, $b: default)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassParameter {
+    pub name: String,
+    pub default: Option<Expression>,
+}
+
+// The parameters a mixin or function is declared with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArgumentDeclaration {
+    pub parameters: Vec<SassParameter>,
+    // Takes whatever arguments are left over, as in `$args...`.
+    pub rest: Option<String>,
+}
+
+// The arguments written in an `@include` or a function call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArgumentInvocation {
+    pub positional: Vec<Expression>,
+    pub named: Vec<(String, Expression)>,
+    // A list, map or argument list spread out into more arguments, as in
+    // `$list...`.
+    pub rest: Option<Expression>,
+}
+
+// Evaluated arguments, with any rest argument spread out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArgumentList {
+    pub positional: Vec<Expression>,
+    pub named: Vec<(String, Expression)>,
+}
+
+impl ArgumentDeclaration {
+    pub fn new() -> ArgumentDeclaration {
+        ArgumentDeclaration {
+            parameters: vec![],
+            rest: None,
+        }
+    }
+
+    // `parts` is everything between the parentheses.
+    pub fn parse(parts: Vec<Expression>, offset: Option<usize>) -> Result<ArgumentDeclaration> {
+        let mut declaration = ArgumentDeclaration::new();
+        for segment in arguments(parts).into_iter() {
+            if declaration.rest.is_some() {
+                return Err(argument_error("Only the last parameter can take `...`.", offset))
+            }
+            let name = match segment.first().map(|e| e.to_string()) {
+                Some(ref name) if name.starts_with("$") => name.clone(),
+                other => return Err(SassError {
+                    offset: offset.unwrap_or(0),
+                    kind: ErrorKind::ParserError,
+                    message: format!(
+                        "Expected a parameter name like `$name`, instead saw `{}`.",
+                        other.unwrap_or_default(),
+                    ),
+                    location: None,
+                    calls: vec![],
+                }),
+            };
+
+            if name.ends_with("...") && segment.len() == 1 {
+                declaration.rest = Some(String::from(name.trim_end_matches("...")));
+            } else if segment.len() == 1 {
+                declaration.parameters.push(SassParameter { name, default: None });
+            } else if segment[1].is_operator_kind(Operator::Colon) {
+                declaration.parameters.push(SassParameter {
+                    name,
+                    default: Some(Expression::from_parts(segment[2..].to_vec())),
+                });
+            } else {
+                return Err(argument_error(
+                    &format!("Expected `:` or `,` after parameter `{}`.", name), offset
+                ))
+            }
+        }
+        Ok(declaration)
+    }
+
+    // Sets each parameter as a variable in `context`, from `args` or its
+    // default value. Defaults can refer to the parameters before them.
+    pub fn bind(&self, args: ArgumentList, context: &mut Context, offset: Option<usize>) -> Result<()> {
+        let ArgumentList { positional, mut named } = args;
+        if positional.len() > self.parameters.len() && self.rest.is_none() {
+            return Err(argument_error(&format!(
+                "Only {} argument{} allowed, but {} {} passed.",
+                self.parameters.len(),
+                if self.parameters.len() == 1 { "" } else { "s" },
+                positional.len(),
+                if positional.len() == 1 { "was" } else { "were" },
+            ), offset))
+        }
+
+        let mut positional = positional.into_iter();
+        for parameter in self.parameters.iter() {
+            let by_name = named.iter()
+                               .position(|(n, _)| *n == parameter.name)
+                               .map(|i| named.remove(i).1);
+            let value = match (positional.next(), by_name) {
+                (Some(_), Some(_)) => return Err(argument_error(&format!(
+                    "Argument {} was passed both by position and by name.", parameter.name
+                ), offset)),
+                (Some(value), None) | (None, Some(value)) => value,
+                (None, None) => match parameter.default {
+                    Some(ref default) => ExpressionEvaluator::evaluate(default.clone(), context)?,
+                    None => return Err(argument_error(
+                        &format!("Missing argument {}.", parameter.name), offset
+                    )),
+                },
+            };
+            context.add_variable(variable(&parameter.name, value, offset));
+        }
+
+        match self.rest {
+            Some(ref rest) => {
+                let list = Expression::comma_list(positional.collect(), offset);
+                context.add_variable(variable(rest, list, offset));
+                context.set_keywords(rest, named);
+            },
+            None if !named.is_empty() => {
+                let names = named.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+                return Err(argument_error(&format!(
+                    "No argument{} named {}.",
+                    if names.len() == 1 { "" } else { "s" },
+                    names.join(" or "),
+                ), offset))
+            },
+            None => {},
+        }
+        Ok(())
+    }
+}
+
+impl ArgumentInvocation {
+    pub fn new() -> ArgumentInvocation {
+        ArgumentInvocation {
+            positional: vec![],
+            named: vec![],
+            rest: None,
+        }
+    }
+
+    // `parts` is everything between the parentheses.
+    pub fn parse(parts: Vec<Expression>, offset: Option<usize>) -> Result<ArgumentInvocation> {
+        let mut invocation = ArgumentInvocation::new();
+        for mut segment in arguments(parts).into_iter() {
+            if invocation.rest.is_some() {
+                return Err(argument_error("Only the last argument can take `...`.", offset))
+            }
+
+            let is_named = segment.len() > 2 &&
+                           segment[1].is_operator_kind(Operator::Colon) &&
+                           segment[0].to_string().starts_with("$");
+            if is_named {
+                let name = segment[0].to_string();
+                invocation.named.push((name, Expression::from_parts(segment.split_off(2))));
+            } else if segment.last().is_some_and(|e| e.to_string().ends_with("...")) {
+                // `$list...` is one token, but `f(x)...` ends in a separate `...`.
+                let last = segment.pop().unwrap();
+                if last.to_string() != "..." {
+                    segment.push(without_ellipsis(last));
+                }
+                invocation.rest = Some(Expression::from_parts(segment));
+            } else if invocation.named.is_empty() {
+                invocation.positional.push(Expression::from_parts(segment));
+            } else {
+                return Err(argument_error(
+                    "Positional arguments must come before keyword arguments.", offset
+                ))
+            }
+        }
+        Ok(invocation)
+    }
+
+    pub fn evaluate(self, context: &Context) -> Result<ArgumentList> {
+        let mut positional = vec![];
+        for argument in self.positional.into_iter() {
+            positional.push(ExpressionEvaluator::evaluate(argument, context)?);
+        }
+        let mut named = vec![];
+        for (name, argument) in self.named.into_iter() {
+            named.push((name, ExpressionEvaluator::evaluate(argument, context)?));
+        }
+
+        if let Some(rest) = self.rest {
+            if let Some(keywords) = context.keywords(&rest.to_string()) {
+                named.extend(keywords);
+            }
+            let rest = ExpressionEvaluator::evaluate(rest, context)?;
+            match rest.map_entries() {
+                Some(entries) => named.extend(
+                    entries.into_iter().map(|(key, value)| (format!("${}", key), value))
+                ),
+                None => positional.extend(rest.list_elements()),
+            }
+        }
+
+        Ok(ArgumentList {
+            positional,
+            named,
+        })
+    }
+}
+
+// Each comma-separated argument, leaving out a trailing comma.
+fn arguments(parts: Vec<Expression>) -> Vec<Vec<Expression>> {
+    if parts.is_empty() {
+        return vec![]
+    }
+    let mut arguments = split_at_depth(&parts, Operator::Comma);
+    if arguments.last().is_some_and(|a| a.is_empty()) {
+        arguments.pop();
+    }
+    arguments
+}
+
+fn without_ellipsis(argument: Expression) -> Expression {
+    match argument {
+        Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(s), offset
+        })) => Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(String::from(s.trim_end_matches("..."))),
+            offset,
+        })),
+        other => other,
+    }
+}
+
+fn variable(name: &str, value: Expression, offset: Option<usize>) -> SassVariable {
+    SassVariable {
+        name: TokenOffset {
+            token: Token::String(String::from(name)),
+            offset,
+        },
+        value,
+    }
+}
+
+fn argument_error(message: &str, offset: Option<usize>) -> SassError {
+    SassError {
+        offset: offset.unwrap_or(0),
+        kind: ErrorKind::ArgumentError,
+        message: String::from(message),
+        location: None,
+        calls: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::expression::Expression;
+    use context::Context;
+    use error::ErrorKind;
+    use tokenizer::Tokenizer;
+
+    fn parts(text: &str) -> Vec<Expression> {
+        Tokenizer::new(text).map(|t| Expression::Value(t.unwrap())).collect()
+    }
+
+    fn bind(declaration: &str, invocation: &str) -> Result<Context> {
+        let declaration = ArgumentDeclaration::parse(parts(declaration), None)?;
+        let invocation = ArgumentInvocation::parse(parts(invocation), None)?;
+        let mut context = Context::new();
+        let args = invocation.evaluate(&context)?;
+        declaration.bind(args, &mut context, None)?;
+        Ok(context)
+    }
+
+    fn value(context: &Context, name: &str) -> String {
+        context.variables[name].value.to_string()
+    }
+
+    #[test]
+    fn it_binds_positional_keyword_and_default_arguments() {
+        let context = bind("$a, $b: $a * 2, $c: 3", "1, $c: 4").unwrap();
+        assert_eq!(value(&context, "$a"), "1");
+        assert_eq!(value(&context, "$b"), "2");
+        assert_eq!(value(&context, "$c"), "4");
+    }
+
+    #[test]
+    fn it_collects_and_spreads_rest_arguments() {
+        let context = bind("$a, $rest...", "1, 2, 3, $x: 4").unwrap();
+        assert_eq!(value(&context, "$rest"), "2, 3");
+        let keywords = context.keywords("$rest").unwrap();
+        assert_eq!(keywords[0].0, "$x");
+        assert_eq!(keywords[0].1.to_string(), "4");
+
+        let context = bind("$a, $b", "(a: 1, b: 2)...").unwrap();
+        assert_eq!(value(&context, "$b"), "2");
+        let context = bind("$a, $b, $c", "(1px 2px 3px)...").unwrap();
+        assert_eq!(value(&context, "$a"), "1px");
+        assert_eq!(value(&context, "$c"), "3px");
+        let error = bind("$a, $b, $c", "1, $rest...").unwrap_err();
+        assert_eq!(error.message, "Undefined variable `$rest`.");
+    }
+
+    #[test]
+    fn it_errors_on_missing_unknown_and_extra_arguments() {
+        let message = |d, i| bind(d, i).map(|_| ()).map_err(|e| (e.kind, e.message));
+        assert_eq!(
+            message("$a, $b", "1"),
+            Err((ErrorKind::ArgumentError, String::from("Missing argument $b.")))
+        );
+        assert_eq!(
+            message("$a", "1, $b: 2, $c: 3"),
+            Err((ErrorKind::ArgumentError, String::from("No arguments named $b or $c.")))
+        );
+        assert_eq!(
+            message("$a", "1, 2"),
+            Err((ErrorKind::ArgumentError, String::from("Only 1 argument allowed, but 2 were passed.")))
+        );
+        assert_eq!(
+            message("$a", "1, $a: 2"),
+            Err((ErrorKind::ArgumentError, String::from("Argument $a was passed both by position and by name.")))
+        );
+        assert_eq!(
+            message("$a", "$a: 1, 2").map_err(|e| e.0),
+            Err(ErrorKind::ArgumentError)
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use ast::node::Node;
+use context::Context;
+use error::Result;
+use sass::rule::evaluate_children;
+
+// `@at-root (query) { ... }`, or `@at-root selector { ... }`, which the
+// parser reads as a block holding that one rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassAtRoot {
+    pub query: AtRootQuery,
+    pub children: Vec<Node>,
+}
+
+// `(with: rule)` or `(without: media rule)`. Without a query, `@at-root`
+// leaves the style rules it's in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AtRootQuery {
+    With(Vec<String>),
+    Without(Vec<String>),
+}
+
+impl AtRootQuery {
+    pub fn new() -> AtRootQuery {
+        AtRootQuery::Without(vec![String::from("rule")])
+    }
+
+    // Whether the rules named `name`, like `rule` or `media`, are left
+    // behind.
+    pub fn excludes(&self, name: &str) -> bool {
+        match *self {
+            AtRootQuery::With(ref names) => !names.iter().any(|n| n == name || n == "all"),
+            AtRootQuery::Without(ref names) => names.iter().any(|n| n == name || n == "all"),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == AtRootQuery::new()
+    }
+}
+
+impl SassAtRoot {
+    // Keeps the `@at-root` around its evaluated children only if they
+    // leave the style rules or media queries they're in, for the optimizer
+    // to pull out.
+    pub fn evaluate(self, context: &mut Context) -> Result<Vec<Node>> {
+        let children = evaluate_children(self.children, context)?;
+        if self.query.excludes("rule") || self.query.excludes("media") {
+            Ok(vec![Node::AtRoot(SassAtRoot {
+                query: self.query,
+                children,
+            })])
+        } else {
+            Ok(children)
+        }
+    }
+}
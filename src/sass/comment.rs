@@ -1,6 +1,7 @@
 use sass::output_style::{SassOutputStyle, Streamable};
 use error::Result;
 use token_offset::TokenOffset;
+use source_map::MappedOutput;
 
 use std::io::Write;
 
@@ -10,11 +11,12 @@ pub struct SassComment {
 }
 
 impl Streamable for SassComment {
-    fn stream(&self, output: &mut Write, style: &SassOutputStyle)
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle)
                         -> Result<()> {
         let comment = self.content.token.to_string();
         // TODO: Shouldn't write! call into here, and not need the call to try?
-        try!(write!(output, "{}", style.comment(&comment)));
+        output.mark(self.content.offset);
+        write!(output, "{}", style.comment(&comment))?;
         Ok(())
     }
 }
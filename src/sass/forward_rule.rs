@@ -0,0 +1,77 @@
+use sass::variable::SassVariable;
+use token_offset::TokenOffset;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ForwardVisibility {
+    All,
+    Show(Vec<String>),
+    Hide(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassForward {
+    pub url: TokenOffset,
+    pub prefix: Option<String>,
+    pub visibility: ForwardVisibility,
+    pub config: Vec<SassVariable>,
+}
+
+impl SassForward {
+    // Member names are given without the prefix in `show`/`hide`, and
+    // variables keep their `$`, so `$radius` forwarded `as corner-*` becomes
+    // `$corner-radius`.
+    pub fn forwards(&self, member: &str) -> bool {
+        match self.visibility {
+            ForwardVisibility::All => true,
+            ForwardVisibility::Show(ref names) => names.iter().any(|n| n == member),
+            ForwardVisibility::Hide(ref names) => !names.iter().any(|n| n == member),
+        }
+    }
+
+    pub fn forwarded_name(&self, member: &str) -> String {
+        match self.prefix {
+            Some(ref prefix) if member.starts_with("$") => {
+                format!("${}{}", prefix, &member[1..])
+            },
+            Some(ref prefix) => format!("{}{}", prefix, member),
+            None => String::from(member),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::Token;
+    use token_offset::TokenOffset;
+
+    fn forward(prefix: Option<&str>, visibility: ForwardVisibility) -> SassForward {
+        SassForward {
+            url: TokenOffset {
+                token: Token::StringLiteral("\"src/list\"".into()),
+                offset: None,
+            },
+            prefix: prefix.map(String::from),
+            visibility,
+            config: vec![],
+        }
+    }
+
+    #[test]
+    fn it_filters_members_with_show_and_hide() {
+        let shown = forward(None, ForwardVisibility::Show(vec!["$gap".into()]));
+        assert!(shown.forwards("$gap"));
+        assert!(!shown.forwards("$width"));
+
+        let hidden = forward(None, ForwardVisibility::Hide(vec!["list-reset".into()]));
+        assert!(!hidden.forwards("list-reset"));
+        assert!(hidden.forwards("$gap"));
+    }
+
+    #[test]
+    fn it_prefixes_forwarded_names() {
+        let prefixed = forward(Some("list-"), ForwardVisibility::All);
+        assert_eq!(prefixed.forwarded_name("$gap"), "$list-gap");
+        assert_eq!(prefixed.forwarded_name("reset"), "list-reset");
+    }
+}
@@ -0,0 +1,33 @@
+use error::Result;
+
+use std::fmt;
+use std::rc::Rc;
+
+// A function defined by the host program rather than in Sass. It gets its
+// evaluated arguments as CSS text and returns the CSS text of its result,
+// which is parsed as a value so it can be used in further math.
+#[derive(Clone)]
+pub struct SassFunction {
+    callback: Rc<Callback>,
+}
+
+type Callback = dyn Fn(&[String]) -> Result<String>;
+
+impl SassFunction {
+    pub fn new<F>(callback: F) -> SassFunction
+        where F: Fn(&[String]) -> Result<String> + 'static {
+        SassFunction {
+            callback: Rc::new(callback),
+        }
+    }
+
+    pub fn call(&self, args: &[String]) -> Result<String> {
+        (self.callback)(args)
+    }
+}
+
+impl fmt::Debug for SassFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SassFunction")
+    }
+}
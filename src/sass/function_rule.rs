@@ -0,0 +1,70 @@
+use ast::expression::Expression;
+use ast::node::Node;
+use context::Context;
+use error::{Result, SassError, ErrorKind};
+use expression_evaluator::ExpressionEvaluator;
+use module::Module;
+use sass::arguments::{ArgumentDeclaration, ArgumentList};
+use token_offset::TokenOffset;
+
+use std::rc::Rc;
+
+// `@function name($parameters...) { ... @return value; }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassFunctionRule {
+    pub name: TokenOffset,
+    pub parameters: ArgumentDeclaration,
+    pub children: Vec<Node>,
+    // As for `SassMixin`.
+    pub scope: Option<Rc<Module>>,
+}
+
+impl SassFunctionRule {
+    pub fn name_string(&self) -> String {
+        self.name.token.to_string()
+    }
+
+    // Runs the body up to the first `@return`.
+    pub fn call(&self, args: ArgumentList, context: &Context, offset: Option<usize>)
+                -> Result<Expression> {
+        let mut local_context = context.clone();
+        if let Some(ref scope) = self.scope {
+            local_context.add_module(String::from("*"), (**scope).clone());
+        }
+        self.parameters.bind(args, &mut local_context, offset)?;
+
+        let returned = self.evaluate_body(&mut local_context)
+                           .map_err(|e| e.called_from(offset, &self.name_string()))?;
+        returned.ok_or_else(|| SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::FunctionError,
+            message: format!("Function {} finished without @return.", self.name_string()),
+            location: None,
+            calls: vec![],
+        })
+    }
+
+    // The value of the first `@return`, if the body reaches one.
+    fn evaluate_body(&self, context: &mut Context) -> Result<Option<Expression>> {
+        for child in self.children.iter().cloned() {
+            match child {
+                Node::Variable(sv) => sv.assign(context)?,
+                Node::Message(sm) => sm.evaluate(context)?,
+                Node::Comment(..) => {},
+                Node::Return(value) => {
+                    return ExpressionEvaluator::evaluate(value, context).map(Some)
+                },
+                _ => return Err(SassError {
+                    offset: self.name.offset.unwrap_or(0),
+                    kind: ErrorKind::ParserError,
+                    message: String::from(
+                        "Functions can only contain variable declarations, messages and @return."
+                    ),
+                    location: None,
+                    calls: vec![],
+                }),
+            }
+        }
+        Ok(None)
+    }
+}
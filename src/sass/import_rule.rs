@@ -0,0 +1,72 @@
+use sass::output_style::{SassOutputStyle, Streamable};
+use sass::use_rule::unquote_url;
+use token::Token;
+use token_offset::TokenOffset;
+use error::Result;
+use source_map::MappedOutput;
+
+use std::io::Write;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassImport {
+    pub url: TokenOffset,
+    pub media: Option<String>,
+}
+
+impl Streamable for SassImport {
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle)
+              -> Result<()> {
+        let import = match self.media {
+            Some(ref media) => format!("{} {}", self.url, media),
+            None => self.url.to_string(),
+        };
+        output.mark(self.url.offset);
+        write!(output, "{}", style.css_import(&import))?;
+        Ok(())
+    }
+}
+
+impl SassImport {
+    pub fn url_string(&self) -> String {
+        unquote_url(&self.url)
+    }
+
+    // Imports that stay in the output as CSS `@import`s instead of being
+    // loaded as Sass.
+    pub fn is_plain_css(&self) -> bool {
+        let url = self.url_string();
+        let quoted = matches!(self.url.token, Token::StringLiteral(_));
+
+        !quoted ||
+          self.media.is_some() ||
+          url.ends_with(".css") ||
+          url.starts_with("http://") ||
+          url.starts_with("https://") ||
+          url.starts_with("//")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::Token;
+    use token_offset::TokenOffset;
+
+    fn import(token: Token, media: Option<&str>) -> SassImport {
+        SassImport {
+            url: TokenOffset { token, offset: None },
+            media: media.map(String::from),
+        }
+    }
+
+    #[test]
+    fn it_knows_which_imports_are_plain_css() {
+        assert!(!import(Token::StringLiteral("\"foo\"".into()), None).is_plain_css());
+        assert!(!import(Token::StringLiteral("\"foo.scss\"".into()), None).is_plain_css());
+        assert!(import(Token::StringLiteral("\"foo.css\"".into()), None).is_plain_css());
+        assert!(import(Token::StringLiteral("\"http://x.com/a\"".into()), None).is_plain_css());
+        assert!(import(Token::StringLiteral("\"//x.com/a\"".into()), None).is_plain_css());
+        assert!(import(Token::String("url(foo)".into()), None).is_plain_css());
+        assert!(import(Token::StringLiteral("\"foo\"".into()), Some("screen")).is_plain_css());
+    }
+}
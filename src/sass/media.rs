@@ -0,0 +1,431 @@
+use ast::expression::Expression;
+use ast::node::Node;
+use context::Context;
+use error::Result;
+use expression_evaluator::ExpressionEvaluator;
+use parser::Parser;
+use sass::output_style::{SassOutputStyle, Streamable};
+use sass::rule::{evaluate_children, nest_children, optimize_children, stream_block_children};
+use source_map::MappedOutput;
+use token_offset::TokenOffset;
+use tokenizer::Tokenizer;
+
+use std::fmt;
+use std::io::Write;
+use std::mem;
+
+// `@media queries, ... { ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassMedia {
+    pub queries: Vec<MediaQuery>,
+    pub children: Vec<Node>,
+    pub offset: Option<usize>,
+}
+
+// `only screen and (min-width: 768px) and ...`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaQuery {
+    // `not` or `only`.
+    pub modifier: Option<String>,
+    pub media_type: Option<String>,
+    pub features: Vec<MediaFeature>,
+    // Queries written with interpolation, which can only be parsed once
+    // it's filled in. They're kept as written until then.
+    pub interpolated: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaFeature {
+    // `(name: value)`, with SassScript in the value.
+    Declaration(String, Expression),
+    // Anything else, like `(color)`, passed through as written.
+    Raw(String),
+}
+
+// What two queries that both have to match come to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaQueryMerge {
+    Query(MediaQuery),
+    // Nothing matches both.
+    Empty,
+    // Something matches both, but there's no way to write it as a query.
+    Unrepresentable,
+}
+
+impl fmt::Display for MediaFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MediaFeature::Declaration(ref name, ref value) => write!(f, "({}: {})", name, value),
+            MediaFeature::Raw(ref text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref text) = self.interpolated {
+            return write!(f, "{}", text)
+        }
+        let mut parts = vec![];
+        if let Some(ref modifier) = self.modifier {
+            parts.push(modifier.clone());
+        }
+        if let Some(ref media_type) = self.media_type {
+            parts.push(media_type.clone());
+        }
+        let mut query = parts.join(" ");
+        for feature in self.features.iter() {
+            if !query.is_empty() {
+                query.push_str(" and ");
+            }
+            query.push_str(&feature.to_string());
+        }
+        write!(f, "{}", query)
+    }
+}
+
+impl MediaQuery {
+    pub fn new() -> MediaQuery {
+        MediaQuery {
+            modifier: None,
+            media_type: None,
+            features: vec![],
+            interpolated: None,
+        }
+    }
+
+    pub fn evaluate(self, context: &Context) -> Result<MediaQuery> {
+        let mut features = vec![];
+        for feature in self.features.into_iter() {
+            features.push(match feature {
+                MediaFeature::Declaration(name, value) => MediaFeature::Declaration(
+                    name, ExpressionEvaluator::evaluate(value, context)?
+                ),
+                raw => raw,
+            });
+        }
+        Ok(MediaQuery {
+            modifier: self.modifier,
+            media_type: self.media_type,
+            features,
+            interpolated: None,
+        })
+    }
+
+    fn modifier_is(&self, modifier: &str) -> bool {
+        self.modifier.as_ref().is_some_and(|m| m.to_lowercase() == modifier)
+    }
+
+    fn lowercase_type(&self) -> Option<String> {
+        self.media_type.as_ref().map(|t| t.to_lowercase())
+    }
+
+    fn matches_all_types(&self) -> bool {
+        self.lowercase_type().is_none_or(|t| t == "all")
+    }
+
+    // The query matching what both this and `other` match, following
+    // dart-sass.
+    pub fn merge(&self, other: &MediaQuery) -> MediaQueryMerge {
+        let our_type = self.lowercase_type();
+        let their_type = other.lowercase_type();
+        let both_features = || {
+            self.features.iter().chain(other.features.iter()).cloned().collect::<Vec<_>>()
+        };
+
+        if our_type.is_none() && their_type.is_none() {
+            return MediaQueryMerge::Query(MediaQuery {
+                features: both_features(),
+                ..MediaQuery::new()
+            })
+        }
+
+        let (modifier, media_type, features) = if self.modifier_is("not") != other.modifier_is("not") {
+            let (negative, positive) = if self.modifier_is("not") {
+                (self, other)
+            } else {
+                (other, self)
+            };
+            if our_type == their_type {
+                // `not screen and (color)` has nothing in common with
+                // `screen and (color) and (grid)`.
+                return if contains_all(&positive.features, &negative.features) {
+                    MediaQueryMerge::Empty
+                } else {
+                    MediaQueryMerge::Unrepresentable
+                }
+            } else if self.matches_all_types() || other.matches_all_types() {
+                return MediaQueryMerge::Unrepresentable
+            }
+            (positive.modifier.clone(), positive.media_type.clone(), positive.features.clone())
+        } else if self.modifier_is("not") {
+            // There's no way to write "neither screen nor print".
+            if our_type != their_type {
+                return MediaQueryMerge::Unrepresentable
+            }
+            let (more, fewer) = if self.features.len() > other.features.len() {
+                (self, other)
+            } else {
+                (other, self)
+            };
+            // The query with more features is narrower, if it has all the
+            // other's features too.
+            if !contains_all(&more.features, &fewer.features) {
+                return MediaQueryMerge::Unrepresentable
+            }
+            (self.modifier.clone(), self.media_type.clone(), more.features.clone())
+        } else if self.matches_all_types() {
+            // Leave the type off if neither query had one.
+            let media_type = if other.matches_all_types() && self.media_type.is_none() {
+                None
+            } else {
+                other.media_type.clone()
+            };
+            (other.modifier.clone(), media_type, both_features())
+        } else if other.matches_all_types() {
+            (self.modifier.clone(), self.media_type.clone(), both_features())
+        } else if our_type != their_type {
+            return MediaQueryMerge::Empty
+        } else {
+            (self.modifier.clone().or(other.modifier.clone()), self.media_type.clone(), both_features())
+        };
+
+        MediaQueryMerge::Query(MediaQuery {
+            modifier,
+            media_type,
+            features,
+            interpolated: None,
+        })
+    }
+}
+
+fn contains_all(features: &[MediaFeature], others: &[MediaFeature]) -> bool {
+    let features = features.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+    others.iter().all(|o| features.contains(&o.to_string()))
+}
+
+// Each of `outer` merged with each of `inner`, leaving out the ones that
+// can't match anything, or nothing if any of them can't be written as a
+// query.
+pub fn merge_queries(outer: &[MediaQuery], inner: &[MediaQuery]) -> Option<Vec<MediaQuery>> {
+    let mut merged = vec![];
+    for o in outer.iter() {
+        for i in inner.iter() {
+            match o.merge(i) {
+                MediaQueryMerge::Query(query) => merged.push(query),
+                MediaQueryMerge::Empty => {},
+                MediaQueryMerge::Unrepresentable => return None,
+            }
+        }
+    }
+    Some(merged)
+}
+
+impl SassMedia {
+    pub fn evaluate(self, context: &Context) -> Result<SassMedia> {
+        let mut queries = vec![];
+        for query in self.queries.into_iter() {
+            match query.interpolated {
+                Some(ref text) => {
+                    let text = ExpressionEvaluator::interpolate(text, self.offset, context)?;
+                    let tokens = Tokenizer::starting_at(&text, self.offset.unwrap_or(0))
+                                     .collect::<Result<Vec<_>>>()?;
+                    queries.extend(Parser::media_queries(tokens, self.offset)?);
+                },
+                None => queries.push(query.evaluate(context)?),
+            }
+        }
+        let mut local_context = context.clone();
+        Ok(SassMedia {
+            queries,
+            children: evaluate_children(self.children, &mut local_context)?,
+            offset: self.offset,
+        })
+    }
+
+    // As for `SassSupports::nest_in`.
+    pub fn nest_in(self, selectors: &[TokenOffset]) -> SassMedia {
+        SassMedia {
+            queries: self.queries,
+            children: nest_children(self.children, selectors),
+            offset: self.offset,
+        }
+    }
+
+    // This block, split around the ones nested in it that can be merged
+    // into it, then the rules that leave it with `@at-root (without: media)`.
+    pub fn optimize(self) -> Vec<Node> {
+        let mut escaped = vec![];
+        let mut children = vec![];
+        for child in self.children.into_iter() {
+            match child {
+                Node::Rule(mut rule) => {
+                    let selectors = rule.selectors.clone();
+                    escaped.extend(rule.take_at_root_rules(&selectors, "media"));
+                    if !rule.children.is_empty() {
+                        children.push(Node::Rule(rule));
+                    }
+                },
+                Node::AtRoot(ref at_root) if at_root.query.excludes("media") => {
+                    escaped.extend(at_root.children.iter().cloned().filter_map(|c| match c {
+                        Node::Rule(rule) => Some(rule),
+                        _ => None,
+                    }));
+                },
+                other => children.push(other),
+            }
+        }
+
+        let mut results = vec![];
+        let mut kept = vec![];
+        for child in optimize_children(children).into_iter() {
+            match child {
+                Node::Media(inner) => match merge_queries(&self.queries, &inner.queries) {
+                    Some(ref queries) if queries.is_empty() => {},
+                    Some(queries) => {
+                        if !kept.is_empty() {
+                            results.push(Node::Media(SassMedia {
+                                queries: self.queries.clone(),
+                                children: mem::take(&mut kept),
+                                offset: self.offset,
+                            }));
+                        }
+                        results.push(Node::Media(SassMedia {
+                            queries,
+                            children: inner.children,
+                            offset: inner.offset,
+                        }));
+                    },
+                    None => kept.push(Node::Media(inner)),
+                },
+                other => kept.push(other),
+            }
+        }
+        if !kept.is_empty() {
+            results.push(Node::Media(SassMedia {
+                queries: self.queries,
+                children: kept,
+                offset: self.offset,
+            }));
+        }
+        results.extend(escaped.into_iter().flat_map(|r| r.optimize()).map(Node::Rule));
+        results
+    }
+
+    // Writes the block without anything after it.
+    pub fn stream_block(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle) -> Result<()> {
+        let queries = self.queries.iter().map(|q| q.to_string()).collect::<Vec<_>>();
+        output.mark(self.offset);
+        write!(output, "@media {}{}", queries.join(&style.selector_separator()), style.at_rule_open())?;
+        output.indent();
+        stream_block_children(&self.children, output, style)?;
+        output.dedent();
+        Ok(write!(output, "{}", style.at_rule_close())?)
+    }
+}
+
+impl Streamable for SassMedia {
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle) -> Result<()> {
+        if self.children.is_empty() {
+            return Ok(())
+        }
+        self.stream_block(output, style)?;
+        Ok(write!(output, "{}", style.rule_separator())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compile_string;
+
+    fn query(modifier: Option<&str>, media_type: Option<&str>, features: &[&str]) -> MediaQuery {
+        MediaQuery {
+            modifier: modifier.map(String::from),
+            media_type: media_type.map(String::from),
+            features: features.iter().map(|f| MediaFeature::Raw(String::from(*f))).collect(),
+            interpolated: None,
+        }
+    }
+
+    fn merged(a: MediaQuery, b: MediaQuery) -> String {
+        match a.merge(&b) {
+            MediaQueryMerge::Query(query) => query.to_string(),
+            other => format!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_merges_queries_like_dart_sass() {
+        assert_eq!(
+            merged(query(None, Some("screen"), &[]), query(None, None, &["(min-width: 1px)"])),
+            "screen and (min-width: 1px)"
+        );
+        assert_eq!(
+            merged(query(Some("only"), Some("screen"), &["(color)"]), query(None, Some("screen"), &["(grid)"])),
+            "only screen and (color) and (grid)"
+        );
+        assert_eq!(merged(query(None, Some("screen"), &[]), query(None, Some("print"), &[])), "Empty");
+        assert_eq!(
+            merged(query(Some("not"), Some("screen"), &[]), query(None, Some("print"), &["(color)"])),
+            "print and (color)"
+        );
+        assert_eq!(
+            merged(query(Some("not"), Some("screen"), &["(color)"]), query(None, Some("screen"), &["(color)"])),
+            "Empty"
+        );
+        assert_eq!(
+            merged(query(Some("not"), Some("screen"), &[]), query(Some("not"), Some("print"), &[])),
+            "Unrepresentable"
+        );
+    }
+
+    #[test]
+    fn it_evaluates_merges_and_bubbles_media_queries() {
+        let mut output = Vec::new();
+        compile_string(
+            "$md: 768px;\n\
+             @media screen { a { @media (min-width: $md + 1) { b: c; } } }\n\
+             @media print { @media screen { d { e: f; } } }",
+            &mut output, "expanded",
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "@media screen and (min-width: 769px) {\n  a {\n    b: c;\n  }\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_queries_once_their_interpolation_is_filled_in() {
+        let mut output = Vec::new();
+        compile_string(
+            "$q: \"screen and (min-width: 1px)\";\n$w: 2px;\n\
+             .a { @media #{$q} { x: 1; } }\n\
+             @media print and (max-width: #{$w}), #{$q} { .b { y: 2; } }",
+            &mut output, "compact",
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "@media screen and (min-width: 1px) {\n  .a { x: 1; } }\n\n\
+             @media print and (max-width: 2px), screen and (min-width: 1px) {\n  .b { y: 2; } }\n\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_bubbled_media_queries_in_order() {
+        let mut output = Vec::new();
+        compile_string(
+            ".a { @media screen { x: 1; } .child { y: 2; } }
+             @media screen { .b { p: 1; @media (min-width: 1px) { q: 2; } r: 3; } }",
+            &mut output, "expanded",
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "@media screen {\n  .a {\n    x: 1;\n  }\n}\n\n\
+             .a .child {\n  y: 2;\n}\n\n\
+             @media screen {\n  .b {\n    p: 1;\n  }\n}\n\n\
+             @media screen and (min-width: 1px) {\n  .b {\n    q: 2;\n  }\n}\n\n\
+             @media screen {\n  .b {\n    r: 3;\n  }\n}\n\n"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use ast::expression::Expression;
+use context::Context;
+use error::{Result, SassError, ErrorKind};
+use expression_evaluator::ExpressionEvaluator;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageKind {
+    Debug,
+    Warn,
+    Error,
+}
+
+// `@debug`, `@warn` or `@error` followed by the value to report.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassMessage {
+    pub kind: MessageKind,
+    pub value: Expression,
+    pub offset: Option<usize>,
+}
+
+impl SassMessage {
+    // `@debug` and `@warn` messages are left in `context` for the module
+    // loader to pass on to the logger; `@error` stops the compilation.
+    pub fn evaluate(self, context: &Context) -> Result<()> {
+        let value = ExpressionEvaluator::evaluate(self.value, context)?;
+        let message = unquote(&value.to_string());
+        let offset = self.offset.unwrap_or(0);
+
+        match self.kind {
+            MessageKind::Error => Err(SassError {
+                offset,
+                kind: ErrorKind::UserError,
+                message,
+                location: None,
+                calls: vec![],
+            }),
+            kind => {
+                context.log(kind, message, offset);
+                Ok(())
+            },
+        }
+    }
+}
+
+pub fn unquote(message: &str) -> String {
+    if message.len() >= 2 && (
+        message.starts_with('"') && message.ends_with('"') ||
+        message.starts_with('\'') && message.ends_with('\'')
+    ) {
+        String::from(&message[1..message.len() - 1])
+    } else {
+        String::from(message)
+    }
+}
@@ -0,0 +1,102 @@
+use ast::node::Node;
+use context::Context;
+use error::{Result, SassError, ErrorKind};
+use module::Module;
+use sass::arguments::{ArgumentDeclaration, ArgumentInvocation, ArgumentList};
+use sass::rule::evaluate_children;
+use token_offset::TokenOffset;
+
+use std::rc::Rc;
+
+// `@mixin name($parameters...) { ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassMixin {
+    pub name: TokenOffset,
+    pub parameters: ArgumentDeclaration,
+    pub children: Vec<Node>,
+    // Everything in the module the mixin was defined in, once it's loaded
+    // by another one.
+    pub scope: Option<Rc<Module>>,
+}
+
+// `@include name(arguments...);`, or with a content block,
+// `@include name(arguments...) using ($parameters...) { ... }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassInclude {
+    pub name: TokenOffset,
+    pub arguments: ArgumentInvocation,
+    pub content: Option<SassContentBlock>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassContentBlock {
+    pub parameters: ArgumentDeclaration,
+    pub children: Vec<Node>,
+}
+
+// `@content;` or `@content(arguments...);` in a mixin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassContent {
+    pub arguments: ArgumentInvocation,
+    pub offset: Option<usize>,
+}
+
+// The content block passed to the mixin being included, along with the
+// context it was passed from, since that's where it gets evaluated.
+#[derive(Clone, Debug)]
+pub struct Content {
+    pub block: SassContentBlock,
+    pub context: Context,
+}
+
+impl SassMixin {
+    pub fn name_string(&self) -> String {
+        self.name.token.to_string()
+    }
+
+    pub fn include(&self, args: ArgumentList, content: Option<SassContentBlock>,
+                   context: &Context, offset: Option<usize>) -> Result<Vec<Node>> {
+        let mut local_context = context.clone();
+        if let Some(ref scope) = self.scope {
+            local_context.add_module(String::from("*"), (**scope).clone());
+        }
+        local_context.content = content.map(|block| Rc::new(Content {
+            block,
+            context: context.clone(),
+        }));
+        self.parameters.bind(args, &mut local_context, offset)?;
+        evaluate_children(self.children.clone(), &mut local_context)
+            .map_err(|e| e.called_from(offset, &self.name_string()))
+    }
+}
+
+impl SassInclude {
+    pub fn evaluate(self, context: &Context) -> Result<Vec<Node>> {
+        let name = self.name.token.to_string();
+        let offset = self.name.offset;
+        let mixin = context.get_mixin(&name).ok_or_else(|| SassError {
+            offset: offset.unwrap_or(0),
+            kind: ErrorKind::UndefinedMixin,
+            message: format!("Undefined mixin `{}`.", name),
+            location: None,
+            calls: vec![],
+        })?;
+        let args = self.arguments.evaluate(context)?;
+        mixin.include(args, self.content, context, offset)
+    }
+}
+
+impl SassContent {
+    // Evaluates the content block passed to the current mixin, if there
+    // is one, with `@content`'s arguments bound to its `using` parameters.
+    pub fn evaluate(self, context: &Context) -> Result<Vec<Node>> {
+        let content = match context.content {
+            Some(ref content) => content.clone(),
+            None => return Ok(vec![]),
+        };
+        let args = self.arguments.evaluate(context)?;
+        let mut block_context = content.context.clone();
+        content.block.parameters.bind(args, &mut block_context, self.offset)?;
+        evaluate_children(content.block.children.clone(), &mut block_context)
+    }
+}
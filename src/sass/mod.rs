@@ -1,4 +1,15 @@
+pub mod arguments;
+pub mod at_root;
 pub mod comment;
+pub mod forward_rule;
+pub mod function;
+pub mod function_rule;
+pub mod import_rule;
+pub mod media;
+pub mod message_rule;
+pub mod mixin;
 pub mod output_style;
 pub mod rule;
+pub mod supports;
+pub mod use_rule;
 pub mod variable;
@@ -1,11 +1,11 @@
 use ast::node::Node;
+use ast::expression::Expression;
 use sass::rule::SassRule;
 use error::Result;
-
-use std::io::Write;
+use source_map::MappedOutput;
 
 pub trait Streamable {
-    fn stream(&self, output: &mut Write, style: &SassOutputStyle)
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle)
              -> Result<()>;
 }
 
@@ -69,6 +69,11 @@ pub trait SassOutputStyle {
         format!("  {}: {};", name, value)
     }
 
+    // A property's value, with numbers rounded to `precision` digits.
+    fn value(&self, value: &Expression, precision: usize) -> String {
+        format!("{:.*}", precision, value)
+    }
+
     fn before_comment(&self) -> String {
         String::new()
     }
@@ -81,7 +86,23 @@ pub trait SassOutputStyle {
         String::from("\n")
     }
 
-    fn filter_child_properties<'a>(&self, children: &[Node])
+    fn css_import(&self, import: &str) -> String {
+        format!("@import {};\n", import)
+    }
+
+    fn at_rule_open(&self) -> String {
+        String::from(" {\n")
+    }
+
+    fn at_rule_child_separator(&self) -> String {
+        String::from("\n")
+    }
+
+    fn at_rule_close(&self) -> String {
+        String::from(" }")
+    }
+
+    fn filter_child_properties(&self, children: &[Node])
        -> Vec<Node> {
         children.iter().filter(|c|
             match **c {
@@ -89,6 +110,14 @@ pub trait SassOutputStyle {
                 Node::Comment(..)  => true,
                 Node::Property(..) => true,
                 Node::Variable(..) => true,
+                Node::Import(..)   => false,
+                Node::Message(..)  => false,
+                Node::Include(..)  => false,
+                Node::Content(..)  => false,
+                Node::AtRoot(..)   => false,
+                Node::Supports(..) => false,
+                Node::Media(..)    => false,
+                Node::Return(..)   => false,
             }
         ).cloned().collect()
     }
@@ -113,6 +142,10 @@ impl SassOutputStyle for Expanded {
     fn before_comment(&self) -> String {
         String::from("  ")
     }
+
+    fn at_rule_close(&self) -> String {
+        String::from("\n}")
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -181,10 +214,10 @@ impl SassOutputStyle for Compact {
     }
 
     fn comment(&self, content: &str) -> String {
-        String::from(content.lines()
-                            .map(|s| s.trim())
-                            .collect::<Vec<_>>()
-                            .join(" "))
+        content.lines()
+               .map(|s| s.trim())
+               .collect::<Vec<_>>()
+               .join(" ")
     }
 
 }
@@ -221,6 +254,10 @@ impl SassOutputStyle for Compressed {
         format!("{}:{}", name, value)
     }
 
+    fn value(&self, value: &Expression, precision: usize) -> String {
+        format!("{:#.*}", precision, value)
+    }
+
     fn comment(&self, _content: &str) -> String {
         String::new()
     }
@@ -229,6 +266,22 @@ impl SassOutputStyle for Compressed {
         String::new()
     }
 
+    fn css_import(&self, import: &str) -> String {
+        format!("@import {};", import)
+    }
+
+    fn at_rule_open(&self) -> String {
+        String::from("{")
+    }
+
+    fn at_rule_child_separator(&self) -> String {
+        String::new()
+    }
+
+    fn at_rule_close(&self) -> String {
+        String::from("}")
+    }
+
     fn selector_string(&self, rule: &SassRule, parents: &str) -> String {
         let separator: String = self.selector_separator();
         let s = if parents.is_empty() {
@@ -257,6 +310,14 @@ impl SassOutputStyle for Compressed {
                Node::Comment(..)  => false,
                Node::Property(..) => true,
                Node::Variable(..) => true,
+               Node::Import(..)   => false,
+               Node::Message(..)  => false,
+               Node::Include(..)  => false,
+               Node::Content(..)  => false,
+               Node::AtRoot(..)   => false,
+               Node::Supports(..) => false,
+               Node::Media(..)    => false,
+               Node::Return(..)   => false,
            }
         ).cloned().collect()
     }
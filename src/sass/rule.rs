@@ -1,13 +1,18 @@
 use sass::output_style::{SassOutputStyle, Streamable};
 use expression_evaluator::ExpressionEvaluator;
-use sass::variable::SassVariable;
 use ast::node::Node;
+use ast::expression::Expression;
+use operator_or_token::OperatorOrToken;
 use token::Token;
 use token_offset::TokenOffset;
-use error::Result;
+use error::{Result, SassError, ErrorKind};
 use context::Context;
+use sass::at_root::SassAtRoot;
+use source_map::MappedOutput;
 
 use std::io::Write;
+use std::mem;
+use std::slice;
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct SassRule {
@@ -16,10 +21,10 @@ pub struct SassRule {
 }
 
 impl Streamable for SassRule {
-    fn stream(&self, output: &mut Write, style: &SassOutputStyle)
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle)
               -> Result<()> {
-        try!(self.recursive_stream(output, style, "", ""));
-        Ok(try!(write!(output, "{}", style.rule_separator())))
+        self.recursive_stream(output, style, "", "")?;
+        Ok(write!(output, "{}", style.rule_separator())?)
     }
 }
 
@@ -31,7 +36,7 @@ impl SassRule {
         }
     }
 
-    pub fn recursive_stream(&self, output: &mut Write, style: &SassOutputStyle, parents: &str, nesting: &str) -> Result<()> {
+    pub fn recursive_stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle, parents: &str, nesting: &str) -> Result<()> {
 
         let selector_string = style.selector_string(self, parents);
 
@@ -39,27 +44,28 @@ impl SassRule {
         let has_properties = !properties.is_empty();
 
         if has_properties {
-            try!(write!(output, "{}{}{{{}{}",
+            output.mark(self.selectors.first().and_then(|s| s.offset));
+            write!(output, "{}{}{{{}{}",
               selector_string,
               style.selector_brace_separator(),
               style.brace_property_separator(),
               style.before_property(nesting),
-            ));
+            )?;
 
             let mut properties = properties.iter();
 
             // Must be at least one because it's not empty
             let first_prop = properties.next().unwrap();
-            try!(first_prop.stream(output, style));
+            first_prop.stream(output, style)?;
 
             for prop in properties {
-                try!(write!(output, "{}{}",
+                write!(output, "{}{}",
                     style.after_property(),
                     style.before_property(nesting),
-                ));
-                try!(prop.stream(output, style));
+                )?;
+                prop.stream(output, style)?;
             }
-            try!(write!(output, "{}}}", style.property_brace_separator()));
+            write!(output, "{}}}", style.property_brace_separator())?;
         }
 
         let mut child_rules = self.child_rules().into_iter();
@@ -67,14 +73,14 @@ impl SassRule {
             let mut recursive_nesting = String::from(nesting);
             if has_properties {
                 recursive_nesting.push_str("  ");
-                try!(write!(
+                write!(
                     output, "{}", style.rule_and_child_rules_separator(&recursive_nesting)
-                ));
+                )?;
             }
-            try!(cr.recursive_stream(output, style, &selector_string, &recursive_nesting));
+            cr.recursive_stream(output, style, &selector_string, &recursive_nesting)?;
             for cr in child_rules {
-                try!(write!(output, "{}", style.child_rule_separator(has_properties)));
-                try!(cr.recursive_stream(output, style, &selector_string, &recursive_nesting));
+                write!(output, "{}", style.child_rule_separator(has_properties))?;
+                cr.recursive_stream(output, style, &selector_string, &recursive_nesting)?;
             }
         }
 
@@ -90,6 +96,14 @@ impl SassRule {
                Node::Comment(..)  => true,
                Node::Property(..) => true,
                Node::Variable(..) => true,
+               Node::Import(..)   => false,
+               Node::Message(..)  => false,
+               Node::Include(..)  => false,
+               Node::Content(..)  => false,
+               Node::AtRoot(..)   => false,
+               Node::Supports(..) => false,
+               Node::Media(..)    => false,
+               Node::Return(..)   => false,
            }
        ).cloned().collect()
    }
@@ -101,6 +115,14 @@ impl SassRule {
                 Node::Comment(..)  => None,
                 Node::Property(..) => None,
                 Node::Variable(..) => None,
+                Node::Import(..)   => None,
+                Node::Message(..)  => None,
+                Node::Include(..)  => None,
+                Node::Content(..)  => None,
+                Node::AtRoot(..)   => None,
+                Node::Supports(..) => None,
+                Node::Media(..)    => None,
+                Node::Return(..)   => None,
             }
         ).collect::<Vec<_>>()
     }
@@ -109,62 +131,299 @@ impl SassRule {
         !self.child_properties().is_empty()
     }
 
-    pub fn optimize(self) -> Vec<SassRule> {
+    pub fn optimize(mut self) -> Vec<SassRule> {
+        let parents = self.selectors.clone();
+        let at_root = self.take_at_root_rules(&parents, "rule");
+
         let mut results = vec![];
         if self.has_properties() {
-            results.push(self.clone());
-            return results
+            results.push(self);
         } else {
-            self.child_rules().into_iter().flat_map(|cr|
+            results.extend(self.child_rules().into_iter().flat_map(|cr|
                 cr.collapse_with_parent_selectors(&self.selectors)
-            ).collect::<Vec<_>>()
+            ));
         }
+        results.extend(at_root.into_iter().flat_map(|r| r.optimize()));
+        results
     }
 
-    pub fn collapse_with_parent_selectors(self, parents: &Vec<TokenOffset>) -> Vec<SassRule> {
-        let new_selectors = parents.iter().flat_map(|p|
-            self.selectors.iter().map(|c|
-                TokenOffset {
-                    token: Token::String(format!("{} {}", p.token, c.token)),
-                    offset: p.offset,
-                }
-            ).collect::<Vec<_>>()
-        ).collect();
+    pub fn collapse_with_parent_selectors(self, parents: &[TokenOffset]) -> Vec<SassRule> {
         SassRule {
-            selectors: new_selectors,
+            selectors: nest_selectors(parents, &self.selectors),
             children: self.children,
         }.optimize()
     }
 
-    pub fn evaluate(self, context: &Context) -> SassRule {
-        let mut local_context = (*context).clone();
-        SassRule {
-            selectors: self.selectors,
-            children: self.children.into_iter().filter_map(|c|
-                match c {
-                    Node::Rule(sr) => Some(Node::Rule(sr.evaluate(&local_context))),
-                    Node::Property(lex, ex) => {
-                        Some(Node::Property(
-                            lex,
-                            ExpressionEvaluator::evaluate(ex, &local_context)
-                        ))
-                    },
-                    Node::Comment(sc) => Some(Node::Comment(sc)),
-                    Node::Variable(sv) => {
-                        let evaluated_var = ExpressionEvaluator::evaluate(
-                            sv.value,
-                            &local_context
-                        );
-                        local_context.add_variable(SassVariable {
-                            name: sv.name,
-                            value: evaluated_var,
-                        });
-                        None
+    // Splits this rule around the `@supports` and `@media` blocks in it
+    // and the rules nested in it, so each block comes out where it was
+    // written: the parts of the rule, keeping its own selectors, in between
+    // the blocks nested in the full `selectors`.
+    pub fn split_bubbled(self, selectors: &[TokenOffset]) -> Vec<Node> {
+        let mut split = vec![];
+        let mut current = SassRule { selectors: self.selectors.clone(), children: vec![] };
+        for child in self.children.into_iter() {
+            let bubbled = match child {
+                Node::Supports(supports) => vec![Node::Supports(supports.nest_in(selectors))],
+                Node::Media(media) => vec![Node::Media(media.nest_in(selectors))],
+                Node::Rule(rule) => {
+                    let nested = nest_selectors(selectors, &rule.selectors);
+                    rule.split_bubbled(&nested)
+                },
+                // Its rules come out at the top level, in between the parts
+                // of this rule like the blocks do.
+                Node::AtRoot(at_root) if at_root.query.excludes("rule") => {
+                    let mut left = vec![];
+                    for c in at_root.children.into_iter() {
+                        match c {
+                            Node::Rule(rule) => {
+                                let rule = rule.leaving_rules(selectors);
+                                let own = rule.selectors.clone();
+                                left.extend(rule.split_bubbled(&own));
+                            },
+                            other => current.children.push(other),
+                        }
+                    }
+                    if left.is_empty() {
+                        continue
+                    }
+                    vec![Node::AtRoot(SassAtRoot { query: at_root.query, children: left })]
+                },
+                other => {
+                    current.children.push(other);
+                    continue
+                },
+            };
+            for node in bubbled.into_iter() {
+                match node {
+                    Node::Rule(rule) => current.children.push(Node::Rule(rule)),
+                    block => {
+                        if !current.children.is_empty() {
+                            let rest = SassRule { selectors: self.selectors.clone(), children: vec![] };
+                            split.push(Node::Rule(mem::replace(&mut current, rest)));
+                        }
+                        split.push(block);
                     },
                 }
+            }
+        }
+        if !current.children.is_empty() {
+            split.push(Node::Rule(current));
+        }
+        split
+    }
+
+    // This rule taken out of the ones with `parents` as their selectors,
+    // which it only keeps where it uses `&`.
+    fn leaving_rules(self, parents: &[TokenOffset]) -> SassRule {
+        SassRule {
+            selectors: self.selectors.iter().flat_map(|s|
+                if s.token.to_string().contains("&") {
+                    nest_selectors(parents, slice::from_ref(s))
+                } else {
+                    vec![s.clone()]
+                }
             ).collect(),
+            children: self.children,
+        }
+    }
+
+    // Takes the rules inside `@at-root` out of this rule and the ones
+    // nested in it, where they leave `leaving`: `rule` for the top level,
+    // or `media` for outside the `@media` block this rule is in. Rules
+    // leaving rules only keep their parents where they use `&`.
+    pub fn take_at_root_rules(&mut self, parents: &[TokenOffset], leaving: &str) -> Vec<SassRule> {
+        let mut taken = vec![];
+        let mut children = vec![];
+        for child in self.children.drain(..) {
+            match child {
+                Node::AtRoot(ref at_root) if at_root.query.excludes(leaving) && at_root.query.excludes("rule") => {
+                    for c in at_root.children.iter().cloned() {
+                        match c {
+                            Node::Rule(rule) => taken.push(rule.leaving_rules(parents)),
+                            other => children.push(other),
+                        }
+                    }
+                },
+                Node::AtRoot(ref at_root) if at_root.query.excludes(leaving) => {
+                    taken.extend(nest_children(at_root.children.clone(), parents).into_iter().filter_map(|c|
+                        match c {
+                            Node::Rule(rule) => Some(rule),
+                            _ => None,
+                        }
+                    ));
+                },
+                // It only leaves `@media`, and there's none to leave here.
+                Node::AtRoot(at_root) if leaving == "rule" => children.extend(at_root.children),
+                Node::Rule(mut rule) => {
+                    let nested = nest_selectors(parents, &rule.selectors);
+                    taken.extend(rule.take_at_root_rules(&nested, leaving));
+                    // Don't leave an empty rule behind.
+                    if !rule.children.is_empty() {
+                        children.push(Node::Rule(rule));
+                    }
+                },
+                other => children.push(other),
+            }
         }
+        self.children = children;
+        taken
     }
+
+    pub fn evaluate(self, context: &Context) -> Result<SassRule> {
+        let mut local_context = (*context).clone();
+        Ok(SassRule {
+            selectors: self.selectors,
+            children: evaluate_children(self.children, &mut local_context)?,
+        })
+    }
+}
+
+// Each of `selectors` inside each of `parents`, in place of any `&`.
+pub fn nest_selectors(parents: &[TokenOffset], selectors: &[TokenOffset]) -> Vec<TokenOffset> {
+    parents.iter().flat_map(|p|
+        selectors.iter().map(move |c| {
+            let selector = c.token.to_string();
+            TokenOffset {
+                token: Token::String(if selector.contains("&") {
+                    selector.replace("&", &p.token.to_string())
+                } else {
+                    format!("{} {}", p.token, selector)
+                }),
+                offset: p.offset,
+            }
+        })
+    ).collect()
+}
+
+// The contents of an at-rule like `@media`, found in a rule with
+// `selectors`, once it's moved out of the rule: its properties go in a rule
+// of their own, and its rules get the full selectors they were nested in.
+pub fn nest_children(children: Vec<Node>, selectors: &[TokenOffset]) -> Vec<Node> {
+    let mut properties = vec![];
+    let mut nested = vec![];
+    for child in children.into_iter() {
+        match child {
+            Node::Rule(rule) => {
+                let nested_selectors = nest_selectors(selectors, &rule.selectors);
+                for node in rule.split_bubbled(&nested_selectors).into_iter() {
+                    match node {
+                        Node::Rule(mut part) => {
+                            part.selectors = nested_selectors.clone();
+                            nested.push(Node::Rule(part));
+                        },
+                        block => nested.push(block),
+                    }
+                }
+            },
+            Node::Supports(supports) => nested.push(Node::Supports(supports.nest_in(selectors))),
+            Node::Media(media) => nested.push(Node::Media(media.nest_in(selectors))),
+            other => properties.push(other),
+        }
+    }
+    if !properties.is_empty() {
+        nested.insert(0, Node::Rule(SassRule {
+            selectors: selectors.to_vec(),
+            children: properties,
+        }));
+    }
+    nested
+}
+
+// Optimizes the contents of an at-rule, leaving out any that would come
+// out empty.
+pub fn optimize_children(children: Vec<Node>) -> Vec<Node> {
+    let mut optimized = vec![];
+    for child in children.into_iter() {
+        match child {
+            Node::Rule(rule) => {
+                let selectors = rule.selectors.clone();
+                for node in rule.split_bubbled(&selectors).into_iter() {
+                    match node {
+                        Node::Rule(part) => optimized.extend(part.optimize().into_iter().map(Node::Rule)),
+                        block => optimized.extend(optimize_children(vec![block])),
+                    }
+                }
+            },
+            Node::AtRoot(at_root) => optimized.extend(optimize_children(at_root.children)),
+            Node::Supports(supports) => optimized.push(Node::Supports(supports.optimize())),
+            Node::Media(media) => optimized.extend(media.optimize()),
+            comment @ Node::Comment(..) => optimized.push(comment),
+            _ => {},
+        }
+    }
+    optimized.into_iter().filter(|c| match *c {
+        Node::Supports(ref supports) => !supports.children.is_empty(),
+        Node::Media(ref media) => !media.children.is_empty(),
+        _ => true,
+    }).collect()
+}
+
+pub fn stream_block_children(children: &[Node], output: &mut MappedOutput, style: &dyn SassOutputStyle)
+                             -> Result<()> {
+    for (i, child) in children.iter().enumerate() {
+        if i > 0 {
+            write!(output, "{}", style.at_rule_child_separator())?;
+        }
+        match *child {
+            Node::Rule(ref rule) => rule.recursive_stream(output, style, "", "")?,
+            Node::Supports(ref supports) => supports.stream_block(output, style)?,
+            Node::Media(ref media) => media.stream_block(output, style)?,
+            Node::Comment(ref comment) => comment.stream(output, style)?,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+// Evaluates the contents of a rule or mixin, setting any variables in
+// `context`.
+pub fn evaluate_children(children: Vec<Node>, context: &mut Context) -> Result<Vec<Node>> {
+    let mut evaluated = vec![];
+
+    for c in children.into_iter() {
+        match c {
+            Node::Rule(sr) => {
+                evaluated.push(Node::Rule(sr.evaluate(context)?));
+            },
+            Node::Property(ref lex, ref ex) if lex.token.to_string().starts_with("--") => {
+                let value = ExpressionEvaluator::interpolate(&ex.to_string(), ex.offset(), context)?;
+                evaluated.push(Node::Property(lex.clone(), Expression::Value(OperatorOrToken::Token(
+                    TokenOffset { token: Token::String(value), offset: ex.offset() }
+                ))));
+            },
+            Node::Property(lex, ex) => {
+                let value = ExpressionEvaluator::evaluate(ex, context)?;
+                if value == Expression::List(vec![]) {
+                    return Err(SassError {
+                        offset: lex.offset.unwrap_or(0),
+                        kind: ErrorKind::TypeError,
+                        message: String::from("() isn't a valid CSS value."),
+                        location: None,
+                        calls: vec![],
+                    })
+                }
+                evaluated.push(Node::Property(lex, value));
+            },
+            Node::Comment(sc) => evaluated.push(Node::Comment(sc)),
+            Node::Import(..) => {}, // loaded before evaluation
+            Node::Message(sm) => sm.evaluate(context)?,
+            Node::Variable(sv) => sv.assign(context)?,
+            Node::Include(include) => evaluated.extend(include.evaluate(context)?),
+            Node::Content(content) => evaluated.extend(content.evaluate(context)?),
+            Node::AtRoot(at_root) => evaluated.extend(at_root.evaluate(context)?),
+            Node::Supports(supports) => evaluated.push(Node::Supports(supports.evaluate(context)?)),
+            Node::Media(media) => evaluated.push(Node::Media(media.evaluate(context)?)),
+            Node::Return(value) => return Err(SassError {
+                offset: value.offset().unwrap_or(0),
+                kind: ErrorKind::ParserError,
+                message: String::from("@return may only be used within a function."),
+                location: None,
+                calls: vec![],
+            }),
+        }
+    }
+
+    Ok(evaluated)
 }
 
 #[cfg(test)]
@@ -175,6 +434,9 @@ mod tests {
     use token::Token;
     use token_offset::TokenOffset;
     use operator_or_token::OperatorOrToken;
+    use ast::root::Root;
+    use context::Context;
+    use parser::Parser;
 
     #[test]
     fn it_collapses_subrules_without_properties() {
@@ -268,4 +530,52 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn it_moves_at_root_rules_out_of_their_parents() {
+        let root = Parser::new(
+            ".a { color: red; .b { @at-root .c { d: e; } @at-root { &-f, .g { h: i; } } } }"
+        ).next().unwrap().unwrap();
+        let rule = match root {
+            Root::Rule(rule) => rule.evaluate(&Context::new()).unwrap(),
+            other => panic!("Expected a rule, got {:?}", other),
+        };
+        let selectors = rule.optimize().into_iter().map(|r|
+            r.selectors.iter().map(|s| s.token.to_string()).collect::<Vec<_>>()
+        ).collect::<Vec<_>>();
+        assert_eq!(selectors, vec![
+            vec![String::from(".a")],
+            vec![String::from(".c")],
+            vec![String::from(".a .b-f"), String::from(".g")],
+        ]);
+    }
+
+    #[test]
+    fn it_keeps_at_root_rules_where_they_were_written() {
+        let mut output = Vec::new();
+        ::compile_string(
+            ".b { &__el { x: 1; } @at-root &--mod { y: 2; } &__el2 { z: 3; } }",
+            &mut output, "compact"
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ".b__el { x: 1; }\n\n.b--mod { y: 2; }\n\n.b__el2 { z: 3; }\n\n"
+        );
+    }
+
+    #[test]
+    fn it_only_interpolates_custom_property_values() {
+        let root = Parser::new(
+            "a { --b: #{1 + 2}px, { c: $d } !important; e: 1 + 2; }"
+        ).next().unwrap().unwrap();
+        let rule = match root {
+            Root::Rule(rule) => rule.evaluate(&Context::new()).unwrap(),
+            other => panic!("Expected a rule, got {:?}", other),
+        };
+        let values = rule.children.iter().map(|c| match *c {
+            Node::Property(_, ref value) => value.to_string(),
+            ref other => panic!("Expected a property, got {:?}", other),
+        }).collect::<Vec<_>>();
+        assert_eq!(values, vec!["3px, { c: $d } !important", "3"]);
+    }
 }
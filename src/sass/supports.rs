@@ -0,0 +1,184 @@
+use ast::expression::Expression;
+use ast::node::Node;
+use context::Context;
+use error::Result;
+use expression_evaluator::ExpressionEvaluator;
+use sass::message_rule::unquote;
+use sass::output_style::{SassOutputStyle, Streamable};
+use sass::rule::{evaluate_children, nest_children, optimize_children, stream_block_children};
+use source_map::MappedOutput;
+use token_offset::TokenOffset;
+
+use std::fmt;
+use std::io::Write;
+
+// `@supports condition { ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassSupports {
+    pub condition: SupportsCondition,
+    pub children: Vec<Node>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SupportsCondition {
+    Not(Box<SupportsCondition>),
+    And(Vec<SupportsCondition>),
+    Or(Vec<SupportsCondition>),
+    Parenthesized(Box<SupportsCondition>),
+    // `(name: value)`, where the name is `Raw`, an `Interpolation` or a
+    // `Script`.
+    Declaration(Box<SupportsCondition>, Expression),
+    // `#{...}`
+    Interpolation(Expression),
+    // SassScript written without interpolation, like the `$prop` of
+    // `($prop: grid)`.
+    Script(Expression),
+    // Anything passed through as written, like `selector(a > b)`.
+    Raw(String),
+}
+
+impl fmt::Display for SupportsCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SupportsCondition::Not(ref condition) => write!(f, "not {}", condition),
+            SupportsCondition::And(ref conditions) => write!(f, "{}", join(conditions, " and ")),
+            SupportsCondition::Or(ref conditions) => write!(f, "{}", join(conditions, " or ")),
+            SupportsCondition::Parenthesized(ref condition) => write!(f, "({})", condition),
+            SupportsCondition::Declaration(ref name, ref value) => {
+                write!(f, "({}: {})", name, value)
+            },
+            SupportsCondition::Interpolation(ref value) => write!(f, "#{{{}}}", value),
+            SupportsCondition::Script(ref value) => write!(f, "{}", value),
+            SupportsCondition::Raw(ref text) => write!(f, "{}", text),
+        }
+    }
+}
+
+fn join(conditions: &[SupportsCondition], separator: &str) -> String {
+    conditions.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(separator)
+}
+
+impl SupportsCondition {
+    // Fills in the values of declarations and interpolations.
+    pub fn evaluate(self, context: &Context) -> Result<SupportsCondition> {
+        Ok(match self {
+            SupportsCondition::Not(condition) => {
+                SupportsCondition::Not(Box::new(condition.evaluate(context)?))
+            },
+            SupportsCondition::And(conditions) => {
+                SupportsCondition::And(evaluate_all(conditions, context)?)
+            },
+            SupportsCondition::Or(conditions) => {
+                SupportsCondition::Or(evaluate_all(conditions, context)?)
+            },
+            SupportsCondition::Parenthesized(condition) => {
+                SupportsCondition::Parenthesized(Box::new(condition.evaluate(context)?))
+            },
+            SupportsCondition::Declaration(name, value) => SupportsCondition::Declaration(
+                Box::new(name.evaluate(context)?),
+                ExpressionEvaluator::evaluate(value, context)?,
+            ),
+            SupportsCondition::Interpolation(value) | SupportsCondition::Script(value) => {
+                let value = ExpressionEvaluator::evaluate(value, context)?;
+                SupportsCondition::Raw(unquote(&value.to_string()))
+            },
+            raw @ SupportsCondition::Raw(..) => raw,
+        })
+    }
+}
+
+fn evaluate_all(conditions: Vec<SupportsCondition>, context: &Context) -> Result<Vec<SupportsCondition>> {
+    let mut evaluated = vec![];
+    for condition in conditions.into_iter() {
+        evaluated.push(condition.evaluate(context)?);
+    }
+    Ok(evaluated)
+}
+
+impl SassSupports {
+    pub fn evaluate(self, context: &Context) -> Result<SassSupports> {
+        let mut local_context = context.clone();
+        Ok(SassSupports {
+            condition: self.condition.evaluate(context)?,
+            children: evaluate_children(self.children, &mut local_context)?,
+            offset: self.offset,
+        })
+    }
+
+    // Moves this block, found in a rule with `selectors`, out of the rule.
+    pub fn nest_in(self, selectors: &[TokenOffset]) -> SassSupports {
+        SassSupports {
+            condition: self.condition,
+            children: nest_children(self.children, selectors),
+            offset: self.offset,
+        }
+    }
+
+    pub fn optimize(self) -> SassSupports {
+        SassSupports {
+            condition: self.condition,
+            children: optimize_children(self.children),
+            offset: self.offset,
+        }
+    }
+
+    // Writes the block without anything after it.
+    pub fn stream_block(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle) -> Result<()> {
+        output.mark(self.offset);
+        write!(output, "@supports {}{}", self.condition, style.at_rule_open())?;
+        output.indent();
+        stream_block_children(&self.children, output, style)?;
+        output.dedent();
+        Ok(write!(output, "{}", style.at_rule_close())?)
+    }
+}
+
+impl Streamable for SassSupports {
+    fn stream(&self, output: &mut MappedOutput, style: &dyn SassOutputStyle) -> Result<()> {
+        if self.children.is_empty() {
+            return Ok(())
+        }
+        self.stream_block(output, style)?;
+        Ok(write!(output, "{}", style.rule_separator())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compile_string;
+
+    fn compile(input: &str) -> String {
+        let mut output = Vec::new();
+        compile_string(input, &mut output, "expanded").unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn it_bubbles_out_of_rules_with_the_values_filled_in() {
+        assert_eq!(
+            compile("$p: display;\n$v: grid;\n\
+                     a { color: red; @supports (#{$p}: $v) { display: $v; b { c: d; } } }"),
+            "a {\n  color: red;\n}\n\n\
+             @supports (display: grid) {\n  a {\n    display: grid;\n  }\n  \
+               a b {\n    c: d;\n  }\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn it_evaluates_declaration_names() {
+        assert_eq!(
+            compile("$prop: display;\n$val: grid;\n@supports ($prop: $val) { a { b: c; } }"),
+            "@supports (display: grid) {\n  a {\n    b: c;\n  }\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn it_bubbles_out_where_it_was_written() {
+        assert_eq!(
+            compile(".a { @supports (display: grid) { x: 1; } .child { y: 2; } }"),
+            "@supports (display: grid) {\n  .a {\n    x: 1;\n  }\n}\n\n\
+             .a .child {\n  y: 2;\n}\n\n"
+        );
+    }
+}
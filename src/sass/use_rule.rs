@@ -0,0 +1,64 @@
+use sass::variable::SassVariable;
+use token_offset::TokenOffset;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SassUse {
+    pub url: TokenOffset,
+    pub namespace: Option<String>,
+    pub config: Vec<SassVariable>,
+}
+
+impl SassUse {
+    pub fn url_string(&self) -> String {
+        unquote_url(&self.url)
+    }
+
+    // `@use "src/corners"` is namespaced as `corners`, and `@use "sass:math"`
+    // as `math`, unless `as` says otherwise.
+    pub fn namespace_string(&self) -> String {
+        match self.namespace {
+            Some(ref ns) => ns.clone(),
+            None => {
+                let url = self.url_string();
+                let basename = url.rsplit(['/', ':']).next().unwrap_or("");
+                let basename = basename.split('.').next().unwrap_or("");
+                basename.trim_start_matches('_').to_string()
+            },
+        }
+    }
+}
+
+pub fn unquote_url(url: &TokenOffset) -> String {
+    url.token.to_string().trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::Token;
+    use token_offset::TokenOffset;
+
+    fn sass_use(url: &str, namespace: Option<&str>) -> SassUse {
+        SassUse {
+            url: TokenOffset {
+                token: Token::StringLiteral(url.into()),
+                offset: None,
+            },
+            namespace: namespace.map(String::from),
+            config: vec![],
+        }
+    }
+
+    #[test]
+    fn it_derives_the_namespace_from_the_url() {
+        assert_eq!(sass_use("\"tokens\"", None).namespace_string(), "tokens");
+        assert_eq!(sass_use("\"src/_corners.scss\"", None).namespace_string(), "corners");
+        assert_eq!(sass_use("\"sass:math\"", None).namespace_string(), "math");
+    }
+
+    #[test]
+    fn it_prefers_an_explicit_namespace() {
+        assert_eq!(sass_use("\"tokens\"", Some("t")).namespace_string(), "t");
+        assert_eq!(sass_use("\"tokens\"", Some("*")).namespace_string(), "*");
+    }
+}
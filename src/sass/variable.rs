@@ -1,5 +1,10 @@
 use ast::expression::Expression;
+use context::Context;
+use error::Result;
+use expression_evaluator::ExpressionEvaluator;
 use token_offset::TokenOffset;
+use operator_or_token::OperatorOrToken;
+use token::Token;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct SassVariable {
@@ -11,4 +16,62 @@ impl SassVariable {
     pub fn name_string(&self) -> String {
         self.name.token.to_string()
     }
-}
\ No newline at end of file
+
+    // Members starting with `-` or `_` aren't visible outside their module.
+    pub fn is_private(&self) -> bool {
+        self.name_string().starts_with("$-") || self.name_string().starts_with("$_")
+    }
+
+    pub fn is_default(&self) -> bool {
+        match self.value {
+            Expression::List(ref list) => {
+                list.last().map(is_default_flag).unwrap_or(false)
+            },
+            _ => false,
+        }
+    }
+
+    // Evaluates the value and sets the variable, unless it's `!default` and
+    // already set.
+    pub fn assign(self, context: &mut Context) -> Result<()> {
+        if self.is_default() && context.get_variable(&self.name).is_some() {
+            return Ok(())
+        }
+        let sv = self.without_default_flag();
+        let value = ExpressionEvaluator::evaluate(sv.value, context)?;
+        context.add_variable(SassVariable {
+            name: sv.name,
+            value,
+        });
+        Ok(())
+    }
+
+    pub fn without_default_flag(self) -> SassVariable {
+        let value = match self.value {
+            Expression::List(list) => {
+                let mut list = list.into_iter()
+                                   .filter(|e| !is_default_flag(e))
+                                   .collect::<Vec<_>>();
+                if list.len() == 1 {
+                    list.pop().unwrap()
+                } else {
+                    Expression::List(list)
+                }
+            },
+            other => other,
+        };
+        SassVariable {
+            name: self.name,
+            value,
+        }
+    }
+}
+
+fn is_default_flag(expression: &Expression) -> bool {
+    match *expression {
+        Expression::Value(OperatorOrToken::Token(TokenOffset {
+            token: Token::String(ref s), ..
+        })) => s == "!default",
+        _ => false,
+    }
+}
@@ -0,0 +1,148 @@
+use error::{Location, Frame};
+
+// Every stylesheet loaded during a compilation, laid end to end so that a
+// single offset says which file a token came from as well as where it is in
+// that file.
+pub struct SourceFiles {
+    files: Vec<SourceFile>,
+}
+
+struct SourceFile {
+    url: String,
+    text: String,
+    start: usize,
+    // The offset and name of the rule that loaded this file, or `None` for
+    // the stylesheet being compiled.
+    loaded_by: Option<(usize, &'static str)>,
+}
+
+impl SourceFiles {
+    pub fn new() -> SourceFiles {
+        SourceFiles {
+            files: Vec::new(),
+        }
+    }
+
+    // Returns the offset that `text` starts at.
+    pub fn add(&mut self, url: &str, text: &str, loaded_by: Option<(usize, &'static str)>) -> usize {
+        let start = self.files.last().map_or(0, |f| f.start + f.text.len() + 1);
+        self.files.push(SourceFile {
+            url: String::from(url),
+            text: String::from(text),
+            start,
+            loaded_by,
+        });
+        start
+    }
+
+    // `calls` are the offset and name of each call `offset` is inside,
+    // innermost first.
+    pub fn locate(&self, offset: usize, calls: &[(usize, String)]) -> Option<Location> {
+        let file = self.file_at(offset)?;
+        let (line, column) = file.line_and_column(offset);
+        let source_line = file.text.lines().nth(line - 1).unwrap_or("");
+
+        let mut stack = vec![];
+        let mut current_offset = offset;
+        for &(call_offset, ref name) in calls.iter() {
+            if let Some(f) = self.file_at(current_offset) {
+                let (line, column) = f.line_and_column(current_offset);
+                stack.push(Frame {
+                    url: f.url.clone(),
+                    line,
+                    column,
+                    name: name.clone(),
+                });
+            }
+            current_offset = call_offset;
+        }
+        let mut current = self.file_at(current_offset);
+        while let Some(f) = current {
+            let (line, column) = f.line_and_column(current_offset);
+            stack.push(Frame {
+                url: f.url.clone(),
+                line,
+                column,
+                name: String::from(f.loaded_by.map_or("root stylesheet", |(_, rule)| rule)),
+            });
+            current = f.loaded_by.and_then(|(o, _)| {
+                current_offset = o;
+                self.file_at(o)
+            });
+        }
+
+        Some(Location {
+            url: file.url.clone(),
+            line,
+            column,
+            length: highlight_length(source_line.chars().skip(column - 1)),
+            source_line: String::from(source_line),
+            stack,
+        })
+    }
+
+    // The URL of the file `offset` is in, and the line and column there,
+    // counting from 0.
+    pub fn position(&self, offset: usize) -> Option<(&str, usize, usize)> {
+        self.file_at(offset).map(|file| {
+            let (line, column) = file.line_and_column(offset);
+            (file.url.as_str(), line - 1, column - 1)
+        })
+    }
+
+    pub fn contents(&self, url: &str) -> Option<&str> {
+        self.files.iter().find(|f| f.url == url).map(|f| f.text.as_str())
+    }
+
+    fn file_at(&self, offset: usize) -> Option<&SourceFile> {
+        self.files.iter().rev().find(|f|
+            f.start <= offset && offset <= f.start + f.text.len()
+        )
+    }
+}
+
+impl SourceFile {
+    // Both start at 1; columns count characters, not bytes.
+    fn line_and_column(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..offset - self.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+// Underlines the rest of the word the error points at.
+fn highlight_length<I: Iterator<Item = char>>(rest: I) -> usize {
+    let length = rest.take_while(|c| !c.is_whitespace() && !";{},()".contains(*c))
+                     .count();
+    if length == 0 { 1 } else { length }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::{Location, Frame};
+
+    #[test]
+    fn it_locates_offsets_in_the_file_they_came_from() {
+        let mut sources = SourceFiles::new();
+        assert_eq!(sources.add("main.scss", "@import \"b\";\na { c: d; }", None), 0);
+        let start = sources.add("_b.scss", "b {\n  c: $nope;\n}", Some((8, "@import")));
+        assert_eq!(start, 25);
+
+        assert_eq!(
+            sources.locate(start + 9, &[]),
+            Some(Location {
+                url: String::from("_b.scss"),
+                line: 2,
+                column: 6,
+                length: 5,
+                source_line: String::from("  c: $nope;"),
+                stack: vec![
+                    Frame { url: String::from("_b.scss"), line: 2, column: 6, name: String::from("@import") },
+                    Frame { url: String::from("main.scss"), line: 1, column: 9, name: String::from("root stylesheet") },
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use std::io::{self, Write};
+use std::path::{Component, Path};
+
+// Passes output through while keeping track of the line and column it's up
+// to, so whatever is written next can be mapped back to the offset in the
+// source files it came from.
+pub struct MappedOutput<'a> {
+    output: &'a mut dyn Write,
+    line: usize,
+    column: usize,
+    // Generated line and column, both counted from 0, and source offset.
+    pub mappings: Vec<(usize, usize, usize)>,
+    // How many levels each line gets indented by, for the contents of
+    // at-rules.
+    indentation: usize,
+    // Digits after the decimal point in numbers.
+    pub precision: usize,
+}
+
+impl<'a> MappedOutput<'a> {
+    pub fn new(output: &'a mut dyn Write) -> MappedOutput<'a> {
+        MappedOutput {
+            output,
+            line: 0,
+            column: 0,
+            mappings: vec![],
+            indentation: 0,
+            precision: 10,
+        }
+    }
+
+    pub fn indent(&mut self) {
+        self.indentation += 1;
+    }
+
+    pub fn dedent(&mut self) {
+        self.indentation -= 1;
+    }
+
+    // The next thing written came from `offset`.
+    pub fn mark(&mut self, offset: Option<usize>) {
+        if let Some(offset) = offset {
+            // At the start of a line, it'll go after the indentation.
+            let column = if self.column == 0 { self.indent_string().len() } else { self.column };
+            self.mappings.push((self.line, column, offset));
+        }
+    }
+
+    // Writes `text` along with the `mappings` that were made while it was
+    // written to a `MappedOutput` of its own.
+    pub fn write_mapped(&mut self, text: &[u8], mappings: Vec<(usize, usize, usize)>) -> io::Result<()> {
+        for (line, column, offset) in mappings.into_iter() {
+            let column = if line == 0 { self.column + column } else { column };
+            self.mappings.push((self.line + line, column, offset));
+        }
+        self.write_all(text)
+    }
+
+    fn indent_string(&self) -> String {
+        "  ".repeat(self.indentation)
+    }
+
+    fn count(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter() {
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 0;
+            } else if byte & 0xC0 != 0x80 {
+                // Count characters rather than the bytes that continue them.
+                self.column += 1;
+            }
+        }
+    }
+}
+
+impl<'a> Write for MappedOutput<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.indentation > 0 {
+            // Indent each line that has something on it.
+            let mut lines = buf.split(|&b| b == b'\n').peekable();
+            while let Some(line) = lines.next() {
+                if self.column == 0 && !line.is_empty() {
+                    let indent = self.indent_string();
+                    self.output.write_all(indent.as_bytes())?;
+                    self.column = indent.len();
+                }
+                self.output.write_all(line)?;
+                self.count(line);
+                if lines.peek().is_some() {
+                    self.output.write_all(b"\n")?;
+                    self.count(b"\n");
+                }
+            }
+            return Ok(buf.len())
+        }
+        let written = self.output.write(buf)?;
+        self.count(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub generated_line: usize,
+    pub generated_column: usize,
+    // Index into `SourceMap::sources`.
+    pub source: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+// A version 3 source map. Lines and columns all count from 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceMap {
+    pub sources: Vec<String>,
+    // The text of each of `sources`, when it's to be included in the map.
+    pub sources_content: Option<Vec<String>>,
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn to_json(&self) -> String {
+        let sources = self.sources.iter()
+                                  .map(|s| json_string(s))
+                                  .collect::<Vec<_>>().join(",");
+        let mut json = format!(
+            "{{\"version\":3,\"sourceRoot\":\"\",\"sources\":[{}],\"names\":[],\"mappings\":{}",
+            sources, json_string(&self.encoded_mappings())
+        );
+        if let Some(ref contents) = self.sources_content {
+            let contents = contents.iter()
+                                   .map(|c| json_string(c))
+                                   .collect::<Vec<_>>().join(",");
+            json.push_str(&format!(",\"sourcesContent\":[{}]", contents));
+        }
+        json.push('}');
+        json
+    }
+
+    // Makes each source that's a filesystem path relative to `dir`, the
+    // directory the map is written to, or a `file:` URL when the map isn't
+    // written anywhere of its own.
+    pub fn relativize_sources(&mut self, dir: Option<&Path>) {
+        for source in self.sources.iter_mut() {
+            let path = Path::new(source.as_str());
+            if !path.is_absolute() {
+                continue
+            }
+            *source = match dir {
+                Some(dir) => relative_url(path, dir),
+                None => format!("file://{}", url_path(path.components())),
+            };
+        }
+    }
+
+    pub fn to_data_url(&self) -> String {
+        format!("data:application/json;charset=utf-8;base64,{}", base64(self.to_json().as_bytes()))
+    }
+
+    // Each generated line's segments, separated by `;`. Every field except
+    // the first is relative to the segment before, even on an earlier line.
+    fn encoded_mappings(&self) -> String {
+        let mut mappings = self.mappings.clone();
+        mappings.sort_by_key(|m| (m.generated_line, m.generated_column));
+
+        let mut encoded = String::new();
+        let mut line = 0;
+        let mut previous_column = 0;
+        let mut previous = (0, 0, 0);
+        for (i, m) in mappings.iter().enumerate() {
+            if m.generated_line > line {
+                for _ in line..m.generated_line {
+                    encoded.push(';');
+                }
+                line = m.generated_line;
+                previous_column = 0;
+            } else if i > 0 {
+                encoded.push(',');
+            }
+            vlq(m.generated_column as i64 - previous_column as i64, &mut encoded);
+            vlq(m.source as i64 - previous.0 as i64, &mut encoded);
+            vlq(m.line as i64 - previous.1 as i64, &mut encoded);
+            vlq(m.column as i64 - previous.2 as i64, &mut encoded);
+            previous_column = m.generated_column;
+            previous = (m.source, m.line, m.column);
+        }
+        encoded
+    }
+}
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn vlq(value: i64, encoded: &mut String) {
+    let mut rest = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+    loop {
+        let mut digit = rest & 0b11111;
+        rest >>= 5;
+        if rest > 0 {
+            digit |= 0b100000;
+        }
+        encoded.push(BASE64[digit as usize] as char);
+        if rest == 0 {
+            break;
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i)) & 0b111111] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// `path` as a URL relative to `dir`; both are absolute.
+fn relative_url(path: &Path, dir: &Path) -> String {
+    let path = path.components().filter(|c| *c != Component::CurDir).collect::<Vec<_>>();
+    let dir = dir.components().filter(|c| *c != Component::CurDir).collect::<Vec<_>>();
+    let common = path.iter().zip(dir.iter()).take_while(|&(a, b)| a == b).count();
+    let mut parts = vec![String::from(".."); dir.len() - common];
+    parts.push(url_path(path[common..].iter().cloned()));
+    parts.join("/")
+}
+
+fn url_path<'p, I: Iterator<Item = Component<'p>>>(components: I) -> String {
+    let parts = components.map(|c| match c {
+        Component::RootDir => String::new(),
+        other => other.as_os_str().to_string_lossy().into_owned(),
+    }).collect::<Vec<_>>();
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_vlqs() {
+        let mut encoded = String::new();
+        for &n in [0, 1, -1, 15, 16, -16, 1000].iter() {
+            vlq(n, &mut encoded);
+            encoded.push(' ');
+        }
+        assert_eq!(encoded, "A C D e gB hB w+B ");
+    }
+
+    #[test]
+    fn it_encodes_base64() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+    }
+
+    #[test]
+    fn it_makes_source_paths_relative_to_the_map() {
+        let mut map = SourceMap {
+            sources: vec![
+                String::from("/styles/main.scss"),
+                String::from("/styles/lib/_a.scss"),
+                String::from("memory.scss"),
+            ],
+            sources_content: None,
+            mappings: vec![],
+        };
+        let mut unplaced = map.clone();
+
+        map.relativize_sources(Some(Path::new("/styles/out")));
+        assert_eq!(map.sources, vec!["../main.scss", "../lib/_a.scss", "memory.scss"]);
+
+        unplaced.relativize_sources(None);
+        assert_eq!(
+            unplaced.sources,
+            vec!["file:///styles/main.scss", "file:///styles/lib/_a.scss", "memory.scss"]
+        );
+    }
+
+    #[test]
+    fn it_writes_source_map_json() {
+        let map = SourceMap {
+            sources: vec![String::from("main.scss")],
+            sources_content: Some(vec![String::from("a {\n  b: c; }")]),
+            mappings: vec![
+                Mapping { generated_line: 0, generated_column: 0, source: 0, line: 0, column: 0 },
+                Mapping { generated_line: 1, generated_column: 2, source: 0, line: 1, column: 2 },
+            ],
+        };
+        assert_eq!(
+            map.to_json(),
+            "{\"version\":3,\"sourceRoot\":\"\",\"sources\":[\"main.scss\"],\"names\":[],\
+             \"mappings\":\"AAAA;EACE\",\"sourcesContent\":[\"a {\\n  b: c; }\"]}"
+        );
+    }
+}
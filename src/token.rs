@@ -1,3 +1,5 @@
+use error::{Result, SassError, ErrorKind};
+
 use std::ops::{Add, Sub, Mul, Div, Rem};
 use std::fmt;
 
@@ -5,121 +7,215 @@ use std::fmt;
 pub enum Token {
     String(String),
     StringLiteral(String),
-    Number { value: f32, units: Option<String>, computed: bool },
+    Number { value: f64, units: Option<String>, computed: bool },
     Comment(String),
 }
 
 impl Add for Token {
-    type Output = Token;
-
-    fn add(self, other: Token) -> Token {
-        match mathy(self, other) {
-            Ok((self_value, other_value, units)) => {
-                Token::Number {
-                    value: self_value + other_value,
-                    units: units,
-                    computed: true,
-                }
-            },
-            Err(msg) => panic!("Cannot add: {}", msg),
-        }
+    type Output = Result<Token>;
+
+    fn add(self, other: Token) -> Result<Token> {
+        let (self_value, other_value, units) = mathy(self, other, "+")?;
+        let units = units.same()?;
+        Ok(Token::Number {
+            value: self_value + other_value,
+            units,
+            computed: true,
+        })
     }
 }
 
 impl Sub for Token {
-    type Output = Token;
-
-    fn sub(self, other: Token) -> Token {
-        match mathy(self, other) {
-            Ok((self_value, other_value, units)) => {
-                Token::Number {
-                    value: self_value - other_value,
-                    units: units,
-                    computed: true,
-                }
-            },
-            Err(msg) => panic!("Cannot subtract: {}", msg),
-        }
+    type Output = Result<Token>;
+
+    fn sub(self, other: Token) -> Result<Token> {
+        let (self_value, other_value, units) = mathy(self, other, "-")?;
+        let units = units.same()?;
+        Ok(Token::Number {
+            value: self_value - other_value,
+            units,
+            computed: true,
+        })
     }
 }
 
 impl Mul for Token {
-    type Output = Token;
-
-    fn mul(self, other: Token) -> Token {
-        match mathy(self, other) {
-            Ok((self_value, other_value, units)) => {
-                Token::Number {
-                    value: self_value * other_value,
-                    units: units,
-                    computed: true,
-                }
-            },
-            Err(msg) => panic!("Cannot multiply: {}", msg),
-        }
+    type Output = Result<Token>;
+
+    fn mul(self, other: Token) -> Result<Token> {
+        let (self_value, other_value, units) = mathy(self, other, "*")?;
+        let units = units.multiplied()?;
+        Ok(Token::Number {
+            value: self_value * other_value,
+            units,
+            computed: true,
+        })
     }
 }
 
 impl Div for Token {
-    type Output = Token;
-
-    fn div(self, other: Token) -> Token {
-        match mathy(self, other) {
-            Ok((self_value, other_value, units)) => {
-                Token::Number {
-                    value: self_value / other_value,
-                    units: units,
-                    computed: true,
+    type Output = Result<Token>;
+
+    fn div(self, other: Token) -> Result<Token> {
+        let (self_value, other_value, units) = mathy(self, other, "/")?;
+        let units = units.divided()?;
+        Ok(Token::Number {
+            value: self_value / other_value,
+            units,
+            computed: true,
+        })
+    }
+}
+
+impl Rem for Token {
+    type Output = Result<Token>;
+
+    fn rem(self, other: Token) -> Result<Token> {
+        let (self_value, other_value, units) = mathy(self, other, "%")?;
+        let units = units.same()?;
+        Ok(Token::Number {
+            value: self_value % other_value,
+            units,
+            computed: true,
+        })
+    }
+}
+
+// The units of both sides of an operation, to be combined depending on
+// the operation.
+struct Units(Option<String>, Option<String>);
+
+impl Units {
+    // Addition, subtraction and remainder need matching units, but a
+    // unitless number takes on the units of the other side.
+    fn same(self) -> Result<Option<String>> {
+        match self {
+            Units(Some(a), Some(b)) => {
+                if a == b {
+                    Ok(Some(a))
+                } else {
+                    Err(incompatible_units(&a, &b))
                 }
             },
-            Err(msg) => panic!("Cannot divide: {}", msg),
+            Units(a, b) => Ok(a.or(b)),
         }
     }
-}
 
-impl Rem for Token {
-    type Output = Token;
-
-    fn rem(self, other: Token) -> Token {
-        match mathy(self, other) {
-            Ok((self_value, other_value, units)) => {
-                Token::Number {
-                    value: self_value % other_value,
-                    units: units,
-                    computed: true,
+    fn multiplied(self) -> Result<Option<String>> {
+        match self {
+            Units(Some(a), Some(b)) => {
+                Err(not_css(format!("{}*{}", a, b)))
+            },
+            Units(a, b) => Ok(a.or(b)),
+        }
+    }
+
+    fn divided(self) -> Result<Option<String>> {
+        match self {
+            Units(Some(a), Some(b)) => {
+                if a == b {
+                    Ok(None)
+                } else {
+                    Err(incompatible_units(&a, &b))
                 }
             },
-            Err(msg) => panic!("Cannot find the remainder: {}", msg),
+            Units(None, Some(b)) => Err(not_css(format!("{}^-1", b))),
+            Units(a, None) => Ok(a),
         }
     }
 }
 
-fn mathy(first: Token, second: Token) -> Result<(f32, f32, Option<String>), String> {
-    match (&first, &second) {
+fn incompatible_units(first: &str, second: &str) -> SassError {
+    SassError {
+        offset: 0,
+        kind: ErrorKind::IncompatibleUnits,
+        message: format!("Incompatible units {} and {}.", first, second),
+        location: None,
+        calls: vec![],
+    }
+}
+
+fn not_css(units: String) -> SassError {
+    SassError {
+        offset: 0,
+        kind: ErrorKind::IncompatibleUnits,
+        message: format!("Units of `{}` aren't valid in CSS.", units),
+        location: None,
+        calls: vec![],
+    }
+}
+
+fn mathy(first: Token, second: Token, operator: &str) -> Result<(f64, f64, Units)> {
+    match (first, second) {
         (
-            &Token::Number {
-                value: ref first_value, units: ref first_units, ..
-            },
-            &Token::Number {
-                value: ref second_value, units: ref _second_units, ..
-            },
+            Token::Number { value: first_value, units: first_units, .. },
+            Token::Number { value: second_value, units: second_units, .. },
         ) => {
-            Ok((*first_value, *second_value, first_units.clone()))
+            Ok((first_value, second_value, Units(first_units, second_units)))
         },
-        _ => Err(format!("Cannot perform math operations on {:?} and {:?}", first, second)),
+        (first, second) => Err(SassError {
+            offset: 0,
+            kind: ErrorKind::TypeError,
+            message: format!("Undefined operation `{} {} {}`.", first, operator, second),
+            location: None,
+            calls: vec![],
+        }),
     }
 }
 
+// Numbers get rounded to the formatter's precision, 10 digits after the
+// decimal point if it has none, and `{:#}` leaves off the leading zero of
+// numbers between -1 and 1, as compressed output does.
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Token::String(ref i) => write!(f, "{}", i),
             Token::StringLiteral(ref i) => write!(f, "{}", i),
-            Token::Number { value: i, units: Some(ref u), .. } => {
-                write!(f, "{}{}", i, u)
+            Token::Number { value: i, ref units, .. } => {
+                let number = format_number(i, f.precision().unwrap_or(10), !f.alternate());
+                write!(f, "{}{}", number, units.as_ref().map_or("", |u| &u[..]))
             },
-            Token::Number { value: i, units: None, .. } => write!(f, "{}", i),
             Token::Comment(ref i) => write!(f, "{}", i),
         }
     }
 }
+
+// `value` written the way Sass writes numbers: never with an exponent,
+// without trailing zeros, and with `-0` as `0`.
+pub fn format_number(value: f64, precision: usize, leading_zero: bool) -> String {
+    if value.is_nan() {
+        return String::from("NaN")
+    }
+    if value.is_infinite() {
+        return String::from(if value > 0.0 { "Infinity" } else { "-Infinity" })
+    }
+    let factor = 10f64.powi(precision as i32);
+    let rounded = if (value * factor).is_finite() { (value * factor).round() / factor } else { value };
+    let mut number = format!("{:.*}", precision, rounded);
+    if number.contains('.') {
+        let trimmed = number.trim_end_matches('0').trim_end_matches('.').len();
+        number.truncate(trimmed);
+    }
+    if number == "-0" {
+        number = String::from("0");
+    }
+    if !leading_zero {
+        if number.starts_with("0.") {
+            number.remove(0);
+        } else if number.starts_with("-0.") {
+            number.remove(1);
+        }
+    }
+    number
+}
+
+// Formats `value` with the same precision and flags as `f`, for values
+// made up of other values.
+pub fn format_like<T: fmt::Display>(f: &fmt::Formatter, value: &T) -> String {
+    match (f.precision(), f.alternate()) {
+        (Some(precision), true) => format!("{:#.*}", precision, value),
+        (Some(precision), false) => format!("{:.*}", precision, value),
+        (None, true) => format!("{:#}", value),
+        (None, false) => format!("{}", value),
+    }
+}
@@ -5,59 +5,217 @@ use operator::Operator;
 use operator_offset::OperatorOffset;
 use error::{Result, SassError, ErrorKind};
 
+use std::collections::{HashMap, VecDeque};
 use std::str::CharIndices;
 use std::iter::Peekable;
 
+// The operators each line ending in the indented syntax stands for, keyed
+// by the offset of the line ending, or the error in the indentation of the
+// line after it.
+type LineEndings = HashMap<usize, Result<Vec<Operator>>>;
+
 pub struct Tokenizer<'a> {
     chars: Peekable<CharIndices<'a>>,
+    // Added to every offset, for text that doesn't start at offset 0 of
+    // the compilation's source files.
+    start: usize,
+    // A token handed back with `push_back`, returned before anything else.
+    pending: Option<OperatorOrToken>,
+    // Only for the indented syntax.
+    line_endings: LineEndings,
+    // For the indented syntax, the rule each `=` or `+` shorthand at the
+    // start of a line stands for, keyed by its offset.
+    shorthands: HashMap<usize, &'static str>,
+    // Operators from a line ending that haven't been returned yet.
+    line_ending_operators: VecDeque<OperatorOrToken>,
+    length: usize,
+    // `//` comments are usually thrown away, since they never make it to
+    // the CSS.
+    keep_line_comments: bool,
+    // Whether whitespace came before the last token returned.
+    after_whitespace: bool,
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
     type Item = Result<OperatorOrToken>;
 
     fn next(&mut self) -> Option<Result<OperatorOrToken>> {
+        if let Some(op_or_token) = self.pending.take() {
+            return Some(Ok(op_or_token))
+        }
+        let start = self.start;
+        self.after_whitespace = false;
         match self.parse() {
-            Ok(Some(t)) => Some(Ok(t)),
+            Ok(Some(OperatorOrToken::Token(t))) => {
+                Some(Ok(OperatorOrToken::Token(TokenOffset {
+                    offset: t.offset.map(|o| o + start),
+                    ..t
+                })))
+            },
+            Ok(Some(OperatorOrToken::Operator(o))) => {
+                Some(Ok(OperatorOrToken::Operator(OperatorOffset {
+                    offset: o.offset.map(|o| o + start),
+                    ..o
+                })))
+            },
             Ok(None) => None,
-            Err(e) => Some(Err(e)),
+            Err(e) => Some(Err(SassError { offset: e.offset + start, ..e })),
         }
     }
 }
 
 impl<'a> Tokenizer<'a> {
-    pub fn new(text: &str) -> Tokenizer {
+    pub fn new(text: &str) -> Tokenizer<'_> {
+        Tokenizer::starting_at(text, 0)
+    }
+
+    pub fn starting_at(text: &str, start: usize) -> Tokenizer<'_> {
         Tokenizer {
             chars: text.char_indices().peekable(),
+            start,
+            pending: None,
+            line_endings: HashMap::new(),
+            shorthands: HashMap::new(),
+            line_ending_operators: VecDeque::new(),
+            length: text.len(),
+            keep_line_comments: false,
+            after_whitespace: false,
+        }
+    }
+
+    pub fn keeping_line_comments(mut self) -> Tokenizer<'a> {
+        self.keep_line_comments = true;
+        self
+    }
+
+    // The value of a custom property, like the `{ a: b }` of `--c: { a: b };`,
+    // as written: everything up to the `;` or `}` that ends the
+    // declaration, which is left to be tokenized.
+    pub fn declaration_value(&mut self) -> String {
+        let mut value = String::new();
+        let mut closing = vec![];
+        let mut quote = None;
+        while let Some(&(offset, c)) = self.chars.peek() {
+            match quote {
+                Some(q) if c == q && !value.ends_with("\\") => quote = None,
+                Some(_) => {},
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '(' => closing.push(')'),
+                    '[' => closing.push(']'),
+                    '{' => closing.push('}'),
+                    ')' | ']' | '}' if closing.last() == Some(&c) => { closing.pop(); },
+                    ';' | '}' if closing.is_empty() => break,
+                    '\n' if closing.is_empty() && self.line_endings.contains_key(&offset) => break,
+                    _ => {},
+                },
+            }
+            value.push(c);
+            self.chars.next();
+        }
+        String::from(value.trim())
+    }
+
+    pub fn after_whitespace(&self) -> bool {
+        self.after_whitespace
+    }
+
+    // Whether the rest of the statement ends in a block rather than a `;`,
+    // which tells a selector like `&:hover {` from a declaration like
+    // `color:red;`. Nothing is consumed.
+    pub fn opens_block(&self) -> bool {
+        let mut chars = self.chars.clone();
+        let mut quote = None;
+        let mut previous = None;
+        while let Some((offset, c)) = chars.next() {
+            match quote {
+                Some(q) if c == q && previous != Some('\\') => quote = None,
+                Some(_) => {},
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '{' if previous == Some('#') => {
+                        for (_, c) in chars.by_ref() {
+                            if c == '}' {
+                                break;
+                            }
+                        }
+                    },
+                    '{' => return true,
+                    ';' | '}' => return false,
+                    '\n' => if let Some(operators) = self.line_endings.get(&offset) {
+                        return operators.as_ref().ok().and_then(|o| o.first()) ==
+                               Some(&Operator::LeftCurlyBrace)
+                    },
+                    _ => {},
+                },
+            }
+            previous = Some(c);
+        }
+        false
+    }
+
+    // Tokenizes the indented syntax, where line endings and indentation
+    // take the place of `;`, `{` and `}`. Those operators are produced at
+    // the end of each line, so the parser sees the same tokens as it would
+    // for SCSS.
+    pub fn indented(text: &str, start: usize) -> Tokenizer<'_> {
+        let (line_endings, shorthands) = line_endings(text);
+        Tokenizer {
+            line_endings,
+            shorthands,
+            ..Tokenizer::starting_at(text, start)
         }
     }
 
+    pub fn push_back(&mut self, op_or_token: OperatorOrToken) {
+        self.pending = Some(op_or_token);
+    }
+
     // In the string parsing sense, not the parsing-out-semantics part yet.
     // Well, there's a tiny bit of semantics, depending on how you define "meaning".
     pub fn parse(&mut self) -> Result<Option<OperatorOrToken>> {
+        if let Some(op) = self.line_ending_operators.pop_front() {
+            return Ok(Some(op))
+        }
         while let Some((char_offset, curr_char)) = self.chars.next() {
             // Skip leading whitespace
             if curr_char.is_whitespace() {
+                if curr_char == '\n' && self.line_endings.contains_key(&char_offset) {
+                    return self.end_line(char_offset)
+                }
+                self.after_whitespace = true;
                 continue;
+            } else if let Some(rule) = self.shorthands.remove(&char_offset) {
+                return Ok(Some(OperatorOrToken::Token(TokenOffset {
+                    token: Token::String(String::from(rule)),
+                    offset: Some(char_offset),
+                })))
             } else {
-                let single_char_op = Operator::from_char(curr_char);
-                if single_char_op.is_some()
-                   && !self.hyphen_starting_shit(curr_char)
-                   && !self.multiline_comment_starting(curr_char)
-                   && !self.singleline_comment_starting(curr_char) {
-                    // We already tested that single_char_token was Some.
+                let single_char_op = Operator::from_char(curr_char).filter(|_|
+                    !self.hyphen_starting_shit(curr_char)
+                    && !self.multiline_comment_starting(curr_char)
+                    && !self.singleline_comment_starting(curr_char)
+                );
+                if let Some(operator) = single_char_op {
                     return Ok(Some(OperatorOrToken::Operator(OperatorOffset {
-                        operator: single_char_op.unwrap(),
+                        operator,
                         offset: Some(char_offset),
                     })))
                 } else {
                     if self.multiline_comment_starting(curr_char) {
                         return self.multiline_comment(curr_char, char_offset)
                     } else if self.singleline_comment_starting(curr_char) {
-                        self.discard_singleline_comment();
+                        let comment = self.singleline_comment(curr_char);
+                        if self.keep_line_comments {
+                            return Ok(Some(OperatorOrToken::Token(TokenOffset {
+                                token: Token::Comment(comment),
+                                offset: Some(char_offset),
+                            })))
+                        }
                         return self.parse()
-                    } else if curr_char == '"' {
+                    } else if curr_char == '"' || curr_char == '\'' {
                         return self.string_literal(curr_char, char_offset)
-                    } else if curr_char.is_numeric() || self.hyphen_starting_number(curr_char) {
+                    } else if self.number_starting(curr_char) {
                         return self.number(curr_char, char_offset)
                     } else {
                         return self.ident(curr_char, char_offset)
@@ -65,7 +223,22 @@ impl<'a> Tokenizer<'a> {
                 }
             }
         }
-        return Ok(None)
+        let length = self.length;
+        if self.line_endings.contains_key(&length) {
+            return self.end_line(length)
+        }
+        Ok(None)
+    }
+
+    fn end_line(&mut self, offset: usize) -> Result<Option<OperatorOrToken>> {
+        let operators = self.line_endings.remove(&offset).unwrap_or(Ok(vec![]))?;
+        self.line_ending_operators.extend(operators.into_iter().map(|operator|
+            OperatorOrToken::Operator(OperatorOffset {
+                operator,
+                offset: Some(offset),
+            })
+        ));
+        Ok(self.line_ending_operators.pop_front())
     }
 
     fn hyphen_starting_shit(&mut self, curr_char: char) -> bool {
@@ -87,16 +260,41 @@ impl<'a> Tokenizer<'a> {
           && peek_char.unwrap() == '/'
     }
 
-    fn hyphen_starting_number(&mut self, curr_char: char) -> bool {
-        let peek_char = self.peek_char();
-        curr_char == '-' && peek_char.is_some() && peek_char.unwrap().is_numeric()
+    // `1`, `-1`, `.5` or `-.5`.
+    fn number_starting(&self, curr_char: char) -> bool {
+        let mut rest = self.chars.clone().map(|(_, c)| c);
+        let after_sign = if curr_char == '-' { rest.next() } else { Some(curr_char) };
+        match after_sign {
+            Some('.') => rest.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_numeric(),
+            None => false,
+        }
     }
 
-    fn peek_char(&mut self) -> Option<char> {
-        match self.chars.peek() {
-            Some(&(_, peek_char)) => Some(peek_char),
-            None => None,
+    // The `e3`, `e+3` or `e-3` after a number, if there is one.
+    fn exponent(&self) -> Option<String> {
+        let mut rest = self.chars.clone().map(|(_, c)| c);
+        let mut exponent = String::new();
+        match rest.next() {
+            Some(c @ 'e') | Some(c @ 'E') => exponent.push(c),
+            _ => return None,
         }
+        let mut next = rest.next();
+        if let Some(sign @ '+') | Some(sign @ '-') = next {
+            exponent.push(sign);
+            next = rest.next();
+        }
+        let mut digits = false;
+        while let Some(c) = next.filter(|c| c.is_ascii_digit()) {
+            exponent.push(c);
+            digits = true;
+            next = rest.next();
+        }
+        if digits { Some(exponent) } else { None }
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, peek_char)| peek_char)
     }
 
     fn ident(&mut self, curr_char: char, start: usize) -> Result<Option<OperatorOrToken>> {
@@ -114,11 +312,97 @@ impl<'a> Tokenizer<'a> {
                 self.chars.next();
             }
         }
+
+        if value == "url" && self.peek_char() == Some('(') {
+            if let Some(url) = self.url_contents() {
+                value.push_str(&url);
+            }
+        } else if is_special_function(&value) && self.peek_char() == Some('(') {
+            value.push_str(&self.special_contents());
+        } else if value.to_lowercase() == "progid" && self.peek_char() == Some(':') {
+            // An old IE filter, like `progid:DXImageTransform.Microsoft.Alpha(Opacity=80)`.
+            while let Some(peek_char) = self.peek_char() {
+                if peek_char == '(' {
+                    value.push_str(&self.special_contents());
+                    break;
+                } else if peek_char.is_whitespace() || peek_char == ';' || peek_char == '}' {
+                    break;
+                }
+                value.push(peek_char);
+                self.chars.next();
+            }
+        }
+
         Ok(Some(OperatorOrToken::Token(
             TokenOffset { token: Token::String(value), offset: Some(start) }
         )))
     }
 
+    // The contents of `url(...)` aren't Sass, so `:` and `//` in
+    // `url(http://x.com/a.png)` don't become operators or comments. Contents
+    // that can't be a plain URL, like `url($image)`, are left to be
+    // tokenized as a function call instead.
+    fn url_contents(&mut self) -> Option<String> {
+        let mut lookahead = self.chars.clone();
+        let mut contents = String::new();
+        let mut quote = None;
+        let mut quoted = false;
+        let mut interpolation_level = 0;
+
+        while let Some((_, c)) = lookahead.next() {
+            match quote {
+                Some(q) => {
+                    if c == q && !contents.ends_with("\\") {
+                        quote = None;
+                    }
+                },
+                None if c == '{' && contents.ends_with('#') => interpolation_level += 1,
+                None if c == '}' && interpolation_level > 0 => interpolation_level -= 1,
+                None if interpolation_level > 0 => {},
+                None if contents.is_empty() => {}, // the opening paren
+                None if c == ')' => {
+                    contents.push(c);
+                    self.chars = lookahead;
+                    return Some(contents)
+                },
+                None if c.is_whitespace() => {},
+                None if (c == '"' || c == '\'') && contents[1..].trim().is_empty() => {
+                    quote = Some(c);
+                    quoted = true;
+                },
+                None if is_url_char(c) && !quoted => {},
+                None => return None,
+            }
+            contents.push(c);
+        }
+        None
+    }
+
+    // Everything up to the `)` matching the `(` next, left as written apart
+    // from any `#{...}` in it, which gets filled in when it's evaluated.
+    fn special_contents(&mut self) -> String {
+        let mut contents = String::new();
+        let mut paren_level = 0;
+        let mut quote = None;
+        for (_, c) in self.chars.by_ref() {
+            contents.push(c);
+            match quote {
+                Some(q) if c == q && !contents[..contents.len() - 1].ends_with("\\") => quote = None,
+                Some(_) => {},
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == '(' => paren_level += 1,
+                None if c == ')' => {
+                    paren_level -= 1;
+                    if paren_level == 0 {
+                        break;
+                    }
+                },
+                None => {},
+            }
+        }
+        contents
+    }
+
     fn number(&mut self, curr_char: char, start: usize) -> Result<Option<OperatorOrToken>> {
         let mut value = String::new();
         value.push(curr_char);
@@ -133,6 +417,12 @@ impl<'a> Tokenizer<'a> {
                 self.chars.next();
             }
         }
+        if let Some(exponent) = self.exponent() {
+            for _ in 0..exponent.len() {
+                self.chars.next();
+            }
+            value.push_str(&exponent);
+        }
 
         let value = match value.parse() {
             Ok(v) => v,
@@ -140,9 +430,11 @@ impl<'a> Tokenizer<'a> {
                 offset: start,
                 kind: ErrorKind::TokenizerError,
                 message: format!(
-                    "Tried to parse `{}` into a f32 but failed.",
+                    "Tried to parse `{}` into a f64 but failed.",
                     value,
                 ),
+                location: None,
+                calls: vec![],
             })
         };
 
@@ -159,36 +451,50 @@ impl<'a> Tokenizer<'a> {
             }
         }
 
-        let token = if unit.len() > 0 {
-            Token::Number { value: value, units: Some(unit), computed: false }
+        let token = if !unit.is_empty() {
+            Token::Number { value, units: Some(unit), computed: false }
         } else {
-            Token::Number { value: value, units: None, computed: false }
+            Token::Number { value, units: None, computed: false }
         };
 
         Ok(Some(OperatorOrToken::Token(
-            TokenOffset { token: token, offset: Some(start) }
+            TokenOffset { token, offset: Some(start) }
         )))
     }
 
-    fn string_literal(&mut self, curr_char: char, start: usize) -> Result<Option<OperatorOrToken>> {
-        let mut value = String::new();
-        value.push(curr_char);
-
-        while let Some(peek_char) = self.peek_char() {
-            if peek_char == '"' && !value.ends_with("\\") {
-                value.push(peek_char);
-                self.chars.next();
-                break;
-            } else {
-                value.push(peek_char);
-                self.chars.next();
+    // A string in `'` or `"` quotes, given back in the quotes it'll be
+    // written out in, so `'a'` and `"a"` are the same token.
+    fn string_literal(&mut self, quote: char, start: usize) -> Result<Option<OperatorOrToken>> {
+        let mut contents = vec![];
+        loop {
+            match self.chars.next() {
+                Some((_, c)) if c == quote => break,
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, '\n')) => {}, // a line continuation
+                    Some((_, c)) if c.is_ascii_hexdigit() => {
+                        // Unicode escapes, like the `\f00c` of an icon font,
+                        // are written out as they are.
+                        let mut escape = format!("\\{}", c);
+                        while escape.len() < 7 && self.peek_char().is_some_and(|p| p.is_ascii_hexdigit()) {
+                            escape.push(self.chars.next().unwrap().1);
+                        }
+                        if self.peek_char().is_some_and(|p| p.is_whitespace()) {
+                            escape.push(self.chars.next().unwrap().1);
+                        }
+                        contents.push(StringPart::Escape(escape));
+                    },
+                    Some((_, c)) => contents.push(StringPart::Char(c)),
+                    None => return Err(unterminated_string(quote, start)),
+                },
+                Some((_, '\n')) | None => return Err(unterminated_string(quote, start)),
+                Some((_, c)) => contents.push(StringPart::Char(c)),
             }
         }
 
-        let token = Token::StringLiteral(value);
+        let token = Token::StringLiteral(quoted_string(&contents));
 
         Ok(Some(OperatorOrToken::Token(
-            TokenOffset { token: token, offset: Some(start) }
+            TokenOffset { token, offset: Some(start) }
         )))
     }
 
@@ -213,24 +519,179 @@ impl<'a> Tokenizer<'a> {
         let token = Token::Comment(value);
 
         Ok(Some(OperatorOrToken::Token(
-            TokenOffset { token: token, offset: Some(start) }
+            TokenOffset { token, offset: Some(start) }
         )))
     }
 
-    fn discard_singleline_comment(&mut self) {
+    fn singleline_comment(&mut self, curr_char: char) -> String {
+        let mut value = String::new();
+        value.push(curr_char);
+        // Leaves the line ending, which means something in the indented
+        // syntax.
         while let Some(peek_char) = self.peek_char() {
-            self.chars.next();
             if peek_char == '\n' {
                 break;
             }
+            value.push(peek_char);
+            self.chars.next();
+        }
+        String::from(value.trim_end())
+    }
+}
+
+// Works out which operators each line ending in indented syntax `text`
+// stands for: `{` before a more indented line, otherwise `;` followed by a
+// `}` for each block the next line is outdented from. A line continues onto
+// the next one after a `,` or inside a `/* */` comment. Every block is
+// indented by as much as the first one is; the line ending before a line
+// that isn't, or that's outdented to where no block is, stands for an error.
+//
+// Also finds the `=` and `+` that start lines as shorthands for `@mixin` and
+// `@include`.
+fn line_endings(text: &str) -> (LineEndings, HashMap<usize, &'static str>) {
+    // The indentation of each logical line, the offset its content starts
+    // at and the offset it ends at.
+    let mut lines: Vec<(usize, usize, usize)> = vec![];
+    let mut shorthands = HashMap::new();
+    let mut continuing = false;
+    let mut in_comment = false;
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let line_end = line_start + line.len();
+        let content = line.trim();
+        let was_continuing = continuing || in_comment;
+        let indentation = line.len() - line.trim_start().len();
+        let content_start = line_start + indentation;
+        line_start = line_end + 1;
+
+        if !was_continuing && (content.is_empty() || content.starts_with("//")) {
+            continue;
+        }
+        if let Some(open) = content.rfind("/*") {
+            in_comment = !content[open..].contains("*/");
+        } else if in_comment && content.contains("*/") {
+            in_comment = false;
+        }
+        continuing = content.ends_with(',');
+
+        if was_continuing {
+            if let Some(last) = lines.last_mut() {
+                last.2 = line_end;
+            }
+        } else {
+            let mut chars = content.chars();
+            let rule = match chars.next() {
+                Some('=') => Some("@mixin"),
+                Some('+') => Some("@include"),
+                _ => None,
+            };
+            if let (Some(rule), Some(c)) = (rule, chars.next()) {
+                if c.is_alphabetic() || c == '-' || c == '_' {
+                    shorthands.insert(content_start, rule);
+                }
+            }
+            lines.push((indentation, content_start, line_end));
+        }
+    }
+
+    let mut endings = HashMap::new();
+    let mut blocks = vec![0];
+    let mut step = None;
+    for (i, &(indentation, _, line_end)) in lines.iter().enumerate() {
+        let (next, next_start) = lines.get(i + 1).map_or((0, 0), |l| (l.0, l.1));
+        let mut operators = vec![];
+        if next > indentation {
+            let expected = indentation + *step.get_or_insert(next - indentation);
+            if next != expected {
+                endings.insert(line_end, Err(inconsistent_indentation(expected, next_start)));
+                break;
+            }
+            operators.push(Operator::LeftCurlyBrace);
+            blocks.push(next);
+        } else {
+            operators.push(Operator::Semicolon);
+            while blocks.len() > 1 && *blocks.last().unwrap() > next {
+                blocks.pop();
+                operators.push(Operator::RightCurlyBrace);
+            }
+            let open = *blocks.last().unwrap();
+            if next != open {
+                endings.insert(line_end, Err(inconsistent_indentation(open, next_start)));
+                break;
+            }
+        }
+        endings.insert(line_end, Ok(operators));
+    }
+    (endings, shorthands)
+}
+
+fn inconsistent_indentation(expected: usize, offset: usize) -> SassError {
+    SassError {
+        offset,
+        kind: ErrorKind::TokenizerError,
+        message: format!("Inconsistent indentation, expected {} spaces.", expected),
+        location: None,
+        calls: vec![],
+    }
+}
+
+enum StringPart {
+    Char(char),
+    Escape(String),
+}
+
+// Double quotes, unless the string has a double quote and no single one in
+// it, so that `'say "hi"'` doesn't need escaping.
+fn quoted_string(contents: &[StringPart]) -> String {
+    let has = |quote| contents.iter().any(|p| match *p { StringPart::Char(c) => c == quote, _ => false });
+    let quote = if has('"') && !has('\'') { '\'' } else { '"' };
+    let mut quoted = String::new();
+    quoted.push(quote);
+    for part in contents.iter() {
+        match *part {
+            StringPart::Char(c) if c == quote || c == '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            },
+            StringPart::Char(c) => quoted.push(c),
+            StringPart::Escape(ref escape) => quoted.push_str(escape),
         }
     }
+    quoted.push(quote);
+    quoted
+}
+
+fn unterminated_string(quote: char, start: usize) -> SassError {
+    SassError {
+        offset: start,
+        kind: ErrorKind::TokenizerError,
+        message: format!("Expected {}.", quote),
+        location: None,
+        calls: vec![],
+    }
+}
+
+// Functions whose arguments are plain CSS that Sass passes through, like
+// `calc(100% - 10px)` or `element(#a)`, with or without a vendor prefix.
+// Others, like `var(--a, $fallback)`, have their arguments evaluated.
+fn is_special_function(name: &str) -> bool {
+    let name = name.to_lowercase();
+    let unprefixed = match name.strip_prefix('-') {
+        Some(prefixed) => prefixed.split_once('-').map_or("", |(_, rest)| rest),
+        None => &name[..],
+    };
+    matches!(unprefixed, "calc" | "element" | "expression")
 }
 
 fn is_single_char_token(ch: char) -> bool {
     Operator::from_char(ch).is_some()
 }
 
+fn is_url_char(ch: char) -> bool {
+    ch == '!' || ch == '#' || ch == '%' || ch == '&' ||
+      ('*'..='~').contains(&ch) || ch as u32 >= 0x80
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -359,6 +820,23 @@ mod tests {
         assert_eq!(tokenizer.next(), None);
     }
 
+    #[test]
+    fn it_reads_exponents_and_leading_dots_in_numbers() {
+        let number = |value, units: Option<&str>, offset| expected_token(
+            Token::Number { value, units: units.map(String::from), computed: false }, offset
+        );
+        let mut tokenizer = Tokenizer::new("1e3 2.5e-1px 1E+2 .5 -.5 1em 1e .a");
+        assert_eq!(tokenizer.next(), number(1000.0, None, 0));
+        assert_eq!(tokenizer.next(), number(0.25, Some("px"), 4));
+        assert_eq!(tokenizer.next(), number(100.0, None, 13));
+        assert_eq!(tokenizer.next(), number(0.5, None, 18));
+        assert_eq!(tokenizer.next(), number(-0.5, None, 21));
+        assert_eq!(tokenizer.next(), number(1.0, Some("em"), 25));
+        assert_eq!(tokenizer.next(), number(1.0, Some("e"), 29));
+        assert_eq!(tokenizer.next(), expected_ident(".a", 32));
+        assert_eq!(tokenizer.next(), None);
+    }
+
     #[test]
     fn it_separates_hyphen() {
         let mut tokenizer = Tokenizer::new("font-weight -webkit -3 - 4-5 a-1 -");
@@ -428,12 +906,74 @@ mod tests {
         assert_eq!(tokenizer.next(), expected_operator(Operator::Colon, 4));
         assert_eq!(
             tokenizer.next(),
-            expected_token(Token::StringLiteral("\"hey \\\"ya\"".into()), 6)
+            expected_token(Token::StringLiteral("'hey \"ya'".into()), 6)
         );
         assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 16));
         assert_eq!(tokenizer.next(), None);
     }
 
+    #[test]
+    fn it_requotes_string_literals_with_the_quote_that_needs_no_escaping() {
+        let mut tokenizer = Tokenizer::new("'a' 'say \"hi\"' 'it\\'s' \"\\\\\" \"\\f00c\" '\\a b'");
+        assert_eq!(tokenizer.next(), expected_token(Token::StringLiteral("\"a\"".into()), 0));
+        assert_eq!(tokenizer.next(), expected_token(Token::StringLiteral("'say \"hi\"'".into()), 4));
+        assert_eq!(tokenizer.next(), expected_token(Token::StringLiteral("\"it's\"".into()), 15));
+        assert_eq!(tokenizer.next(), expected_token(Token::StringLiteral("\"\\\\\"".into()), 23));
+        assert_eq!(tokenizer.next(), expected_token(Token::StringLiteral("\"\\f00c\"".into()), 28));
+        assert_eq!(tokenizer.next(), expected_token(Token::StringLiteral("\"\\a b\"".into()), 36));
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn it_errors_on_unterminated_strings() {
+        for input in &["\"abc", "'abc\ndef'", "\"abc\\"] {
+            let mut tokenizer = Tokenizer::new(input);
+            match tokenizer.next() {
+                Some(Err(SassError { kind: ErrorKind::TokenizerError, offset: 0, .. })) => {},
+                other => panic!("Expected an unterminated string error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn it_keeps_url_contents_together() {
+        let mut tokenizer = Tokenizer::new("url(http://x.com/a.png) url( \"a b\" ) url($x)");
+        assert_eq!(tokenizer.next(), expected_ident("url(http://x.com/a.png)", 0));
+        assert_eq!(tokenizer.next(), expected_ident("url( \"a b\" )", 24));
+        assert_eq!(tokenizer.next(), expected_ident("url", 37));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::LeftParen, 40));
+        assert_eq!(tokenizer.next(), expected_ident("$x", 41));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::RightParen, 43));
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn it_keeps_special_functions_together() {
+        let mut tokenizer = Tokenizer::new(
+            "url(data:image/png;base64,iVBO=) url(#{$x}.png) calc(100% - (#{$x} / 2)) \
+             element(#a) -webkit-calc(1px+2px) progid:DXImage.Alpha(Opacity=80);"
+        );
+        assert_eq!(tokenizer.next(), expected_ident("url(data:image/png;base64,iVBO=)", 0));
+        assert_eq!(tokenizer.next(), expected_ident("url(#{$x}.png)", 33));
+        assert_eq!(tokenizer.next(), expected_ident("calc(100% - (#{$x} / 2))", 48));
+        assert_eq!(tokenizer.next(), expected_ident("element(#a)", 73));
+        assert_eq!(tokenizer.next(), expected_ident("-webkit-calc(1px+2px)", 85));
+        assert_eq!(tokenizer.next(), expected_ident("progid:DXImage.Alpha(Opacity=80)", 107));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 139));
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn it_takes_custom_property_values_as_written() {
+        let mut tokenizer = Tokenizer::new("--a:  { b: c; d: [e, f] } !important ; g");
+        assert_eq!(tokenizer.next(), expected_ident("--a", 0));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Colon, 3));
+        assert_eq!(tokenizer.declaration_value(), "{ b: c; d: [e, f] } !important");
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 37));
+        assert_eq!(tokenizer.next(), expected_ident("g", 39));
+        assert_eq!(tokenizer.next(), None);
+    }
+
     #[test]
     fn it_separates_multiline_comments() {
         let mut tokenizer = Tokenizer::new("a /* foo\nbar */ no");
@@ -450,4 +990,54 @@ mod tests {
         assert_eq!(tokenizer.next(), expected_ident("c", 18));
         assert_eq!(tokenizer.next(), None);
     }
+
+    #[test]
+    fn it_turns_indentation_into_braces_and_semicolons() {
+        let mut tokenizer = Tokenizer::indented("a\n  b: c // d\n\n  e\n    f: g\nh: i", 0);
+        assert_eq!(tokenizer.next(), expected_ident("a", 0));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::LeftCurlyBrace, 1));
+        assert_eq!(tokenizer.next(), expected_ident("b", 4));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Colon, 5));
+        assert_eq!(tokenizer.next(), expected_ident("c", 7));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 13));
+        assert_eq!(tokenizer.next(), expected_ident("e", 17));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::LeftCurlyBrace, 18));
+        assert_eq!(tokenizer.next(), expected_ident("f", 23));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Colon, 24));
+        assert_eq!(tokenizer.next(), expected_ident("g", 26));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 27));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::RightCurlyBrace, 27));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::RightCurlyBrace, 27));
+        assert_eq!(tokenizer.next(), expected_ident("h", 28));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Colon, 29));
+        assert_eq!(tokenizer.next(), expected_ident("i", 31));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 32));
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn it_errors_on_inconsistent_indentation() {
+        let mut tokenizer = Tokenizer::indented(".a\n  b: c\n   d: e\n", 0);
+        assert_eq!(tokenizer.next(), expected_ident(".a", 0));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::LeftCurlyBrace, 2));
+        assert_eq!(tokenizer.next(), expected_ident("b", 5));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Colon, 6));
+        assert_eq!(tokenizer.next(), expected_ident("c", 8));
+        assert_eq!(tokenizer.next(), Some(Err(inconsistent_indentation(4, 13))));
+
+        let mut tokenizer = Tokenizer::indented("a\n  b\n    c: d\n   e: f\n", 0);
+        let error = tokenizer.by_ref().find(|t| t.is_err());
+        assert_eq!(error, Some(Err(inconsistent_indentation(2, 18))));
+    }
+
+    #[test]
+    fn it_reads_mixin_shorthands_at_the_start_of_lines() {
+        let mut tokenizer = Tokenizer::indented("=m\n  a: b\n.c\n  +m\n  + d\n    e: f", 0);
+        assert_eq!(tokenizer.next(), expected_ident("@mixin", 0));
+        assert_eq!(tokenizer.next(), expected_ident("m", 1));
+        assert_eq!(tokenizer.nth(8), expected_ident("@include", 15));
+        assert_eq!(tokenizer.next(), expected_ident("m", 16));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Semicolon, 17));
+        assert_eq!(tokenizer.next(), expected_operator(Operator::Plus, 20));
+    }
 }